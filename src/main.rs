@@ -1,10 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, IsTerminal, Write};
 use std::time::Duration;
 use thiserror::Error;
 use url::Url;
@@ -28,10 +29,32 @@ pub enum ScraperError {
     AntiBotDetected(String),
     #[error("Network error: {0}")]
     NetworkError(String),
+    /// The connection broke off mid-exchange (request sent, body cut short or empty); unlike
+    /// other network errors these can succeed on a second try
+    #[error("Network error: {0}")]
+    Interrupted(String),
+    /// Failure before a connection was established; the kind says whether DNS, TCP or TLS failed
+    #[error("Network error: {1}")]
+    ConnectFailed(ErrorKind, String),
     #[error("Rate limited: {0}")]
     RateLimited(String),
 }
 
+/// Failure category serialized with --include-errors entries, for aggregating failure reasons
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Timeout,
+    Dns,
+    Connect,
+    Tls,
+    HttpStatus,
+    AntiBot,
+    InvalidUrl,
+    Network,
+    Other,
+}
+
 /// CLI arguments
 #[derive(Parser, Debug)]
 #[command(name = "simple-web-scraper")]
@@ -40,7 +63,8 @@ struct Args {
     /// URL(s) to scrape (can provide multiple, or use --url-file)
     urls: Vec<String>,
 
-    /// Output format: json, csv, or text
+    /// Output format: json, ndjson, csv, text, parquet, sitemap, mermaid, or urls
+    /// (parquet requires --output; ndjson crawls are written page by page)
     #[arg(short, long, default_value = "json")]
     format: String,
 
@@ -48,18 +72,70 @@ struct Args {
     #[arg(short, long, default_value = "30")]
     timeout: u64,
 
+    /// Seconds allowed for reading a response body, separate from --timeout
+    #[arg(long, value_name = "SECONDS")]
+    read_timeout: Option<u64>,
+
+    /// Seconds allowed for a whole page (fetch, retries and extraction) before it is abandoned
+    #[arg(long, value_name = "SECONDS")]
+    page_timeout: Option<u64>,
+
     /// Custom user agent
     #[arg(short, long)]
     user_agent: Option<String>,
 
+    /// Per-domain user agents, e.g. "docs.example.com=Googlebot/2.1,example.com=Chrome"
+    /// (subdomains included, most specific domain wins; other hosts use the global user agent)
+    #[arg(long, value_name = "MAP", value_parser = parse_ua_map)]
+    ua_map: Option<DomainMap>,
+
+    /// Request mobile layouts: mobile user agent plus mobile client hint headers
+    #[arg(long)]
+    mobile: bool,
+
+    /// Preferred languages sent as the Accept-Language header, e.g. "en-US,en;q=0.9"
+    #[arg(long, value_name = "LANGS")]
+    lang: Option<String>,
+
+    /// Re-extract pages archived in DIR (by --dump-dom) instead of fetching anything
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["crawl", "dump_dom"])]
+    replay: Option<String>,
+
+    /// Write each page's parsed (normalized) HTML to DIR, or to stderr when no DIR is given
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "-")]
+    dump_dom: Option<String>,
+
+    /// Only parse the first N bytes of very large pages
+    #[arg(long, value_name = "N")]
+    max_parse_bytes: Option<usize>,
+
     /// Proxy URL (e.g., http://proxy.example.com:8080)
     #[arg(short, long)]
     proxy: Option<String>,
 
+    /// Per-domain proxies, e.g. "example.com=http://p1:8080,foo.de=socks5://p2:1080"
+    /// (subdomains included, most specific domain wins; other hosts use --proxy)
+    #[arg(long, value_name = "MAP", value_parser = parse_proxy_map)]
+    proxy_map: Option<DomainMap>,
+
+    /// Only extract content from inside the first element matching this CSS selector
+    #[arg(long, value_name = "SELECTOR")]
+    root_selector: Option<String>,
+
     /// Custom CSS selector to extract (can specify multiple)
     #[arg(short, long)]
     selector: Vec<String>,
 
+    /// Named CSS selector, "name=SELECTOR"; matches are keyed by name in `named_selectors`
+    /// (can specify multiple)
+    #[arg(long, value_name = "NAME=SELECTOR")]
+    selector_name: Vec<String>,
+
+    /// Compare --selector-name values against a JSON snapshot file, report what changed
+    /// since the last run and update the snapshot
+    #[arg(long, value_name = "FILE", requires = "selector_name")]
+    diff_selectors: Option<String>,
+
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
@@ -68,15 +144,28 @@ struct Args {
     #[arg(short, long)]
     quiet: bool,
 
+    /// Report failures with their full chain of causes (e.g. the TLS or socket error
+    /// behind a request failure)
+    #[arg(long)]
+    verbose_errors: bool,
+
     /// Delay between requests in milliseconds
     #[arg(short, long, default_value = "1000")]
     delay: u64,
 
+    /// Minimum milliseconds between two requests to the same host, whatever --delay/--rps allow
+    #[arg(long, value_name = "MS")]
+    min_host_interval: Option<u64>,
+
+    /// Extra delay in milliseconds after a failed request, on top of --delay
+    #[arg(long, value_name = "MS", default_value = "0")]
+    error_delay: u64,
+
     /// Enable crawling (follow links)
     #[arg(long)]
     crawl: bool,
 
-    /// Maximum crawl depth
+    /// Maximum crawl depth (0 fetches only the start URL: no links or pagination are followed)
     #[arg(long, default_value = "2")]
     max_depth: usize,
 
@@ -84,6 +173,18 @@ struct Args {
     #[arg(long, default_value = "10")]
     max_pages: usize,
 
+    /// Maximum number of pages to fetch at each crawl depth
+    #[arg(long, value_name = "N")]
+    max_per_depth: Option<usize>,
+
+    /// Stop crawling once page bodies totalling N bytes have been downloaded
+    #[arg(long, value_name = "N")]
+    max_total_bytes: Option<u64>,
+
+    /// Treat /about and /about/ as the same page when deciding what the crawl has visited
+    #[arg(long)]
+    treat_trailing_slash_equal: bool,
+
     /// Allow crawling to specific domains (comma-separated, e.g., "example.com,docs.example.com")
     #[arg(long)]
     allow_domains: Option<String>,
@@ -96,6 +197,33 @@ struct Args {
     #[arg(long)]
     cross_domain: bool,
 
+    /// While crawling, rewrite http:// links to https:// on hosts that have already served https
+    #[arg(long)]
+    upgrade_insecure: bool,
+
+    /// Collapse pages declaring the same canonical URL into one record listing their URLs
+    #[arg(long)]
+    collapse_canonical: bool,
+
+    /// Drop a fetched page when its declared canonical URL was already stored, so tracking
+    /// URLs of the same content are kept once (crawls don't follow the duplicate's links)
+    #[arg(long)]
+    dedup_by_declared_canonical: bool,
+
+    /// Store page and link URLs canonicalized: lowercase scheme and host, no default port,
+    /// tracking parameters (utm_*, gclid, fbclid, ...) removed (pages are still fetched as given)
+    #[arg(long)]
+    canonical_urls: bool,
+
+    /// Report SEO issues per page (missing title/description, canonical problems, meta refresh,
+    /// images without alt) and duplicate titles across pages
+    #[arg(long)]
+    audit: bool,
+
+    /// After scraping, print clusters of pages that share the same (normalized) title
+    #[arg(long)]
+    report_duplicate_titles: bool,
+
     /// Extract metadata (Open Graph, meta tags)
     #[arg(long)]
     metadata: bool,
@@ -111,10 +239,271 @@ struct Args {
     /// Save each scraped page to a separate file (requires --output as prefix)
     #[arg(long)]
     output_per_page: bool,
+
+    /// Gzip-compress output files (implied by an --output ending in .gz; stdout is unaffected)
+    #[arg(long)]
+    gzip_output: bool,
+
+    /// Treat seed URLs as RSS/Atom feeds and scrape (or crawl from) the articles they list
+    #[arg(long)]
+    feed_mode: bool,
+
+    /// Crawl exactly the URLs in the site's sitemap (/sitemap.xml, or the start URL if it is
+    /// an .xml or gzip-compressed .xml.gz file) without following in-page links
+    #[arg(long, requires = "crawl", conflicts_with = "feed_mode")]
+    sitemap_only: bool,
+
+    /// Crawl from every URL given, not just the first: each seed starts at depth 0, and links
+    /// on any seed's host are in scope (one visited set and page budget)
+    #[arg(long, requires = "crawl", conflicts_with_all = ["feed_mode", "sitemap_only"])]
+    multi_seed: bool,
+
+    /// Skip pages already scraped through another seed URL (non-crawl mode)
+    #[arg(long)]
+    dedup_across_urls: bool,
+
+    /// Retry a page once with a different user agent when anti-bot protection is detected
+    #[arg(long)]
+    retry_on_anti_bot: bool,
+
+    /// Retry a page up to N times after a transient failure (timeout, failed connection,
+    /// response cut off mid-way, or an HTTP status in --retry-status)
+    #[arg(long, value_name = "N", default_value = "0")]
+    retries: usize,
+
+    /// HTTP statuses that --retries treats as transient, e.g. "429,500-504"
+    #[arg(long, value_name = "CODES", default_value = "502,503,504", value_parser = parse_status_list)]
+    retry_status: HashSet<u16>,
+
+    /// Treat an HTTP 200 with a zero-byte body as a network failure, so --retries re-fetches it
+    /// (a page that merely has no headings or paragraphs is not affected)
+    #[arg(long)]
+    retry_on_empty_body: bool,
+
+    /// Base delay before the first retry in milliseconds; doubles with each attempt, plus jitter
+    #[arg(long, value_name = "MS", default_value = "500")]
+    retry_backoff: u64,
+
+    /// Seed for randomized decisions (retry jitter) so a run can be reproduced exactly;
+    /// without it the seed comes from system entropy
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    /// Number of pages fetched at once when scraping a URL list (--delay spaces each worker's
+    /// requests; output keeps the input order)
+    #[arg(long, value_name = "N", default_value = "1")]
+    concurrency: usize,
+
+    /// With --concurrency, never have more than N requests in flight to any one host
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_per_host: Option<u64>,
+
+    /// Cap the total number of retry attempts across the whole run
+    #[arg(long, value_name = "N")]
+    retry_budget: Option<usize>,
+
+    /// Split each page's links into internal_links and external_links (crawls follow internal only)
+    #[arg(long)]
+    separate_external: bool,
+
+    /// Only report status, final URL, content type and size (HEAD request, no extraction)
+    #[arg(long, conflicts_with = "crawl")]
+    head_only: bool,
+
+    /// Record each page's ETag and Last-Modified response headers
+    #[arg(long)]
+    capture_cache_headers: bool,
+
+    /// Record the IP address of the server each page was fetched from
+    #[arg(long)]
+    capture_ip: bool,
+
+    /// Record the size in bytes of each page's decoded response body
+    #[arg(long)]
+    capture_size: bool,
+
+    /// Record failed pages in the output (with status code and error message)
+    #[arg(long)]
+    include_errors: bool,
+
+    /// Only output pages with these status codes, e.g. "200,301" or "400-599"
+    #[arg(long, value_name = "CODES")]
+    keep_status: Option<String>,
+
+    /// Rewrite candidate crawl URLs with a regex rule "PATTERN=>REPLACEMENT" (can specify multiple)
+    #[arg(long)]
+    rewrite: Vec<String>,
+
+    /// Skip crawl links whose path repeats any one segment more than N times (e.g. /a/a/a/a/)
+    #[arg(long, value_name = "N")]
+    max_repeated_segments: Option<usize>,
+
+    /// Skip crawl links whose path has more than N segments
+    #[arg(long, value_name = "N")]
+    max_path_depth: Option<usize>,
+
+    /// Only enqueue crawl links of these content types (e.g. "text/html"), judged by file extension
+    #[arg(long, value_name = "TYPES")]
+    crawl_content_types: Option<String>,
+
+    /// With --crawl-content-types, confirm extensionless links with a HEAD request
+    #[arg(long, requires = "crawl_content_types")]
+    verify_content_type: bool,
+
+    /// Upsert results into a JSON object keyed by URL in FILE, leaving other entries untouched
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output", "output_per_page", "tree"])]
+    merge_into: Option<String>,
+
+    /// Leave null and empty-list fields out of JSON output
+    #[arg(long)]
+    omit_empty: bool,
+
+    /// JSON output as one flat object per page: scalar fields, counts, and newline-joined lists
+    #[arg(long, conflicts_with = "fields")]
+    flat_json: bool,
+
+    /// With --format urls, also list every link URL found on the pages
+    #[arg(long)]
+    include_link_urls: bool,
+
+    /// Truncate titles in the CSV summary to N characters
+    #[arg(long, value_name = "N")]
+    csv_title_max: Option<usize>,
+
+    /// Print crawled pages as an indented tree (by link graph and depth) instead of --format
+    #[arg(long, conflicts_with = "output_per_page")]
+    tree: bool,
+
+    /// Print one JSON object per link ({source_url, text, url, depth}), one per line, instead
+    /// of --format
+    #[arg(long, conflicts_with_all = ["output_per_page", "tree", "merge_into"])]
+    links_only: bool,
+
+    /// Follow rel="next" pagination chains while crawling, regardless of --max-depth
+    #[arg(long)]
+    follow_pagination: bool,
+
+    /// Maximum requests per second across the whole run (token bucket; supersedes --delay)
+    #[arg(long)]
+    rps: Option<f64>,
+
+    /// Resolve relative links and images against the page's declared canonical URL
+    #[arg(long)]
+    resolve_against_canonical: bool,
+
+    /// Fail (nonzero exit) for pages without a non-empty <title>
+    #[arg(long)]
+    require_title: bool,
+
+    /// Fail (nonzero exit) for pages where this CSS selector matches nothing (can specify multiple)
+    #[arg(long, value_name = "SELECTOR")]
+    require_selector: Vec<String>,
+
+    /// Also collect CSS background-image URLs (inline styles and <style> blocks) into images
+    #[arg(long)]
+    background_images: bool,
+
+    /// Truncate link text longer than N characters (an ellipsis marks the cut)
+    #[arg(long, value_name = "N")]
+    max_link_text: Option<usize>,
+
+    /// Extract <abbr title> expansions and <time datetime> values
+    #[arg(long)]
+    semantic_inline: bool,
+
+    /// Extract inline SVG <title> text and <use>/<image> href references
+    #[arg(long)]
+    svg: bool,
+
+    /// Extract content inside <template> elements too (inert and skipped by default, but
+    /// client-rendered pages sometimes keep their real content there)
+    #[arg(long)]
+    include_templates: bool,
+
+    /// Extract email addresses and phone numbers from mailto: and tel: links
+    #[arg(long)]
+    contacts: bool,
+
+    /// Parse framework state embedded in scripts (__NEXT_DATA__, window.__INITIAL_STATE__, ...)
+    #[arg(long)]
+    extract_app_state: bool,
+
+    /// Record DOM complexity: element count and maximum nesting depth
+    #[arg(long)]
+    dom_stats: bool,
+
+    /// Record each heading's section word count (paragraphs and list items up to the next
+    /// heading of the same or a higher level)
+    #[arg(long)]
+    section_stats: bool,
+
+    /// When a page links a rel="amphtml" variant, fetch that instead and extract from it
+    #[arg(long)]
+    prefer_amp: bool,
+
+    /// List external <script src> URLs, marking those served from other hosts as third-party
+    #[arg(long)]
+    collect_scripts: bool,
+
+    /// List <link rel="stylesheet"> URLs, marking those served from other hosts as third-party
+    #[arg(long)]
+    collect_stylesheets: bool,
+
+    /// Record every <meta> name/property and content pair, in page order
+    #[arg(long)]
+    all_meta: bool,
+
+    /// Only count custom selector matches instead of collecting their text
+    #[arg(long)]
+    count_only: bool,
+
+    /// Annotate each custom selector match with its CSS path (e.g. body > div:nth-child(2) > p)
+    #[arg(long)]
+    with_dom_path: bool,
+
+    /// Drop duplicate matches within each custom selector (keeps first-seen order)
+    #[arg(long)]
+    selector_dedup: bool,
+
+    /// Stop requesting a host after consecutive responses with these statuses (e.g. "403,429")
+    #[arg(long)]
+    stop_on_status: Option<String>,
+
+    /// Consecutive matching responses from one host before --stop-on-status gives up on it
+    #[arg(long, default_value = "3")]
+    stop_on_status_threshold: usize,
+
+    /// Attach a key=value tag to every result (can specify multiple)
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    /// Run identifier attached to every result
+    #[arg(long)]
+    run_id: Option<String>,
+
+    /// Only output these fields in JSON/CSV (comma-separated, e.g. "url,title,links")
+    #[arg(long)]
+    fields: Option<String>,
+
+    /// Colorize text output: auto (only on a terminal), always, or never
+    #[arg(long, default_value = "auto", value_parser = ["auto", "always", "never"])]
+    color: String,
 }
 
+/// User agent sent when --user-agent is not given
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// User agent sent with --mobile (unless --user-agent is given)
+const MOBILE_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36";
+
+/// Alternative user agents tried by --retry-on-anti-bot
+const FALLBACK_USER_AGENTS: &[&str] = &[
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_2) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Safari/605.1.15",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0",
+];
+
 /// Metadata extracted from the page
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 struct Metadata {
     description: Option<String>,
     keywords: Option<String>,
@@ -125,6 +514,40 @@ struct Metadata {
     og_url: Option<String>,
     canonical_url: Option<String>,
     favicon: Option<String>,
+    /// Next page of a paginated series (rel="next"), resolved to absolute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    /// Previous page of a paginated series (rel="prev"), resolved to absolute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prev_url: Option<String>,
+    /// Location from geo.* meta tags and schema.org PostalAddress/GeoCoordinates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<Geo>,
+    /// preload/prefetch/preconnect/dns-prefetch <link> targets, resolved to absolute
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resource_hints: Vec<ResourceHint>,
+}
+
+/// A `<link rel>` resource hint and the URL it points at
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct ResourceHint {
+    rel: String,
+    href: String,
+}
+
+/// A raw `<meta>` tag: its name (or property, http-equiv, itemprop) and content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct MetaTag {
+    key: String,
+    value: String,
+}
+
+/// Geographic location of the page subject
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+struct Geo {
+    lat: Option<f64>,
+    lon: Option<f64>,
+    address: Option<String>,
 }
 
 /// Custom selector result
@@ -132,40 +555,238 @@ struct Metadata {
 struct CustomSelectorResult {
     selector: String,
     matches: Vec<String>,
+    /// Number of matching elements (only set in --count-only mode, where matches stays empty)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+    /// CSS path of each match, aligned with `matches` (only with --with-dom-path)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<String>,
+}
+
+/// How custom selector matches are collected
+#[derive(Debug, Clone, Copy, Default)]
+struct SelectorOptions {
+    /// Count matching elements instead of collecting their text
+    count_only: bool,
+    /// Drop repeated match texts, keeping the first occurrence
+    dedup: bool,
+    /// Record each match's CSS path
+    with_dom_path: bool,
+}
+
+impl SelectorOptions {
+    fn from_args(args: &Args) -> Self {
+        SelectorOptions {
+            count_only: args.count_only,
+            dedup: args.selector_dedup,
+            with_dom_path: args.with_dom_path,
+        }
+    }
 }
 
+/// Serialized ScrapedData field names accepted by --fields
+const SCRAPED_DATA_FIELDS: &[&str] = &[
+    "url",
+    "final_url",
+    "upgraded_from",
+    "amp_url",
+    "source_urls",
+    "status_code",
+    "etag",
+    "last_modified",
+    "content_type",
+    "content_length",
+    "content_bytes",
+    "resolved_ip",
+    "title",
+    "headings",
+    "sections",
+    "paragraphs",
+    "links",
+    "internal_links",
+    "external_links",
+    "images",
+    "primary_image",
+    "tables",
+    "code_blocks",
+    "abbreviations",
+    "times",
+    "svg",
+    "contacts",
+    "app_state",
+    "scripts",
+    "stylesheets",
+    "dom_node_count",
+    "dom_max_depth",
+    "metadata",
+    "all_meta",
+    "custom_selectors",
+    "named_selectors",
+    "depth",
+    "retries_used",
+    "error",
+    "error_kind",
+    "audit",
+    "user_data",
+    "ingested_at",
+];
+
 /// Main scraped data structure
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 struct ScrapedData {
     url: String,
+    /// URL the response was served from, when redirects changed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    final_url: Option<String>,
+    /// Original http:// URL when --upgrade-insecure fetched the https:// form instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upgraded_from: Option<String>,
+    /// AMP variant the content was extracted from (only with --prefer-amp)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amp_url: Option<String>,
+    /// Every captured URL merged into this record by --collapse-canonical
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    source_urls: Vec<String>,
     status_code: u16,
+    /// ETag response header (only captured with --capture-cache-headers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    /// Last-Modified response header (only captured with --capture-cache-headers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    /// Content-Type response header (only recorded with --head-only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    /// Content-Length response header (only recorded with --head-only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_length: Option<u64>,
+    /// Decoded response body size in bytes (only with --capture-size)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_bytes: Option<usize>,
+    /// Peer address of the connection that served the page (only with --capture-ip); behind a
+    /// CDN this is the edge, and through --proxy it is the proxy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved_ip: Option<String>,
     title: Option<String>,
     headings: Vec<String>,
+    /// Word counts per heading section (only with --section-stats)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sections: Vec<Section>,
     paragraphs: Vec<String>,
     links: Vec<Link>,
+    /// Same-host links (with --separate-external, `links` is split into these two lists)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    internal_links: Vec<Link>,
+    /// Links to other hosts (with --separate-external)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_links: Vec<Link>,
     images: Vec<Image>,
+    /// Preview image: og:image, then rel="image_src", then the largest/first content image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary_image: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tables: Vec<Table>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     code_blocks: Vec<CodeBlock>,
+    /// `<abbr title>` expansions (only with --semantic-inline)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    abbreviations: Vec<Abbreviation>,
+    /// `<time datetime>` machine-readable values (only with --semantic-inline)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    times: Vec<TimeValue>,
+    /// Inline `<svg>` graphics with their titles and references (only with --svg)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    svg: Vec<SvgGraphic>,
+    /// Emails and phone numbers from mailto:/tel: links (only with --contacts)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contacts: Vec<Contact>,
+    /// Embedded framework state keyed by script id / global name (only with --extract-app-state)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_state: Option<serde_json::Value>,
+    /// External script URLs (only with --collect-scripts)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    scripts: Vec<Asset>,
+    /// Stylesheet URLs (only with --collect-stylesheets)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stylesheets: Vec<Asset>,
+    /// Number of elements in the page (only with --dom-stats)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dom_node_count: Option<usize>,
+    /// Deepest element nesting, counting <html> as 1 (only with --dom-stats)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dom_max_depth: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     metadata: Option<Metadata>,
+    /// Every meta tag as key/value, duplicates kept (only with --all-meta)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    all_meta: Vec<MetaTag>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     custom_selectors: Vec<CustomSelectorResult>,
+    /// --selector-name matches keyed by name (a count instead of a list with --count-only)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    named_selectors: BTreeMap<String, serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     depth: Option<usize>,
+    /// Retry attempts the page needed before it succeeded, 0 for a first-try success
+    /// (only with --retries)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries_used: Option<u8>,
+    /// Why the page failed (only set for --include-errors entries)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Failure category for --include-errors entries (timeout, dns, connect, http_status, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<ErrorKind>,
+    /// SEO audit warnings (only with --audit)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    audit: Vec<String>,
+    /// --tag values and --run-id attached for provenance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_data: Option<BTreeMap<String, String>>,
+    /// When the result was produced (RFC 3339, UTC); set alongside user_data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ingested_at: Option<String>,
+    /// rel="next" target used by --follow-pagination (reported via metadata instead)
+    #[serde(skip)]
+    next_page: Option<String>,
+    /// Unmet --require-title / --require-selector expectations, reported after output
+    #[serde(skip)]
+    requirement_failures: Vec<String>,
+    /// Resolved rel="canonical" target, kept for --collapse-canonical and
+    /// --dedup-by-declared-canonical
+    #[serde(skip)]
+    canonical: Option<String>,
+    /// Pacing requested by a Retry-After header on a successful response
+    #[serde(skip)]
+    retry_after: Option<Duration>,
+    /// Size of the response body (or bodies, with --prefer-amp) downloaded for this page
+    #[serde(skip)]
+    body_bytes: u64,
+}
+
+impl ScrapedData {
+    /// Every link on the page, whether or not --separate-external split them
+    fn all_links(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter().chain(&self.internal_links).chain(&self.external_links)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Link {
     text: String,
     url: String,
+    /// Suggested filename from the `download` attribute (empty when given without a value)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Image {
     alt: String,
     src: String,
+    /// Set for CSS background images found by --background-images
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    background: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -180,6 +801,65 @@ struct CodeBlock {
     language: Option<String>,
 }
 
+/// An abbreviation and its expansion from `<abbr title="...">`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct Abbreviation {
+    text: String,
+    expansion: String,
+}
+
+/// A heading and the number of words in its section, subsections included
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct Section {
+    heading: String,
+    level: u8,
+    word_count: usize,
+}
+
+/// Human-readable text and machine value from `<time datetime="...">`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct TimeValue {
+    text: String,
+    datetime: String,
+}
+
+/// Whether a contact came from a `mailto:` or a `tel:` link
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+enum ContactKind {
+    Email,
+    Phone,
+}
+
+/// An email address or phone number from a link, as written and normalized
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct Contact {
+    kind: ContactKind,
+    raw: String,
+    /// Decoded address(es) with lowercased domains, or the phone number as "+digits"
+    normalized: String,
+    /// mailto query fields (subject, body, cc, ...) or tel parameters (ext, ...)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, String>,
+}
+
+/// An inline `<svg>`: its accessible `<title>` and the sprites/images it pulls in
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct SvgGraphic {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Absolute `<use href>` / `<image href>` targets (xlink:href included)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    references: Vec<String>,
+}
+
+/// An external resource the page loads, and whether it comes from another host
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct Asset {
+    url: String,
+    third_party: bool,
+}
+
 // ========== Helper Functions for Testability ==========
 
 /// Normalize a URL to absolute form
@@ -194,12 +874,101 @@ fn normalize_url(base_url: &Url, relative_url: &str) -> Option<String> {
     }
 }
 
-/// Check if a URL belongs to the same domain as the base domain
+/// Check if a URL belongs to the same host as the base host (host plus port, if any, so
+/// IP-literal and port-bearing start URLs compare correctly)
 fn is_same_domain(url: &str, base_domain: &str) -> bool {
-    if let Ok(parsed_url) = Url::parse(url) {
-        parsed_url.domain() == Some(base_domain)
-    } else {
-        false
+    !base_domain.is_empty() && url_host_key(url) == base_domain
+}
+
+/// The https:// form of an http:// URL whose host is known to serve https (--upgrade-insecure)
+fn upgrade_insecure(url: &str, https_hosts: &HashSet<String>) -> Option<String> {
+    let mut parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    if parsed.scheme() != "http" || !https_hosts.contains(&host) {
+        return None;
+    }
+    // An explicit :80 would otherwise survive as https://host:80
+    if parsed.port() == Some(80) {
+        parsed.set_port(None).ok()?;
+    }
+    parsed.set_scheme("https").ok()?;
+    Some(parsed.to_string())
+}
+
+/// Apply --upgrade-insecure to a crawl candidate, remembering the original URL for the result
+fn upgrade_crawl_candidate(
+    candidate: String,
+    args: &Args,
+    https_hosts: &HashSet<String>,
+    upgrades: &mut HashMap<String, String>,
+) -> String {
+    if !args.upgrade_insecure {
+        return candidate;
+    }
+    match upgrade_insecure(&candidate, https_hosts) {
+        Some(upgraded) => {
+            upgrades.entry(upgraded.clone()).or_insert(candidate);
+            upgraded
+        }
+        None => candidate,
+    }
+}
+
+/// Crawl visited-set key; with --treat-trailing-slash-equal a trailing slash is dropped from
+/// every path except the root
+fn visit_key(url: &str, trailing_slash_equal: bool) -> String {
+    if !trailing_slash_equal {
+        return url.to_string();
+    }
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.path().len() > 1 && parsed.path().ends_with('/') => {
+            let trimmed = parsed.path().trim_end_matches('/').to_string();
+            parsed.set_path(&trimmed);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Key used to detect the same page reached through different seed URLs
+/// Parses the URL so trivially different spellings (e.g. a missing trailing slash on the root) match
+fn dedup_key(url: &str) -> String {
+    Url::parse(url)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// Query parameters that only track a visit; any `utm_*` parameter is also dropped
+const TRACKING_PARAMS: &[&str] = &["gclid", "fbclid", "msclkid", "dclid", "yclid", "mc_cid", "mc_eid", "_ga"];
+
+/// Stable spelling of a URL: the parser lowercases the scheme and host and drops a default
+/// port; tracking parameters are removed, keeping the remaining ones exactly as written
+fn canonicalize_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if let Some(query) = parsed.query() {
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or_default().to_lowercase();
+                !key.is_empty() && !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+            })
+            .collect();
+        let kept = kept.join("&");
+        parsed.set_query((!kept.is_empty()).then_some(kept.as_str()));
+    }
+    parsed.to_string()
+}
+
+/// Rewrite a result's page and link URLs in canonical form, for --canonical-urls
+fn canonicalize_result_urls(data: &mut ScrapedData) {
+    data.url = canonicalize_url(&data.url);
+    if let Some(final_url) = &mut data.final_url {
+        *final_url = canonicalize_url(final_url);
+    }
+    for link in data.links.iter_mut().chain(&mut data.internal_links).chain(&mut data.external_links) {
+        link.url = canonicalize_url(&link.url);
     }
 }
 
@@ -296,26 +1065,106 @@ fn classify_http_status(status_code: u16, url: &str) -> Result<(), ScraperError>
     }
 }
 
-/// Detect common anti-bot protection patterns in HTML content
-fn detect_anti_bot_features(html: &str, title: Option<&str>) -> Option<String> {
-    // Check for Cloudflare challenge
-    if html.contains("cf-browser-verification") || html.contains("Cloudflare") && html.contains("challenge-platform") {
-        return Some("Cloudflare protection detected. The site is checking if you're a bot.".to_string());
+/// HTTP status behind a scrape failure, or 0 when it wasn't caused by a response status
+fn error_status_code(error: &anyhow::Error) -> u16 {
+    match error.downcast_ref::<ScraperError>() {
+        Some(ScraperError::HttpStatus(code, _)) => *code,
+        Some(ScraperError::RateLimited(_)) => 429,
+        _ => 0,
     }
+}
 
-    // Check for Cloudflare Ray ID (common in error pages)
-    if html.contains("Cloudflare Ray ID") || html.contains("cf-ray") {
-        return Some("Cloudflare error page detected. Access may be restricted.".to_string());
+/// Tell DNS and TLS failures apart from plain connection failures via the error's source chain
+fn connect_error_kind(error: &reqwest::Error) -> ErrorKind {
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(err) = source {
+        let message = err.to_string().to_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            return ErrorKind::Dns;
+        }
+        if message.contains("tls") || message.contains("certificate") || message.contains("handshake") {
+            return ErrorKind::Tls;
+        }
+        source = err.source();
     }
+    ErrorKind::Connect
+}
 
-    // Check for reCAPTCHA
-    if html.contains("recaptcha") || html.contains("g-recaptcha") {
-        return Some("reCAPTCHA detected. Human verification required.".to_string());
-    }
+/// Category of a scrape failure, for the `error_kind` field
+fn error_kind(error: &anyhow::Error) -> ErrorKind {
+    let reqwest_kind = |e: &reqwest::Error| {
+        if e.is_timeout() {
+            ErrorKind::Timeout
+        } else if e.is_connect() {
+            connect_error_kind(e)
+        } else if e.is_status() {
+            ErrorKind::HttpStatus
+        } else {
+            ErrorKind::Network
+        }
+    };
 
-    // Check for hCaptcha
-    if html.contains("hcaptcha") || html.contains("h-captcha") {
-        return Some("hCaptcha detected. Human verification required.".to_string());
+    match error.downcast_ref::<ScraperError>() {
+        Some(ScraperError::Timeout(_)) => ErrorKind::Timeout,
+        Some(ScraperError::ConnectFailed(kind, _)) => *kind,
+        Some(ScraperError::HttpStatus(..) | ScraperError::RateLimited(_)) => ErrorKind::HttpStatus,
+        Some(ScraperError::AntiBotDetected(_)) => ErrorKind::AntiBot,
+        Some(ScraperError::InvalidUrl(_)) => ErrorKind::InvalidUrl,
+        Some(ScraperError::NetworkError(_) | ScraperError::Interrupted(_)) => ErrorKind::Network,
+        Some(ScraperError::HttpError(e)) => reqwest_kind(e),
+        Some(ScraperError::InvalidSelector(_) | ScraperError::DepthExceeded(_)) => ErrorKind::Other,
+        // --head-only and other paths can surface reqwest/url errors directly
+        None => match (error.downcast_ref::<reqwest::Error>(), error.downcast_ref::<url::ParseError>()) {
+            (Some(e), _) => reqwest_kind(e),
+            (None, Some(_)) => ErrorKind::InvalidUrl,
+            (None, None) => ErrorKind::Other,
+        },
+    }
+}
+
+/// Build the output entry recorded for a failed page under --include-errors
+/// Uses the HTTP status when the failure came from one, otherwise 0
+fn error_result(url: &str, error: &anyhow::Error, depth: Option<usize>) -> ScrapedData {
+    ScrapedData {
+        url: url.to_string(),
+        status_code: error_status_code(error),
+        depth,
+        error: Some(error.to_string()),
+        error_kind: Some(error_kind(error)),
+        ..Default::default()
+    }
+}
+
+/// Message for a failed page: the top-level error, or with --verbose-errors every cause
+/// in its chain ("outer: cause: root cause")
+fn error_message(error: &anyhow::Error, verbose: bool) -> String {
+    if verbose {
+        format!("{:#}", error)
+    } else {
+        error.to_string()
+    }
+}
+
+/// Detect common anti-bot protection patterns in HTML content
+fn detect_anti_bot_features(html: &str, title: Option<&str>) -> Option<String> {
+    // Check for Cloudflare challenge
+    if html.contains("cf-browser-verification") || html.contains("Cloudflare") && html.contains("challenge-platform") {
+        return Some("Cloudflare protection detected. The site is checking if you're a bot.".to_string());
+    }
+
+    // Check for Cloudflare Ray ID (common in error pages)
+    if html.contains("Cloudflare Ray ID") || html.contains("cf-ray") {
+        return Some("Cloudflare error page detected. Access may be restricted.".to_string());
+    }
+
+    // Check for reCAPTCHA
+    if html.contains("recaptcha") || html.contains("g-recaptcha") {
+        return Some("reCAPTCHA detected. Human verification required.".to_string());
+    }
+
+    // Check for hCaptcha
+    if html.contains("hcaptcha") || html.contains("h-captcha") {
+        return Some("hCaptcha detected. Human verification required.".to_string());
     }
 
     // Check for common bot detection services
@@ -361,19 +1210,32 @@ fn extract_links(document: &Html, base_url: &Url) -> Vec<Link> {
             let href = el.value().attr("href")?;
             let text = el.text().collect::<String>().trim().to_string();
             let absolute_url = normalize_url(base_url, href)?;
+            let download = el.value().attr("download").map(|d| d.trim().to_string());
 
+            // Icon-only links: fall back to their accessible label, then the raw href
             Some(Link {
                 text: if text.is_empty() {
-                    href.to_string()
+                    accessible_label(el.value()).unwrap_or_else(|| href.to_string())
                 } else {
                     text
                 },
                 url: absolute_url,
+                download,
             })
         })
         .collect()
 }
 
+/// Non-empty `aria-label`, else `title`, used when an element has no visible text or alt
+fn accessible_label(element: &scraper::node::Element) -> Option<String> {
+    ["aria-label", "title"]
+        .iter()
+        .filter_map(|name| element.attr(name))
+        .map(str::trim)
+        .find(|label| !label.is_empty())
+        .map(str::to_string)
+}
+
 /// Extract and normalize images from an HTML document
 fn extract_images(document: &Html, base_url: &Url) -> Vec<Image> {
     let img_selector = Selector::parse("img").unwrap();
@@ -381,17 +1243,158 @@ fn extract_images(document: &Html, base_url: &Url) -> Vec<Image> {
         .select(&img_selector)
         .filter_map(|el| {
             let src = el.value().attr("src")?;
-            let alt = el.value().attr("alt").unwrap_or("").to_string();
+            let alt = el
+                .value()
+                .attr("alt")
+                .map(str::trim)
+                .filter(|alt| !alt.is_empty())
+                .map(str::to_string)
+                .or_else(|| accessible_label(el.value()))
+                .unwrap_or_default();
             let absolute_src = normalize_url(base_url, src)?;
 
             Some(Image {
                 alt,
                 src: absolute_src,
+                background: false,
             })
         })
         .collect()
 }
 
+/// Split links into those on the page's own host and those pointing elsewhere
+fn split_external_links(links: Vec<Link>, page_url: &Url) -> (Vec<Link>, Vec<Link>) {
+    let page_host = page_url.host_str().map(str::to_lowercase);
+    links.into_iter().partition(|link| {
+        Url::parse(&link.url)
+            .ok()
+            .is_some_and(|u| u.host_str().map(str::to_lowercase) == page_host)
+    })
+}
+
+/// Extract CSS background images from inline `style` attributes and `<style>` blocks
+fn extract_background_images(document: &Html, base_url: &Url) -> Vec<Image> {
+    let styled_selector = Selector::parse("[style]").unwrap();
+    let style_block_selector = Selector::parse("style").unwrap();
+    // url(...) inside a background or background-image declaration
+    let background_re = regex::Regex::new(
+        r#"(?i)background(?:-image)?\s*:[^;}]*?url\(\s*['"]?([^'")]+?)['"]?\s*\)"#,
+    )
+    .unwrap();
+
+    let css_sources = document
+        .select(&styled_selector)
+        .filter_map(|el| el.value().attr("style").map(str::to_string))
+        .chain(document.select(&style_block_selector).map(|el| el.text().collect::<String>()));
+
+    let mut seen = HashSet::new();
+    let mut images = Vec::new();
+    for css in css_sources {
+        for capture in background_re.captures_iter(&css) {
+            let src = capture[1].trim();
+            if src.starts_with("data:") {
+                continue;
+            }
+            if let Some(absolute_src) = normalize_url(base_url, src) {
+                if seen.insert(absolute_src.clone()) {
+                    images.push(Image {
+                        alt: String::new(),
+                        src: absolute_src,
+                        background: true,
+                    });
+                }
+            }
+        }
+    }
+    images
+}
+
+/// Pick a single preview image: og:image, then <link rel="image_src">, then the largest
+/// content image by declared width × height, falling back to the first one
+fn extract_primary_image(document: &Html, base_url: &Url) -> Option<String> {
+    let og_selector = Selector::parse(r#"meta[property="og:image"], meta[name="og:image"]"#).unwrap();
+    let image_src_selector = Selector::parse(r#"link[rel="image_src"]"#).unwrap();
+    let img_selector = Selector::parse("img[src]").unwrap();
+
+    let declared = document
+        .select(&og_selector)
+        .filter_map(|el| el.value().attr("content"))
+        .chain(document.select(&image_src_selector).filter_map(|el| el.value().attr("href")))
+        .map(str::trim)
+        .find(|src| !src.is_empty());
+    if let Some(src) = declared {
+        return normalize_url(base_url, src);
+    }
+
+    let dimension = |el: &scraper::ElementRef, name: &str| {
+        el.value().attr(name).and_then(|v| v.trim().trim_end_matches("px").parse::<u64>().ok())
+    };
+
+    let mut first = None;
+    let mut largest: Option<(u64, &str)> = None;
+    for el in document.select(&img_selector) {
+        let src = el.value().attr("src").unwrap_or("").trim();
+        if src.is_empty() || src.starts_with("data:") {
+            continue;
+        }
+        let area = dimension(&el, "width").zip(dimension(&el, "height")).map(|(w, h)| w * h);
+        // Tracking pixels and spacers are never the subject of the page
+        if area.is_some_and(|a| a <= 1) {
+            continue;
+        }
+
+        first.get_or_insert(src);
+        if let Some(area) = area {
+            if largest.is_none_or(|(best, _)| area > best) {
+                largest = Some((area, src));
+            }
+        }
+    }
+
+    largest.map(|(_, src)| src).or(first).and_then(|src| normalize_url(base_url, src))
+}
+
+/// Extract the declared canonical URL, resolved against the page URL
+fn extract_canonical_url(document: &Html, base_url: &Url) -> Option<Url> {
+    let canonical_selector = Selector::parse("link[rel]").unwrap();
+    document
+        .select(&canonical_selector)
+        .find(|el| {
+            el.value()
+                .attr("rel")
+                .is_some_and(|rel| rel.eq_ignore_ascii_case("canonical"))
+        })
+        .and_then(|el| el.value().attr("href"))
+        .and_then(|href| base_url.join(href.trim()).ok())
+}
+
+/// Extract rel="next" / rel="prev" pagination targets from <link> and <a> elements
+/// Returns (next, prev), each resolved to absolute
+fn extract_pagination(document: &Html, base_url: &Url) -> (Option<String>, Option<String>) {
+    let rel_selector = Selector::parse("link[rel], a[rel]").unwrap();
+    let mut next = None;
+    let mut prev = None;
+
+    for element in document.select(&rel_selector) {
+        let (Some(rel), Some(href)) = (element.value().attr("rel"), element.value().attr("href"))
+        else {
+            continue;
+        };
+
+        for token in rel.split_whitespace() {
+            if next.is_none() && token.eq_ignore_ascii_case("next") {
+                next = normalize_url(base_url, href.trim());
+            } else if prev.is_none()
+                && (token.eq_ignore_ascii_case("prev") || token.eq_ignore_ascii_case("previous"))
+            {
+                prev = normalize_url(base_url, href.trim());
+            }
+        }
+    }
+
+    (next, prev)
+}
+
 /// Extract title from an HTML document
 fn extract_title(document: &Html) -> Option<String> {
     let title_selector = Selector::parse("title").unwrap();
@@ -426,6 +1429,11 @@ fn extract_paragraphs(document: &Html) -> Vec<String> {
         .collect()
 }
 
+/// Collapse runs of whitespace (including newlines and indentation) to single spaces and trim
+fn clean_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 /// Extract all tables from an HTML document
 fn extract_tables(document: &Html) -> Vec<Table> {
     let table_selector = Selector::parse("table").unwrap();
@@ -444,7 +1452,7 @@ fn extract_tables(document: &Html) -> Vec<Table> {
             // Extract all headers
             let headers: Vec<String> = table_html
                 .select(&th_selector)
-                .map(|th| th.text().collect::<String>().trim().to_string())
+                .map(|th| clean_text(&th.text().collect::<String>()))
                 .filter(|text| !text.is_empty())
                 .collect();
 
@@ -454,7 +1462,7 @@ fn extract_tables(document: &Html) -> Vec<Table> {
                 .filter_map(|tr| {
                     let cells: Vec<String> = tr
                         .select(&td_selector)
-                        .map(|td| td.text().collect::<String>().trim().to_string())
+                        .map(|td| clean_text(&td.text().collect::<String>()))
                         .collect();
 
                     if cells.is_empty() {
@@ -475,6 +1483,338 @@ fn extract_tables(document: &Html) -> Vec<Table> {
         .collect()
 }
 
+/// Extract `<abbr title>` expansions and `<time datetime>` values, which plain text loses
+fn extract_semantic_inline(document: &Html) -> (Vec<Abbreviation>, Vec<TimeValue>) {
+    let abbr_selector = Selector::parse("abbr[title]").unwrap();
+    let time_selector = Selector::parse("time").unwrap();
+    let element_text = |el: scraper::ElementRef| el.text().collect::<String>().trim().to_string();
+
+    let abbreviations = document
+        .select(&abbr_selector)
+        .filter_map(|el| {
+            let expansion = el.value().attr("title")?.trim().to_string();
+            let text = element_text(el);
+            (!text.is_empty() && !expansion.is_empty()).then_some(Abbreviation { text, expansion })
+        })
+        .collect();
+
+    // Without a datetime attribute the element's own text must be the machine value
+    let times = document
+        .select(&time_selector)
+        .filter_map(|el| {
+            let text = element_text(el);
+            let datetime = el
+                .value()
+                .attr("datetime")
+                .map(|d| d.trim().to_string())
+                .unwrap_or_else(|| text.clone());
+            (!datetime.is_empty()).then_some(TimeValue { text, datetime })
+        })
+        .collect();
+
+    (abbreviations, times)
+}
+
+/// Extract contacts from `mailto:` and `tel:` links, first occurrence of each address only
+fn extract_contacts(document: &Html) -> Vec<Contact> {
+    let link_selector = Selector::parse("a[href]").unwrap();
+    let mut seen = HashSet::new();
+
+    document
+        .select(&link_selector)
+        .filter_map(|el| parse_contact_href(el.value().attr("href")?.trim()))
+        .filter(|contact| seen.insert((contact.kind, contact.normalized.clone())))
+        .collect()
+}
+
+/// Parse a `mailto:` or `tel:` href; other schemes (and ones without an address) give None
+fn parse_contact_href(href: &str) -> Option<Contact> {
+    let (scheme, rest) = href.split_once(':')?;
+
+    if scheme.eq_ignore_ascii_case("mailto") {
+        // mailto:addr1,addr2?subject=...&body=...
+        let (addresses, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let normalized = percent_decode(addresses)
+            .split(',')
+            .map(|address| match address.trim().rsplit_once('@') {
+                Some((local, domain)) => format!("{}@{}", local, domain.to_lowercase()),
+                None => address.trim().to_string(),
+            })
+            .filter(|address| !address.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        let params = url::form_urlencoded::parse(query.as_bytes())
+            .map(|(key, value)| (key.to_lowercase(), value.into_owned()))
+            .collect();
+        (!normalized.is_empty()).then(|| Contact {
+            kind: ContactKind::Email,
+            raw: href.to_string(),
+            normalized,
+            params,
+        })
+    } else if scheme.eq_ignore_ascii_case("tel") {
+        // tel:+1-555-123-4567;ext=89 (RFC 3966): separators dropped, the leading + kept
+        let mut parts = rest.split(';');
+        let number = percent_decode(parts.next().unwrap_or_default());
+        let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+        let prefix = if number.trim_start().starts_with('+') { "+" } else { "" };
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.to_lowercase(), percent_decode(value)))
+            .collect();
+        (!digits.is_empty()).then(|| Contact {
+            kind: ContactKind::Phone,
+            raw: href.to_string(),
+            normalized: format!("{}{}", prefix, digits),
+            params,
+        })
+    } else {
+        None
+    }
+}
+
+/// Decode %XX escapes (invalid escapes are kept as-is)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Extract outermost inline `<svg>` elements' `<title>` text and `<use>`/`<image>` references
+/// Graphics with neither are skipped
+fn extract_svg(document: &Html, base_url: &Url) -> Vec<SvgGraphic> {
+    let svg_selector = Selector::parse("svg").unwrap();
+    let reference_selector = Selector::parse("use, image").unwrap();
+
+    document
+        .select(&svg_selector)
+        // Nested <svg> content is reported as part of its outermost graphic
+        .filter(|svg| {
+            !svg.ancestors()
+                .filter_map(scraper::ElementRef::wrap)
+                .any(|el| el.value().name() == "svg")
+        })
+        .filter_map(|svg| {
+            let title = svg
+                .children()
+                .filter_map(scraper::ElementRef::wrap)
+                .find(|el| el.value().name() == "title")
+                .map(|el| el.text().collect::<Vec<_>>().join(" "))
+                .map(|text| clean_text(&text))
+                .filter(|text| !text.is_empty());
+
+            // Match on the local name so both href and xlink:href are picked up
+            let mut references: Vec<String> = Vec::new();
+            for el in svg.select(&reference_selector) {
+                let href = el
+                    .value()
+                    .attrs()
+                    .find(|(name, _)| *name == "href")
+                    .map(|(_, value)| value.trim());
+                if let Some(absolute) = href
+                    .filter(|href| !href.is_empty())
+                    .and_then(|href| normalize_url(base_url, href))
+                {
+                    if !references.contains(&absolute) {
+                        references.push(absolute);
+                    }
+                }
+            }
+
+            (title.is_some() || !references.is_empty()).then_some(SvgGraphic { title, references })
+        })
+        .collect()
+}
+
+/// `<script id>`s whose JSON body is a framework's serialized page data
+const APP_STATE_SCRIPT_IDS: &[&str] = &["__NEXT_DATA__", "__NUXT_DATA__", "__APOLLO_STATE__"];
+
+/// `window.X = {...}` globals that hold a serialized store
+const APP_STATE_GLOBALS: &[&str] = &["__INITIAL_STATE__", "__PRELOADED_STATE__", "__APOLLO_STATE__"];
+
+/// Collect embedded app state (Next.js __NEXT_DATA__, Redux window.__INITIAL_STATE__, ...)
+/// into an object keyed by where it was found; None when the page has none
+fn extract_app_state(document: &Html) -> Option<serde_json::Value> {
+    let script_selector = Selector::parse("script").unwrap();
+    let global_re = regex::Regex::new(r"window\.(__[A-Za-z_]+__)\s*=\s*").unwrap();
+    let mut state = serde_json::Map::new();
+
+    for script in document.select(&script_selector) {
+        let body = script.text().collect::<String>();
+
+        if let Some(id) = script.value().id().filter(|id| APP_STATE_SCRIPT_IDS.contains(id)) {
+            match serde_json::from_str(body.trim()) {
+                Ok(value) => {
+                    state.insert(id.to_string(), value);
+                }
+                Err(e) => log::debug!("Skipping unparsable <script id=\"{}\">: {}", id, e),
+            }
+            continue;
+        }
+
+        for caps in global_re.captures_iter(&body) {
+            let name = &caps[1];
+            if !APP_STATE_GLOBALS.contains(&name) || state.contains_key(name) {
+                continue;
+            }
+            // Parse just the literal after `=`; whatever follows it (`;`, more code) is ignored
+            let literal = &body[caps.get(0).unwrap().end()..];
+            if let Some(Ok(value)) = serde_json::Deserializer::from_str(literal)
+                .into_iter::<serde_json::Value>()
+                .next()
+            {
+                state.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    (!state.is_empty()).then_some(serde_json::Value::Object(state))
+}
+
+/// Class names highlighters give their line-number gutters, which aren't part of the code
+const LINE_NUMBER_CLASSES: &[&str] = &["line-numbers-rows", "hljs-ln-numbers", "linenos", "lineno", "line-number"];
+
+/// Source text of a code element as it renders: highlighter `<span>`s are concatenated as-is,
+/// `<br>` and per-line wrappers (`<div>`, `.line`, table rows) become newlines, and
+/// line-number gutters are dropped
+fn code_text(element: scraper::ElementRef) -> String {
+    fn push_text(element: scraper::ElementRef, out: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                scraper::Node::Text(text) => out.push_str(text),
+                scraper::Node::Element(el) => {
+                    if el.name() == "br" {
+                        out.push('\n');
+                        continue;
+                    }
+                    if el.classes().any(|c| LINE_NUMBER_CLASSES.contains(&c)) {
+                        continue;
+                    }
+                    let is_line = matches!(el.name(), "div" | "p" | "li" | "tr")
+                        || el.classes().any(|c| c == "line" || c == "code-line");
+                    if is_line && !out.is_empty() && !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    if let Some(child) = scraper::ElementRef::wrap(child) {
+                        push_text(child, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = String::new();
+    push_text(element, &mut out);
+    out
+}
+
+/// Language declared on a code or pre element: a "language-rust" / "lang-python" class,
+/// else a data-lang attribute
+fn code_language(element: &scraper::node::Element) -> Option<String> {
+    element
+        .classes()
+        .find_map(|c| c.strip_prefix("language-").or_else(|| c.strip_prefix("lang-")))
+        .or_else(|| element.attr("data-lang").map(str::trim))
+        .filter(|language| !language.is_empty())
+        .map(str::to_string)
+}
+
+/// Stylesheet links, matching the rel token case-insensitively (rel="Stylesheet preload" counts)
+const STYLESHEET_SELECTOR: &str = r#"link[rel~="stylesheet" i]"#;
+
+/// Collect the `attr` URL of every element matching `selector`, resolved absolute and
+/// deduplicated; anything not on the page's own host is third-party
+fn extract_assets(document: &Html, selector: &str, attr: &str, page_url: &Url) -> Vec<Asset> {
+    let selector = Selector::parse(selector).unwrap();
+    let page_host = page_url.host_str().map(str::to_lowercase);
+    let mut assets: Vec<Asset> = Vec::new();
+
+    for element in document.select(&selector) {
+        let Some(url) = element
+            .value()
+            .attr(attr)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .and_then(|v| normalize_url(page_url, v))
+        else {
+            continue;
+        };
+        if assets.iter().any(|asset| asset.url == url) {
+            continue;
+        }
+        let host = Url::parse(&url).ok().and_then(|u| u.host_str().map(str::to_lowercase));
+        assets.push(Asset { third_party: host != page_host, url });
+    }
+
+    assets
+}
+
+/// Walk headings, paragraphs and list items in document order, crediting each block's words to
+/// every open section; a heading closes the open sections of its level or deeper
+/// Text before the first heading belongs to no section
+fn extract_sections(document: &Html) -> Vec<Section> {
+    let selector = Selector::parse("h1, h2, h3, h4, h5, h6, p, li").unwrap();
+    let mut sections: Vec<Section> = Vec::new();
+    // Indices into `sections` of the headings whose sections are still open
+    let mut open: Vec<usize> = Vec::new();
+
+    for el in document.select(&selector) {
+        let name = el.value().name();
+        if let Some(level) = name.strip_prefix('h').and_then(|l| l.parse::<u8>().ok()) {
+            let heading = clean_text(&el.text().collect::<String>());
+            if heading.is_empty() {
+                continue;
+            }
+            open.retain(|&i| sections[i].level < level);
+            open.push(sections.len());
+            sections.push(Section { heading, level, word_count: 0 });
+            continue;
+        }
+
+        // A list item's paragraphs (or a nested list) are counted once, by the outermost block
+        if el.ancestors().filter_map(scraper::ElementRef::wrap).any(|a| matches!(a.value().name(), "p" | "li")) {
+            continue;
+        }
+        let words = el.text().flat_map(str::split_whitespace).count();
+        for &i in &open {
+            sections[i].word_count += words;
+        }
+    }
+    sections
+}
+
+/// Element count and maximum element nesting depth of the whole document (html = depth 1)
+fn dom_stats(document: &Html) -> (usize, usize) {
+    let mut count = 0;
+    let mut max_depth = 0;
+    for node in document.root_element().descendants() {
+        if node.value().is_element() {
+            count += 1;
+            let depth = node.ancestors().filter(|a| a.value().is_element()).count() + 1;
+            max_depth = max_depth.max(depth);
+        }
+    }
+    (count, max_depth)
+}
+
 /// Extract all code blocks from an HTML document
 fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
     let mut code_blocks = Vec::new();
@@ -490,22 +1830,9 @@ fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
         if !code_elements.is_empty() {
             // <pre><code> pattern
             for code in code_elements {
-                let content = code.text().collect::<String>();
-                let language = code
-                    .value()
-                    .attr("class")
-                    .and_then(|classes| {
-                        // Extract language from class like "language-rust" or "lang-python"
-                        classes
-                            .split_whitespace()
-                            .find(|c| c.starts_with("language-") || c.starts_with("lang-"))
-                            .map(|c| {
-                                c.strip_prefix("language-")
-                                    .or_else(|| c.strip_prefix("lang-"))
-                                    .unwrap_or(c)
-                                    .to_string()
-                            })
-                    });
+                let content = code_text(code);
+                // Highlighters put the language on either element
+                let language = code_language(code.value()).or_else(|| code_language(pre.value()));
 
                 if !content.trim().is_empty() {
                     code_blocks.push(CodeBlock { content, language });
@@ -513,11 +1840,11 @@ fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
             }
         } else {
             // Just <pre> without <code>
-            let content = pre.text().collect::<String>();
+            let content = code_text(pre);
             if !content.trim().is_empty() {
                 code_blocks.push(CodeBlock {
                     content,
-                    language: None,
+                    language: code_language(pre.value()),
                 });
             }
         }
@@ -539,22 +1866,9 @@ fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
         }
 
         if !is_inside_pre {
-            let content = code.text().collect::<String>();
+            let content = code_text(code);
             if !content.trim().is_empty() {
-                let language = code
-                    .value()
-                    .attr("class")
-                    .and_then(|classes| {
-                        classes
-                            .split_whitespace()
-                            .find(|c| c.starts_with("language-") || c.starts_with("lang-"))
-                            .map(|c| {
-                                c.strip_prefix("language-")
-                                    .or_else(|| c.strip_prefix("lang-"))
-                                    .unwrap_or(c)
-                                    .to_string()
-                            })
-                    });
+                let language = code_language(code.value());
 
                 code_blocks.push(CodeBlock { content, language });
             }
@@ -568,17 +1882,36 @@ fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
 fn process_custom_selectors(
     document: &Html,
     selectors: &[String],
+    options: SelectorOptions,
 ) -> Result<Vec<CustomSelectorResult>> {
     let mut results = Vec::new();
 
     for selector_str in selectors {
         match Selector::parse(selector_str) {
+            Ok(selector) if options.count_only => {
+                let count = document.select(&selector).count();
+                log::debug!("Custom selector '{}' matched {} elements", selector_str, count);
+
+                results.push(CustomSelectorResult {
+                    selector: selector_str.clone(),
+                    matches: Vec::new(),
+                    count: Some(count),
+                    paths: Vec::new(),
+                });
+            }
             Ok(selector) => {
-                let matches: Vec<String> = document
+                let mut seen = HashSet::new();
+                let (matches, paths): (Vec<String>, Vec<String>) = document
                     .select(&selector)
-                    .map(|el| el.text().collect::<String>().trim().to_string())
-                    .filter(|text| !text.is_empty())
-                    .collect();
+                    .map(|el| (el.text().collect::<String>().trim().to_string(), el))
+                    .filter(|(text, _)| !text.is_empty())
+                    .filter(|(text, _)| !options.dedup || seen.insert(text.clone()))
+                    .map(|(text, el)| {
+                        let path = if options.with_dom_path { dom_path(el) } else { String::new() };
+                        (text, path)
+                    })
+                    .unzip();
+                let paths = if options.with_dom_path { paths } else { Vec::new() };
 
                 log::debug!(
                     "Custom selector '{}' found {} matches",
@@ -589,6 +1922,8 @@ fn process_custom_selectors(
                 results.push(CustomSelectorResult {
                     selector: selector_str.clone(),
                     matches,
+                    count: None,
+                    paths,
                 });
             }
             Err(e) => {
@@ -605,6 +1940,155 @@ fn process_custom_selectors(
     Ok(results)
 }
 
+/// Parse --selector-name "name=SELECTOR" specs into (name, selector) pairs
+/// Names must be unique so each maps to one key in the output
+fn parse_named_selectors(specs: &[String]) -> Result<Vec<(String, String)>> {
+    let mut named: Vec<(String, String)> = Vec::new();
+    for spec in specs {
+        let (name, selector) = spec
+            .split_once('=')
+            .map(|(n, s)| (n.trim(), s.trim()))
+            .filter(|(n, s)| !n.is_empty() && !s.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Invalid --selector-name '{}', expected NAME=SELECTOR", spec)
+            })?;
+        if named.iter().any(|(existing, _)| existing == name) {
+            return Err(anyhow::anyhow!("Duplicate --selector-name '{}'", name));
+        }
+        Selector::parse(selector)
+            .map_err(|e| ScraperError::InvalidSelector(format!("'{}': {}", selector, e)))?;
+        named.push((name.to_string(), selector.to_string()));
+    }
+    Ok(named)
+}
+
+/// Run --selector-name selectors, keying each one's matches (or count) by its name
+fn process_named_selectors(
+    document: &Html,
+    specs: &[String],
+    args: &Args,
+) -> Result<BTreeMap<String, serde_json::Value>> {
+    let named = parse_named_selectors(specs)?;
+    let selectors: Vec<String> = named.iter().map(|(_, selector)| selector.clone()).collect();
+    let results = process_custom_selectors(document, &selectors, SelectorOptions::from_args(args))?;
+
+    Ok(named
+        .into_iter()
+        .zip(results)
+        .map(|((name, _), result)| {
+            let value = match result.count {
+                Some(count) => serde_json::json!(count),
+                None => serde_json::json!(result.matches),
+            };
+            (name, value)
+        })
+        .collect())
+}
+
+/// Unique CSS path of an element, e.g. `html > body > div:nth-child(2) > p`
+/// `:nth-child` is only added where the parent has more than one element child (and never to
+/// html/head/body)
+fn dom_path(element: scraper::ElementRef) -> String {
+    let mut parts = Vec::new();
+    let mut current = Some(element);
+
+    while let Some(el) = current {
+        let name = el.value().name().to_string();
+        let parent = el.parent().and_then(scraper::ElementRef::wrap);
+        let part = match parent {
+            // These occur once per document, so their names are already unique
+            _ if matches!(name.as_str(), "html" | "head" | "body") => name,
+            Some(parent) => {
+                let siblings: Vec<_> = parent.children().filter_map(scraper::ElementRef::wrap).collect();
+                match siblings.iter().position(|sibling| sibling.id() == el.id()) {
+                    Some(index) if siblings.len() > 1 => format!("{}:nth-child({})", name, index + 1),
+                    _ => name,
+                }
+            }
+            None => name,
+        };
+        parts.push(part);
+        current = parent;
+    }
+
+    parts.reverse();
+    parts.join(" > ")
+}
+
+/// Build the provenance map from --tag key=value pairs and --run-id
+/// Returns None when neither is given
+fn parse_user_data(tags: &[String], run_id: Option<&str>) -> Result<Option<BTreeMap<String, String>>> {
+    if tags.is_empty() && run_id.is_none() {
+        return Ok(None);
+    }
+
+    let mut user_data = BTreeMap::new();
+    for tag in tags {
+        let (key, value) = tag
+            .split_once('=')
+            .filter(|(key, _)| !key.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid tag '{}': expected key=value", tag))?;
+        user_data.insert(key.trim().to_string(), value.trim().to_string());
+    }
+    if let Some(run_id) = run_id {
+        user_data.insert("run_id".to_string(), run_id.to_string());
+    }
+
+    Ok(Some(user_data))
+}
+
+/// Format a timestamp as RFC 3339 in UTC (e.g. "2024-01-31T12:00:00Z")
+fn format_rfc3339(time: std::time::SystemTime) -> String {
+    let secs = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        (rem % 3_600) / 60,
+        rem % 60
+    )
+}
+
+/// Parse "PATTERN=>REPLACEMENT" URL rewrite rules
+fn parse_rewrite_rules(rules: &[String]) -> Result<Vec<(regex::Regex, String)>> {
+    rules
+        .iter()
+        .map(|rule| {
+            let (pattern, replacement) = rule.split_once("=>").ok_or_else(|| {
+                anyhow::anyhow!("Invalid rewrite rule '{}': expected PATTERN=>REPLACEMENT", rule)
+            })?;
+            let regex = regex::Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid rewrite pattern '{}': {}", pattern, e))?;
+            Ok((regex, replacement.to_string()))
+        })
+        .collect()
+}
+
+/// Apply URL rewrite rules in order, each to the output of the previous one
+fn apply_rewrite_rules(url: &str, rules: &[(regex::Regex, String)]) -> String {
+    rules.iter().fold(url.to_string(), |current, (regex, replacement)| {
+        regex.replace_all(&current, replacement.as_str()).into_owned()
+    })
+}
+
 /// Parse comma-separated domain list into HashSet
 fn parse_domain_list(domains_str: &str) -> HashSet<String> {
     domains_str
@@ -614,12 +2098,132 @@ fn parse_domain_list(domains_str: &str) -> HashSet<String> {
         .collect()
 }
 
+/// Content types implied by common file extensions, for --crawl-content-types
+const EXTENSION_CONTENT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("php", "text/html"),
+    ("asp", "text/html"),
+    ("aspx", "text/html"),
+    ("jsp", "text/html"),
+    ("txt", "text/plain"),
+    ("css", "text/css"),
+    ("js", "application/javascript"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("doc", "application/msword"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("exe", "application/octet-stream"),
+    ("dmg", "application/octet-stream"),
+    ("iso", "application/octet-stream"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+];
+
+/// Guess a URL's content type from the extension of its last path segment
+fn guess_content_type(url: &str) -> Option<&'static str> {
+    let parsed = Url::parse(url).ok()?;
+    let last_segment = parsed.path_segments()?.next_back()?;
+    let (_, extension) = last_segment.rsplit_once('.')?;
+    EXTENSION_CONTENT_TYPES
+        .iter()
+        .find(|(ext, _)| ext.eq_ignore_ascii_case(extension))
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Whether a Content-Type value (parameters ignored) is in the allowed list; "image/*" style wildcards work
+fn content_type_allowed(content_type: &str, allowed: &[String]) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    allowed.iter().any(|a| match a.strip_suffix("/*") {
+        Some(prefix) => mime.split('/').next() == Some(prefix),
+        None => *a == mime,
+    })
+}
+
+/// Decide whether a crawl link points at an allowed content type: known extensions are judged
+/// offline, anything else passes (--verify-content-type checks those when they are dequeued)
+fn crawl_link_type_allowed(url: &str, allowed: &[String]) -> bool {
+    guess_content_type(url).is_none_or(|content_type| content_type_allowed(content_type, allowed))
+}
+
+/// --verify-content-type: ask the server with HEAD whether an extensionless crawl link is an
+/// allowed type; the HEAD is paced and followed by --delay like any other request
+async fn verify_link_content_type(url: &str, allowed: &[String], args: &Args, limits: &RunLimits) -> bool {
+    limits.pace(url).await;
+    let response = match build_client(args, select_user_agent(args, url), url) {
+        Ok(client) => client.head(url).send().await,
+        Err(_) => return true,
+    };
+    // When HEAD fails or says nothing, let the real request decide
+    let verdict = match response.ok().and_then(|r| r.headers().get(reqwest::header::CONTENT_TYPE).cloned()) {
+        Some(value) => content_type_allowed(value.to_str().unwrap_or(""), allowed),
+        None => true,
+    };
+
+    let pause = request_pause(args, limits.rate_limited(), false);
+    if !pause.is_zero() {
+        tokio::time::sleep(pause).await;
+    }
+    verdict
+}
+
+/// Detect crawler-trap URLs (e.g. /a/a/a/a/ from relative-link bugs) by their path shape
+fn is_path_trap(url: &str, max_repeated_segments: Option<usize>, max_path_depth: Option<usize>) -> bool {
+    let Ok(parsed) = Url::parse(url) else {
+        return false;
+    };
+    let segments: Vec<&str> = parsed
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+
+    if let Some(max) = max_path_depth.filter(|max| segments.len() > *max) {
+        log::debug!("🪤 Skipping {} (path deeper than {} segments)", url, max);
+        return true;
+    }
+
+    if let Some(max) = max_repeated_segments {
+        let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+        for segment in &segments {
+            let count = counts.entry(segment).or_insert(0);
+            *count += 1;
+            if *count > max {
+                log::debug!("🪤 Skipping {} (segment '{}' repeated more than {} times)", url, segment, max);
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
 /// Determine if a link should be added to the crawl queue
-/// Applies filtering in order: block list → allow list → cross-domain → same-domain fallback
+/// Applies filtering in order: block list → allow list → cross-domain → same-host fallback
+/// `base_host` is the start URL's `url_host_key` (host plus any non-default port), so IP
+/// literals and port-bearing hosts scope correctly; the domain lists match on host name alone
 fn should_add_to_crawl_queue(
     link_url: &str,
     base_url: &Url,
-    base_domain: &str,
+    base_host: &str,
     visited: &HashSet<String>,
     allow_domains: &HashSet<String>,
     block_domains: &HashSet<String>,
@@ -643,15 +2247,14 @@ fn should_add_to_crawl_queue(
         return None;
     }
 
-    // Get the domain of the link
-    let link_domain = match parsed_url.domain() {
-        Some(domain) => domain.to_lowercase(),
+    // Get the host of the link
+    let link_domain = match parsed_url.host_str() {
+        Some(host) => host.to_lowercase(),
         None => {
-            log::debug!("❌ Skipping URL with no domain: {}", url_str);
+            log::debug!("❌ Skipping URL with no host: {}", url_str);
             return None;
         }
     };
-
     // 1️⃣ Apply block list first
     if !block_domains.is_empty() && block_domains.contains(&link_domain) {
         log::debug!("🚫 Blocked domain: {} ({})", url_str, link_domain);
@@ -660,8 +2263,8 @@ fn should_add_to_crawl_queue(
 
     // 2️⃣ Check allow list (if specified)
     if !allow_domains.is_empty() {
-        // Base domain is always implicitly allowed
-        if link_domain == base_domain || allow_domains.contains(&link_domain) {
+        // Base host is always implicitly allowed
+        if is_same_domain(&url_str, base_host) || allow_domains.contains(&link_domain) {
             log::debug!("✅ Allowed domain: {} ({})", url_str, link_domain);
             return Some(url_str);
         } else {
@@ -676,13 +2279,13 @@ fn should_add_to_crawl_queue(
         return Some(url_str);
     }
 
-    // 4️⃣ Fallback: same-domain only (default behavior)
-    if link_domain == base_domain {
-        log::debug!("🏠 Same domain: {} ({})", url_str, link_domain);
-        return Some(url_str);
+    // 4️⃣ Fallback: same host only (default behavior)
+    if is_same_domain(&url_str, base_host) {
+        log::debug!("🏠 Same host: {} ({})", url_str, link_domain);
+        Some(url_str)
     } else {
-        log::debug!("🔒 Different domain blocked: {} ({})", url_str, link_domain);
-        return None;
+        log::debug!("🔒 Different host blocked: {} ({})", url_str, link_domain);
+        None
     }
 }
 
@@ -711,7 +2314,7 @@ async fn main() -> Result<()> {
     }
 
     // Validate that we have at least one URL
-    if args.urls.is_empty() {
+    if args.urls.is_empty() && args.replay.is_none() {
         return Err(anyhow::anyhow!(
             "No URLs provided. Use positional arguments or --url-file to specify URLs."
         ));
@@ -724,6 +2327,39 @@ async fn main() -> Result<()> {
         ));
     }
 
+    // Parquet is binary, so it can only be written to a file
+    if args.format.eq_ignore_ascii_case("parquet") && args.output.is_none() {
+        return Err(anyhow::anyhow!("--format parquet requires --output to be specified"));
+    }
+
+    if let Some(fields) = &args.fields {
+        parse_field_list(fields)?;
+    }
+
+    if let Some(codes) = &args.keep_status {
+        parse_status_list(codes)?;
+    }
+
+    if let Some(lang) = &args.lang {
+        if lang.trim().is_empty() || reqwest::header::HeaderValue::from_str(lang.trim()).is_err() {
+            return Err(anyhow::anyhow!("Invalid --lang value '{}'", lang));
+        }
+    }
+
+    for selector in args.require_selector.iter().chain(&args.root_selector) {
+        Selector::parse(selector)
+            .map_err(|e| ScraperError::InvalidSelector(format!("'{}': {}", selector, e)))?;
+    }
+    parse_named_selectors(&args.selector_name)?;
+
+    let user_data = parse_user_data(&args.tags, args.run_id.as_deref())?;
+
+    if let Some(rps) = args.rps {
+        if !(rps.is_finite() && rps > 0.0) {
+            return Err(anyhow::anyhow!("--rps must be a positive number, got {}", rps));
+        }
+    }
+
     log::info!("📋 Scraping {} URL(s)", args.urls.len());
 
     // Validate URLs
@@ -734,58 +2370,541 @@ async fn main() -> Result<()> {
     }
 
     // Scrape URLs
-    let results = if args.crawl {
+    let mut streamed = false;
+    let mut results = if let Some(dir) = &args.replay {
+        replay_archive(dir, &args)?
+    } else if args.crawl {
         // Crawl mode: follow links from the first URL
-        if args.urls.len() > 1 {
-            log::warn!("Crawl mode only uses the first URL provided");
+        if args.urls.len() > 1 && !args.multi_seed {
+            log::warn!("Crawl mode only uses the first URL provided (see --multi-seed)");
+        }
+        if streams_ndjson(&args) {
+            let results = stream_crawl_ndjson(&args, user_data.as_ref()).await?;
+            streamed = true;
+            results
+        } else {
+            crawl_website(&args).await?
         }
-        crawl_website(&args).await?
     } else {
         // Regular mode: scrape provided URLs
         scrape_multiple(&args).await?
     };
 
-    // Output results
-    output_results(&results, &args)?;
+    if args.collapse_canonical {
+        results = collapse_by_canonical(results);
+    }
+    if args.audit {
+        audit_across_pages(&mut results);
+    }
 
-    log::info!("✅ Scraped {} page(s) successfully", results.len());
+    let ingested_at = format_rfc3339(std::time::SystemTime::now());
+    for data in &mut results {
+        finish_page(data, &args, user_data.as_ref(), &ingested_at);
+    }
+
+    // Output results (a streamed crawl has already written them)
+    if !streamed {
+        output_results(&results, &args)?;
+    }
+    if args.report_duplicate_titles {
+        eprint!("{}", duplicate_title_report(&results));
+    }
+    if let Some(path) = &args.diff_selectors {
+        let mut snapshot = load_selector_snapshot(path)?;
+        let changes = diff_selector_snapshot(&mut snapshot, &results);
+        eprint!("{}", selector_change_report(&changes));
+        fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
+    }
+    report_requirement_failures(&results)?;
+
+    log::info!("✅ Scraped {} page(s) successfully", results.len());
     Ok(())
 }
 
+/// Per-page steps applied just before output: --canonical-urls and the --tag/--run-id
+/// provenance stamp
+fn finish_page(
+    data: &mut ScrapedData,
+    args: &Args,
+    user_data: Option<&BTreeMap<String, String>>,
+    ingested_at: &str,
+) {
+    if args.canonical_urls {
+        canonicalize_result_urls(data);
+    }
+    if let Some(user_data) = user_data {
+        data.user_data = Some(user_data.clone());
+        data.ingested_at = Some(ingested_at.to_string());
+    }
+}
+
+/// Whether a crawl can write NDJSON page by page as it goes: nothing afterwards needs the
+/// whole result set (merging, grouping, trees, per-page files or gzip)
+fn streams_ndjson(args: &Args) -> bool {
+    args.format.eq_ignore_ascii_case("ndjson")
+        && !(args.output_per_page
+            || args.merge_into.is_some()
+            || args.tree
+            || args.links_only
+            || args.collapse_canonical
+            || args.audit
+            || args.gzip_output
+            || args.output.as_deref().is_some_and(|path| path.ends_with(".gz")))
+}
+
+/// Crawl, writing each page as an NDJSON line to --output (or stdout) as soon as it is
+/// scraped; the pages are still returned for the end-of-run reports
+async fn stream_crawl_ndjson(
+    args: &Args,
+    user_data: Option<&BTreeMap<String, String>>,
+) -> Result<Vec<ScrapedData>> {
+    let keep_status = args.keep_status.as_deref().map(parse_status_list).transpose()?;
+    let mut out: Option<Box<dyn Write>> = match &args.output {
+        Some(path) => Some(Box::new(std::io::BufWriter::new(fs::File::create(path)?))),
+        None if !args.quiet => Some(Box::new(std::io::stdout())),
+        None => None,
+    };
+
+    let mut write_page = |data: &ScrapedData| -> Result<()> {
+        let Some(out) = out.as_mut() else { return Ok(()) };
+        if keep_status.as_ref().is_some_and(|codes| !codes.contains(&data.status_code)) {
+            return Ok(());
+        }
+        let mut data = data.clone();
+        finish_page(&mut data, args, user_data, &format_rfc3339(std::time::SystemTime::now()));
+        writeln!(out, "{}", ndjson_line(&data, args)?)?;
+        out.flush()?;
+        Ok(())
+    };
+    let results = crawl_website_with(args, &mut write_page).await?;
+
+    if let Some(path) = &args.output {
+        log::info!("💾 Output saved to: {}", path);
+    }
+    Ok(results)
+}
+
+/// Parse a comma-separated list of HTTP status codes and inclusive ranges (e.g. "200,400-499")
+fn parse_status_list(codes: &str) -> Result<HashSet<u16>> {
+    let parse_code = |c: &str| {
+        c.trim()
+            .parse::<u16>()
+            .map_err(|_| anyhow::anyhow!("Invalid HTTP status code '{}'", c.trim()))
+    };
+
+    let mut set = HashSet::new();
+    for item in codes.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        match item.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_code(start)?, parse_code(end)?);
+                if start > end {
+                    return Err(anyhow::anyhow!("Invalid HTTP status range '{}'", item));
+                }
+                set.extend(start..=end);
+            }
+            None => {
+                set.insert(parse_code(item)?);
+            }
+        }
+    }
+    Ok(set)
+}
+
+/// Shared allowance of retry attempts for --retry-budget
+struct RetryBudget {
+    remaining: std::sync::atomic::AtomicUsize,
+}
+
+impl RetryBudget {
+    fn from_args(args: &Args) -> Option<Self> {
+        args.retry_budget.map(|n| RetryBudget {
+            remaining: std::sync::atomic::AtomicUsize::new(n),
+        })
+    }
+
+    /// Take one retry from the budget; false once it is exhausted
+    fn try_spend(&self) -> bool {
+        use std::sync::atomic::Ordering;
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+/// Whether a retry may be attempted under the optional run-wide budget
+fn retry_allowed(budget: Option<&RetryBudget>, url: &str) -> bool {
+    match budget {
+        Some(budget) if !budget.try_spend() => {
+            log::warn!("⛔ Retry budget exhausted, not retrying {}", url);
+            false
+        }
+        _ => true,
+    }
+}
+
+/// Tracks consecutive blocking responses per host for --stop-on-status
+struct HostStatusTracker {
+    codes: HashSet<u16>,
+    threshold: usize,
+    consecutive: std::collections::HashMap<String, usize>,
+    halted: HashSet<String>,
+}
+
+impl HostStatusTracker {
+    fn new(codes: HashSet<u16>, threshold: usize) -> Self {
+        HostStatusTracker {
+            codes,
+            threshold: threshold.max(1),
+            consecutive: std::collections::HashMap::new(),
+            halted: HashSet::new(),
+        }
+    }
+
+    fn from_args(args: &Args) -> Result<Option<Self>> {
+        args.stop_on_status
+            .as_deref()
+            .map(|codes| Ok(Self::new(parse_status_list(codes)?, args.stop_on_status_threshold)))
+            .transpose()
+    }
+
+    fn is_halted(&self, url: &str) -> bool {
+        self.halted.contains(&url_host_key(url))
+    }
+
+    /// Record a response status; any non-matching status resets the host's streak
+    fn record(&mut self, url: &str, status_code: u16) {
+        let host = url_host_key(url);
+        if !self.codes.contains(&status_code) {
+            self.consecutive.remove(&host);
+            return;
+        }
+
+        let count = self.consecutive.entry(host.clone()).or_insert(0);
+        *count += 1;
+        if *count >= self.threshold && self.halted.insert(host.clone()) {
+            log::warn!(
+                "🛑 Stopping requests to {} after {} consecutive HTTP {} responses",
+                host,
+                count,
+                status_code
+            );
+        }
+    }
+}
+
+/// Host (with port, if any) a URL points to, used to group per-host state
+fn url_host_key(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.host_str().map(|host| match u.port() {
+                Some(port) => format!("{}:{}", host.to_lowercase(), port),
+                None => host.to_lowercase(),
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Token-bucket rate limiter shared by every request of a run (--rps)
+/// Holds a single token, so permits are handed out evenly spaced at 1/rps seconds
+struct RateLimiter {
+    interval: Duration,
+    next_slot: tokio::sync::Mutex<tokio::time::Instant>,
+}
+
+impl RateLimiter {
+    fn new(rps: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / rps),
+            next_slot: tokio::sync::Mutex::new(tokio::time::Instant::now()),
+        }
+    }
+
+    /// Wait until a request is allowed to start
+    async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(tokio::time::Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Hard per-host floor on the gap between request starts (--min-host-interval)
+/// Applies on top of --delay/--rps, so no throughput setting can hammer a single host
+struct HostIntervalGate {
+    interval: Duration,
+    next_slot: tokio::sync::Mutex<std::collections::HashMap<String, tokio::time::Instant>>,
+}
+
+impl HostIntervalGate {
+    fn new(interval: Duration) -> Self {
+        HostIntervalGate {
+            interval,
+            next_slot: tokio::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    fn from_args(args: &Args) -> Option<Self> {
+        args.min_host_interval.map(|ms| Self::new(Duration::from_millis(ms)))
+    }
+
+    /// Wait until a request to `url`'s host is allowed to start
+    async fn wait(&self, url: &str) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let now = tokio::time::Instant::now();
+            let entry = next_slot.entry(url_host_key(url)).or_insert(now);
+            let slot = (*entry).max(now);
+            *entry = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Run-wide request limits shared by every page and every retry of a run
+/// (--rps, --min-host-interval and --retry-budget), plus the run's --seed RNG
+struct RunLimits {
+    limiter: Option<RateLimiter>,
+    host_gate: Option<HostIntervalGate>,
+    retry_budget: Option<RetryBudget>,
+    rng: std::sync::Mutex<StdRng>,
+}
+
+impl RunLimits {
+    fn from_args(args: &Args) -> Self {
+        RunLimits {
+            limiter: args.rps.map(RateLimiter::new),
+            host_gate: HostIntervalGate::from_args(args),
+            retry_budget: RetryBudget::from_args(args),
+            rng: std::sync::Mutex::new(seeded_rng(args)),
+        }
+    }
+
+    fn rate_limited(&self) -> bool {
+        self.limiter.is_some()
+    }
+
+    /// Wait until a request to `url` is allowed to start under --rps and --min-host-interval
+    async fn pace(&self, url: &str) {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+        if let Some(gate) = &self.host_gate {
+            gate.wait(url).await;
+        }
+    }
+}
+
+/// RNG for a run's randomized decisions: seeded by --seed, otherwise from system entropy
+fn seeded_rng(args: &Args) -> StdRng {
+    args.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+}
+
+/// Pause before the next request: the normal --delay (unless --rps paces requests instead)
+/// plus --error-delay when the last request failed
+fn request_pause(args: &Args, rate_limited: bool, failed: bool) -> Duration {
+    let base = if rate_limited { 0 } else { args.delay };
+    let backoff = if failed { args.error_delay } else { 0 };
+    Duration::from_millis(base + backoff)
+}
+
+/// Delay from a Retry-After header given in seconds (the HTTP-date form is ignored)
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Longest pause a Retry-After header on a successful page may impose, so one odd header
+/// can't stall a run for hours
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Pause before the next request: a server's Retry-After on the last page (capped at
+/// MAX_RETRY_AFTER) overrides the configured delay for that step
+fn next_request_pause(args: &Args, rate_limited: bool, result: &Result<ScrapedData>) -> Duration {
+    let Some(server_pause) = result.as_ref().ok().and_then(|data| data.retry_after) else {
+        return request_pause(args, rate_limited, result.is_err());
+    };
+
+    let pace = if rate_limited { "--rps" } else { "--delay" };
+    if server_pause > MAX_RETRY_AFTER {
+        log::warn!(
+            "⏳ Server asked for {}s between requests (Retry-After); capping at {}s instead of {}",
+            server_pause.as_secs(),
+            MAX_RETRY_AFTER.as_secs(),
+            pace
+        );
+        return MAX_RETRY_AFTER;
+    }
+    log::info!(
+        "⏳ Server asked for {}s between requests (Retry-After), used instead of {}",
+        server_pause.as_secs(),
+        pace
+    );
+    server_pause
+}
+
 /// Scrape multiple URLs (non-crawling mode)
+/// Up to --concurrency pages are fetched at once; results keep the input order
 async fn scrape_multiple(args: &Args) -> Result<Vec<ScrapedData>> {
-    let mut results = Vec::new();
+    let limits = RunLimits::from_args(args);
+    let status_tracker = std::sync::Mutex::new(HostStatusTracker::from_args(args)?);
+    // Requested URLs claimed by a page (and final URLs already fetched), for
+    // --dedup-across-urls, each with the input position of the page that recorded it
+    let seen: std::sync::Mutex<HashMap<String, usize>> = Default::default();
+    // Semaphores hand out permits first-come first-served, so each host's pages start in
+    // input order
+    let workers = tokio::sync::Semaphore::new(args.concurrency.max(1));
+    let host_slots: std::sync::Mutex<HashMap<String, std::sync::Arc<tokio::sync::Semaphore>>> =
+        Default::default();
+
+    // With --feed-mode the seeds are feeds and their articles are what gets scraped
+    let urls = if args.feed_mode {
+        let mut articles = Vec::new();
+        for feed in &args.urls {
+            match fetch_feed_links(feed, args, Some(&limits)).await {
+                Ok(links) => articles.extend(links),
+                Err(e) => log::error!("Failed to read feed {}: {}", feed, e),
+            }
+        }
+        articles
+    } else {
+        args.urls.clone()
+    };
 
-    for url in &args.urls {
-        log::info!("Scraping: {}", url);
+    let pages = urls.iter().enumerate().map(|(index, url)| {
+        let limits = &limits;
+        let (status_tracker, seen) = (&status_tracker, &seen);
+        let (workers, host_slots, total) = (&workers, &host_slots, urls.len());
+        async move {
+            // --max-per-host is waited for first, so a busy host doesn't tie up workers
+            let host_slot = args.max_per_host.map(|cap| {
+                let mut slots = host_slots.lock().unwrap();
+                let slot = slots.entry(url_host_key(url));
+                slot.or_insert_with(|| std::sync::Arc::new(tokio::sync::Semaphore::new(cap as usize))).clone()
+            });
+            let _host_permit = match host_slot {
+                Some(slot) => Some(slot.acquire_owned().await.expect("semaphore is never closed")),
+                None => None,
+            };
+            let _permit = workers.acquire().await.expect("semaphore is never closed");
+
+            // Pages may start out of input order, so a URL is only skipped for an earlier page
+            if args.dedup_across_urls {
+                let mut seen = seen.lock().unwrap();
+                match seen.get(&dedup_key(url)) {
+                    Some(&claimed_by) if claimed_by < index => {
+                        log::info!("⏭️  Skipping already scraped: {}", url);
+                        return None;
+                    }
+                    _ => {
+                        seen.insert(dedup_key(url), index);
+                    }
+                }
+            }
 
-        match scrape_website(url, args, None).await {
-            Ok(data) => results.push(data),
-            Err(e) => {
-                log::error!("Failed to scrape {}: {}", url, e);
-                if !args.quiet {
-                    eprintln!("Error scraping {}: {}", url, e);
+            if status_tracker.lock().unwrap().as_ref().is_some_and(|t| t.is_halted(url)) {
+                log::warn!("⏭️  Skipping {} (host stopped by --stop-on-status)", url);
+                return None;
+            }
+
+            log::info!("Scraping: {}", url);
+
+            limits.pace(url).await;
+            let result = scrape_website(url, args, None, Some(limits)).await;
+            let pause = next_request_pause(args, limits.rate_limited(), &result);
+            if let Some(tracker) = status_tracker.lock().unwrap().as_mut() {
+                let status = match &result {
+                    Ok(data) => data.status_code,
+                    Err(e) => error_status_code(e),
+                };
+                tracker.record(url, status);
+            }
+
+            match &result {
+                Ok(data) if args.dedup_across_urls => {
+                    if let Some(final_url) = &data.final_url {
+                        let mut seen = seen.lock().unwrap();
+                        let claimed_by = seen.entry(dedup_key(final_url)).or_insert(index);
+                        *claimed_by = (*claimed_by).min(index);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let message = error_message(e, args.verbose_errors);
+                    log::error!("Failed to scrape {}: {}", url, message);
+                    if !args.quiet {
+                        eprintln!("Error scraping {}: {}", url, message);
+                    }
                 }
             }
+
+            // Rate limiting delay, spacing each worker's requests (--rps paces requests
+            // through the limiter instead)
+            if !pause.is_zero() && index + 1 < total {
+                log::debug!("Waiting {}ms before next request", pause.as_millis());
+                tokio::time::sleep(pause).await;
+            }
+            Some(result)
         }
+    });
+    let fetched = futures::future::join_all(pages).await;
 
-        // Rate limiting delay
-        if results.len() < args.urls.len() {
-            log::debug!("Waiting {}ms before next request", args.delay);
-            tokio::time::sleep(Duration::from_millis(args.delay)).await;
+    // Pages can finish in any order, so duplicates are resolved afterwards in input order
+    let mut results = Vec::new();
+    let mut kept_keys: HashSet<String> = HashSet::new();
+    let mut kept_canonicals: HashSet<String> = HashSet::new();
+    for (url, result) in urls.iter().zip(fetched) {
+        match result {
+            Some(Ok(data))
+                if args.dedup_by_declared_canonical && is_canonical_duplicate(&data, &mut kept_canonicals) =>
+            {
+                log::info!("⏭️  {} declares an already stored canonical URL", url);
+            }
+            Some(Ok(data)) if args.dedup_across_urls => {
+                let final_key = dedup_key(data.final_url.as_deref().unwrap_or(url));
+                if kept_keys.contains(&final_key) {
+                    log::info!("⏭️  {} resolved to an already scraped page", url);
+                } else {
+                    kept_keys.insert(dedup_key(url));
+                    kept_keys.insert(final_key);
+                    results.push(data);
+                }
+            }
+            Some(Ok(data)) => results.push(data),
+            Some(Err(e)) if args.include_errors => results.push(error_result(url, &e, None)),
+            Some(Err(_)) | None => {}
         }
     }
-
     Ok(results)
 }
 
 /// Crawl website following links
 async fn crawl_website(args: &Args) -> Result<Vec<ScrapedData>> {
+    crawl_website_with(args, &mut |_| Ok(())).await
+}
+
+/// Crawl website following links, handing each kept page to `on_page` as soon as it is scraped
+async fn crawl_website_with(
+    args: &Args,
+    on_page: &mut dyn FnMut(&ScrapedData) -> Result<()>,
+) -> Result<Vec<ScrapedData>> {
     let start_url = &args.urls[0];
     let base_url = Url::parse(start_url)?;
-    let base_domain = base_url.domain().ok_or_else(|| {
-        ScraperError::InvalidUrl("URL has no domain".to_string())
-    })?;
+    let seeds = if args.multi_seed { &args.urls[..] } else { &args.urls[..1] };
+    // Hosts whose links stay in scope; the first is the start URL's
+    let mut seed_hosts: Vec<String> = Vec::new();
+    for seed in seeds {
+        let host = url_host_key(seed);
+        if host.is_empty() {
+            return Err(ScraperError::InvalidUrl(format!("URL has no host: {}", seed)).into());
+        }
+        if !seed_hosts.contains(&host) {
+            seed_hosts.push(host);
+        }
+    }
 
     // Parse domain filtering lists
     let allow_domains = args
@@ -798,13 +2917,56 @@ async fn crawl_website(args: &Args) -> Result<Vec<ScrapedData>> {
         .as_ref()
         .map(|s| parse_domain_list(s))
         .unwrap_or_default();
+    let rewrite_rules = parse_rewrite_rules(&args.rewrite)?;
+    let limits = RunLimits::from_args(args);
+    let mut status_tracker = HostStatusTracker::from_args(args)?;
+    let content_types: Option<Vec<String>> = args.crawl_content_types.as_ref().map(|types| {
+        types
+            .split(',')
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect()
+    });
 
     let mut results = Vec::new();
     let mut visited = HashSet::new();
     let mut queue = VecDeque::new();
-    queue.push_back((start_url.clone(), 0usize));
+    // Hosts seen serving https, and upgraded URLs mapped back to their http:// originals
+    let mut https_hosts: HashSet<String> = HashSet::new();
+    let mut upgrades: HashMap<String, String> = HashMap::new();
+    let mut fetched_per_depth: HashMap<usize, usize> = HashMap::new();
+    let mut total_bytes: u64 = 0;
+    let mut kept_canonicals: HashSet<String> = HashSet::new();
+    if args.feed_mode {
+        // The feed's articles are the starting points, not the feed document itself
+        for link in fetch_feed_links(start_url, args, Some(&limits)).await? {
+            queue.push_back((link, 0usize));
+        }
+    } else if args.sitemap_only {
+        // Sitemap entries still go through the domain filters and trap guards
+        for link in fetch_sitemap_urls(&base_url, args, Some(&limits)).await? {
+            let allowed = should_add_to_crawl_queue(
+                &link,
+                &base_url,
+                &seed_hosts[0],
+                &visited,
+                &allow_domains,
+                &block_domains,
+                args.cross_domain,
+            )
+            .filter(|l| !is_path_trap(l, args.max_repeated_segments, args.max_path_depth));
+            match allowed {
+                Some(link) => queue.push_back((link, 0usize)),
+                None => log::debug!("🗺️  Skipping sitemap entry {}", link),
+            }
+        }
+    } else {
+        for seed in seeds {
+            queue.push_back((seed.clone(), 0usize));
+        }
+    }
 
-    log::info!("🕷️  Starting crawl from: {}", start_url);
+    log::info!("🕷️  Starting crawl from: {}", seeds.join(", "));
     log::info!("📊 Max depth: {}, Max pages: {}", args.max_depth, args.max_pages);
 
     // Log domain filtering configuration
@@ -821,7 +2983,8 @@ async fn crawl_website(args: &Args) -> Result<Vec<ScrapedData>> {
     }
 
     while let Some((url, depth)) = queue.pop_front() {
-        if visited.contains(&url) || results.len() >= args.max_pages {
+        let key = visit_key(&url, args.treat_trailing_slash_equal);
+        if visited.contains(&key) || results.len() >= args.max_pages {
             continue;
         }
 
@@ -830,57 +2993,287 @@ async fn crawl_website(args: &Args) -> Result<Vec<ScrapedData>> {
             continue;
         }
 
-        visited.insert(url.clone());
+        if status_tracker.as_ref().is_some_and(|t| t.is_halted(&url)) {
+            log::debug!("Skipping {} (host stopped by --stop-on-status)", url);
+            continue;
+        }
+
+        let fetched_at_depth = fetched_per_depth.get(&depth).copied().unwrap_or(0);
+        if args.max_per_depth.is_some_and(|cap| fetched_at_depth >= cap) {
+            log::debug!("Skipping {} (depth {} reached --max-per-depth)", url, depth);
+            continue;
+        }
+
+        // Extensionless links are only checked once they are about to be fetched, so links
+        // past --max-pages never cost a HEAD; a rejected link is marked visited and never
+        // asked about again
+        if let Some(types) = content_types.as_ref().filter(|_| args.verify_content_type && depth > 0) {
+            if guess_content_type(&url).is_none()
+                && !verify_link_content_type(&url, types, args, &limits).await
+            {
+                log::debug!("📎 Skipping {} (not {})", url, types.join(", "));
+                visited.insert(key);
+                continue;
+            }
+        }
+        *fetched_per_depth.entry(depth).or_insert(0) += 1;
+
+        visited.insert(key);
         log::info!("Crawling: {} (depth: {})", url, depth);
 
-        match scrape_website(&url, args, Some(depth)).await {
-            Ok(data) => {
-                // Extract links for further crawling
-                if depth < args.max_depth {
-                    for link in &data.links {
+        limits.pace(&url).await;
+        let result = scrape_website(&url, args, Some(depth), Some(&limits)).await;
+        let pause = next_request_pause(args, limits.rate_limited(), &result);
+        if let Some(tracker) = &mut status_tracker {
+            let status = match &result {
+                Ok(data) => data.status_code,
+                Err(e) => error_status_code(e),
+            };
+            tracker.record(&url, status);
+        }
+        let upgraded_from = upgrades.remove(&url);
+
+        match result {
+            Ok(data)
+                if args.dedup_by_declared_canonical
+                    && is_canonical_duplicate(&data, &mut kept_canonicals) =>
+            {
+                total_bytes += data.body_bytes;
+                log::info!("⏭️  {} declares an already stored canonical URL", url);
+            }
+            Ok(mut data) => {
+                data.upgraded_from = upgraded_from;
+                total_bytes += data.body_bytes;
+                if args.upgrade_insecure {
+                    let served = Url::parse(data.final_url.as_deref().unwrap_or(&url)).ok();
+                    if let Some(host) = served
+                        .filter(|u| u.scheme() == "https")
+                        .and_then(|u| u.host_str().map(str::to_lowercase))
+                    {
+                        https_hosts.insert(host);
+                    }
+                }
+
+                // Extract links for further crawling (--sitemap-only never expands)
+                if depth < args.max_depth && !args.sitemap_only {
+                    // External links are recorded separately and never followed
+                    for link in data.links.iter().chain(&data.internal_links) {
+                        let candidate = upgrade_crawl_candidate(
+                            apply_rewrite_rules(&link.url, &rewrite_rules),
+                            args,
+                            &https_hosts,
+                            &mut upgrades,
+                        );
                         if let Some(link_str) = should_add_to_crawl_queue(
-                            &link.url,
+                            &candidate,
                             &base_url,
-                            base_domain,
+                            crawl_scope_host(&candidate, &seed_hosts),
                             &visited,
                             &allow_domains,
                             &block_domains,
                             args.cross_domain,
                         ) {
+                            if is_path_trap(&link_str, args.max_repeated_segments, args.max_path_depth) {
+                                continue;
+                            }
+                            if let Some(types) = &content_types {
+                                if !crawl_link_type_allowed(&link_str, types) {
+                                    log::debug!("📎 Skipping {} (not {})", link_str, types.join(", "));
+                                    continue;
+                                }
+                            }
                             queue.push_back((link_str, depth + 1));
                         }
                     }
                 }
 
+                // Pagination continues the current page, so it keeps its depth and jumps the queue
+                // (except at --max-depth 0, which is strictly the start page)
+                if args.follow_pagination && !args.sitemap_only && args.max_depth > 0 {
+                    if let Some(next_page) = &data.next_page {
+                        let candidate = upgrade_crawl_candidate(
+                            apply_rewrite_rules(next_page, &rewrite_rules),
+                            args,
+                            &https_hosts,
+                            &mut upgrades,
+                        );
+                        if let Some(link_str) = should_add_to_crawl_queue(
+                            &candidate,
+                            &base_url,
+                            crawl_scope_host(&candidate, &seed_hosts),
+                            &visited,
+                            &allow_domains,
+                            &block_domains,
+                            args.cross_domain,
+                        )
+                        .filter(|l| !is_path_trap(l, args.max_repeated_segments, args.max_path_depth))
+                        {
+                            log::debug!("📄 Following pagination: {}", link_str);
+                            queue.push_front((link_str, depth));
+                        }
+                    }
+                }
+
+                on_page(&data)?;
                 results.push(data);
             }
             Err(e) => {
-                log::error!("Failed to crawl {}: {}", url, e);
+                log::error!("Failed to crawl {}: {}", url, error_message(&e, args.verbose_errors));
+                if let Some(original) = &upgraded_from {
+                    log::warn!("🔒 HTTPS upgrade of {} failed", original);
+                }
+                if args.include_errors {
+                    let data = ScrapedData {
+                        upgraded_from,
+                        ..error_result(&url, &e, Some(depth))
+                    };
+                    on_page(&data)?;
+                    results.push(data);
+                }
             }
         }
 
-        // Rate limiting
-        tokio::time::sleep(Duration::from_millis(args.delay)).await;
+        if let Some(cap) = args.max_total_bytes.filter(|&cap| total_bytes >= cap) {
+            log::warn!(
+                "📦 Stopping crawl: downloaded {} bytes, reaching --max-total-bytes {}",
+                total_bytes,
+                cap
+            );
+            break;
+        }
+
+        // Rate limiting (--rps paces requests through the limiter instead)
+        if !pause.is_zero() {
+            tokio::time::sleep(pause).await;
+        }
     }
 
     Ok(results)
 }
 
-/// Scrape a single website
-async fn scrape_website(url: &str, args: &Args, depth: Option<usize>) -> Result<ScrapedData> {
-    log::debug!("Fetching: {}", url);
+/// Seed host a crawl link is scoped against: its own host when that is one of the seeds'
+/// (--multi-seed), otherwise the start URL's
+fn crawl_scope_host<'a>(link: &str, seed_hosts: &'a [String]) -> &'a str {
+    let host = url_host_key(link);
+    seed_hosts.iter().find(|seed| **seed == host).unwrap_or(&seed_hosts[0])
+}
+
+/// User agent for requests: --user-agent, else the --mobile preset, else the default
+fn base_user_agent(args: &Args) -> &str {
+    match &args.user_agent {
+        Some(user_agent) => user_agent,
+        None if args.mobile => MOBILE_USER_AGENT,
+        None => DEFAULT_USER_AGENT,
+    }
+}
+
+/// Per-domain values given as "DOMAIN=VALUE,..." (--ua-map, --proxy-map), parsed once with the
+/// rest of the command line
+#[derive(Debug, Clone, PartialEq)]
+struct DomainMap(Vec<(String, String)>);
+
+impl DomainMap {
+    /// Value for a URL's host: the entry for the host itself or, failing that, its most
+    /// specific listed parent domain, whatever order the entries were given in
+    fn lookup_by_host(&self, target_url: &str) -> Option<&str> {
+        let host = Url::parse(target_url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_lowercase))
+            .unwrap_or_default();
+        self.0
+            .iter()
+            .filter(|(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse a "example.com=VALUE,foo.de=VALUE" map for `flag` into (domain, value) pairs
+fn parse_domain_map(map: &str, flag: &str, value_name: &str) -> Result<DomainMap> {
+    map.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once('=')
+                .map(|(d, v)| (d.trim().trim_start_matches('.').to_lowercase(), v.trim().to_string()))
+                .filter(|(d, v)| !d.is_empty() && !v.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid {} entry '{}', expected DOMAIN={}", flag, entry, value_name)
+                })
+        })
+        .collect::<Result<_>>()
+        .map(DomainMap)
+}
+
+/// Parse --ua-map "example.com=Agent/1.0,foo.de=Other" into (domain, user agent) pairs
+fn parse_ua_map(map: &str) -> Result<DomainMap> {
+    parse_domain_map(map, "--ua-map", "USER_AGENT")
+}
+
+/// User agent for a target URL: the --ua-map entry for its host (or a parent domain),
+/// otherwise the global user agent
+fn select_user_agent<'a>(args: &'a Args, target_url: &str) -> &'a str {
+    args.ua_map
+        .as_ref()
+        .and_then(|map| map.lookup_by_host(target_url))
+        .unwrap_or_else(|| base_user_agent(args))
+}
+
+/// Extra headers sent with every request
+fn request_headers(args: &Args) -> reqwest::header::HeaderMap {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    let mut headers = HeaderMap::new();
+    if args.mobile {
+        headers.insert("Sec-CH-UA-Mobile", HeaderValue::from_static("?1"));
+        headers.insert("Sec-CH-UA-Platform", HeaderValue::from_static("\"Android\""));
+        headers.insert("Viewport-Width", HeaderValue::from_static("412"));
+    }
+    // Validated at startup
+    if let Some(lang) = args.lang.as_deref().and_then(|l| HeaderValue::from_str(l.trim()).ok()) {
+        headers.insert(reqwest::header::ACCEPT_LANGUAGE, lang);
+    }
+    headers
+}
+
+/// Pick a user agent different from `current` for retrying a blocked request
+fn next_user_agent(current: &str) -> &'static str {
+    FALLBACK_USER_AGENTS
+        .iter()
+        .find(|ua| **ua != current)
+        .copied()
+        .unwrap_or(DEFAULT_USER_AGENT)
+}
+
+/// Parse --proxy-map "example.com=http://p1,foo.de=socks5://p2" into (domain, proxy) pairs
+fn parse_proxy_map(map: &str) -> Result<DomainMap> {
+    let map = parse_domain_map(map, "--proxy-map", "PROXY_URL")?;
+    for (domain, proxy) in &map.0 {
+        Url::parse(proxy)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy URL '{}' for {}: {}", proxy, domain, e))?;
+    }
+    Ok(map)
+}
+
+/// Proxy to use for a target URL: the --proxy-map entry for its host (or a parent domain),
+/// otherwise --proxy
+fn select_proxy<'a>(args: &'a Args, target_url: &str) -> Option<&'a str> {
+    args.proxy_map
+        .as_ref()
+        .and_then(|map| map.lookup_by_host(target_url))
+        .or(args.proxy.as_deref())
+}
 
-    // Build HTTP client with custom configuration
+/// Build an HTTP client with the configured timeout, headers and proxy for `target_url`
+fn build_client(args: &Args, user_agent: &str, target_url: &str) -> Result<reqwest::Client> {
     let mut client_builder = reqwest::Client::builder()
         .timeout(Duration::from_secs(args.timeout))
-        .user_agent(
-            args.user_agent
-                .as_deref()
-                .unwrap_or("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"),
-        );
+        .user_agent(user_agent)
+        .default_headers(request_headers(args));
 
     // Add proxy if specified
-    if let Some(proxy_url) = &args.proxy {
+    if let Some(proxy_url) = select_proxy(args, target_url) {
         log::debug!("Using proxy: {}", proxy_url);
         client_builder = client_builder.proxy(reqwest::Proxy::all(proxy_url)?);
     }
@@ -888,1974 +3281,7524 @@ async fn scrape_website(url: &str, args: &Args, depth: Option<usize>) -> Result<
     let client = client_builder.build().map_err(|e| {
         ScraperError::NetworkError(format!("Failed to build HTTP client: {}", e))
     })?;
+    Ok(client)
+}
 
-    // Fetch the page with enhanced error handling
-    let response = client.get(url).send().await.map_err(|e| {
-        if e.is_timeout() {
-            ScraperError::Timeout(args.timeout)
-        } else if e.is_connect() {
-            ScraperError::NetworkError(format!("Connection failed to {}: {}", url, e))
-        } else if e.is_request() {
-            ScraperError::NetworkError(format!("Request error for {}: {}", url, e))
+/// Extract article links from an RSS (`<item><link>`) or Atom (`<entry><link href>`) feed
+fn parse_feed_links(xml: &str, base_url: &Url) -> Vec<String> {
+    let item_re = regex::Regex::new(r"(?is)<(item|entry)\b[^>]*>(.*?)</(?:item|entry)>").unwrap();
+    let rss_link_re = regex::Regex::new(r"(?is)<link>\s*(?:<!\[CDATA\[)?\s*(.*?)\s*(?:\]\]>)?\s*</link>").unwrap();
+    let atom_link_re = regex::Regex::new(r"(?is)<link\b([^>]*)/?>").unwrap();
+    let attr_re = regex::Regex::new(r#"(?is)\b(rel|href)\s*=\s*["']([^"']*)["']"#).unwrap();
+
+    let mut links = Vec::new();
+    for item in item_re.captures_iter(xml) {
+        let body = &item[2];
+        let link = if item[1].eq_ignore_ascii_case("item") {
+            rss_link_re.captures(body).map(|c| c[1].to_string())
         } else {
-            ScraperError::HttpError(e)
-        }
-    })?;
+            // Atom entries may carry several links; the article is rel="alternate" (the default)
+            atom_link_re.captures_iter(body).find_map(|c| {
+                let attrs: std::collections::HashMap<String, String> = attr_re
+                    .captures_iter(&c[1])
+                    .map(|a| (a[1].to_lowercase(), a[2].to_string()))
+                    .collect();
+                match attrs.get("rel").map(String::as_str) {
+                    None | Some("alternate") => attrs.get("href").cloned(),
+                    _ => None,
+                }
+            })
+        };
 
-    let status_code = response.status().as_u16();
+        let resolved = link
+            .map(|l| l.replace("&amp;", "&"))
+            .and_then(|l| base_url.join(&l).ok())
+            .map(|u| u.to_string());
+        if let Some(url) = resolved {
+            if !links.contains(&url) {
+                links.push(url);
+            }
+        }
+    }
+    links
+}
 
-    // Check HTTP status code and provide detailed error messages
-    classify_http_status(status_code, url)?;
+/// Fetch a feed and return its article links, for --feed-mode
+/// The fetch is paced, time-limited and retried like a page
+async fn fetch_feed_links(url: &str, args: &Args, limits: Option<&RunLimits>) -> Result<Vec<String>> {
+    let client = build_client(args, select_user_agent(args, url), url)?;
+    let (feed_url, body) = fetch_document(&client, url, args, limits).await?;
+    let links = parse_feed_links(&body, &feed_url);
+    if links.is_empty() {
+        return Err(anyhow::anyhow!("No RSS items or Atom entries found in feed {}", url));
+    }
 
-    let html = response.text().await.map_err(|e| {
-        ScraperError::NetworkError(format!("Failed to read response body from {}: {}", url, e))
-    })?;
+    log::info!("📰 Feed {} lists {} article(s)", url, links.len());
+    Ok(links)
+}
 
-    let document = Html::parse_document(&html);
-    let base_url = Url::parse(url)?;
+/// Extract `<loc>` entries from a sitemap, split into page URLs and nested sitemaps
+/// (the children of a `<sitemapindex>`)
+fn parse_sitemap_locs(xml: &str, base_url: &Url) -> (Vec<String>, Vec<String>) {
+    let entry_re = regex::Regex::new(r"(?is)<(url|sitemap)\b[^>]*>(.*?)</(?:url|sitemap)>").unwrap();
+    let loc_re = regex::Regex::new(r"(?is)<loc>\s*(?:<!\[CDATA\[)?\s*(.*?)\s*(?:\]\]>)?\s*</loc>").unwrap();
+
+    let mut pages = Vec::new();
+    let mut sitemaps = Vec::new();
+    for entry in entry_re.captures_iter(xml) {
+        let Some(url) = loc_re
+            .captures(&entry[2])
+            .and_then(|c| base_url.join(&c[1].replace("&amp;", "&")).ok())
+            .map(|u| u.to_string())
+        else {
+            continue;
+        };
+        let list = if entry[1].eq_ignore_ascii_case("sitemap") { &mut sitemaps } else { &mut pages };
+        if !list.contains(&url) {
+            list.push(url);
+        }
+    }
+    (pages, sitemaps)
+}
 
-    // Extract content using helper functions
-    let title = extract_title(&document);
+/// Sitemap text, decompressed first when it is gzip (sitemap.xml.gz, or served as
+/// application/gzip); recognised by the gzip magic bytes, since a server may also have
+/// decoded a .gz file already
+fn decode_sitemap_body(body: &[u8], url: &Url) -> Result<String> {
+    use std::io::Read;
 
-    // Detect anti-bot protection features
-    if let Some(anti_bot_msg) = detect_anti_bot_features(&html, title.as_deref()) {
-        log::warn!("Anti-bot detection for {}: {}", url, anti_bot_msg);
-        return Err(ScraperError::AntiBotDetected(anti_bot_msg).into());
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return Ok(String::from_utf8_lossy(body).into_owned());
     }
-    let headings = extract_headings(&document);
-    let paragraphs = extract_paragraphs(&document);
-    let links = extract_links(&document, &base_url);
-    let images = extract_images(&document, &base_url);
-    let tables = extract_tables(&document);
-    let code_blocks = extract_code_blocks(&document);
+    let mut xml = String::new();
+    flate2::read::GzDecoder::new(body)
+        .read_to_string(&mut xml)
+        .map_err(|e| anyhow::anyhow!("Failed to decompress sitemap {}: {}", url, e))?;
+    Ok(xml)
+}
 
-    // Extract metadata if requested
-    let metadata = if args.metadata {
-        Some(extract_metadata(&document))
+/// Page URLs listed by the start URL's sitemap, for --sitemap-only
+/// A sitemap index is followed one level down; every sitemap fetch is paced, time-limited and
+/// retried like a page
+async fn fetch_sitemap_urls(start_url: &Url, args: &Args, limits: Option<&RunLimits>) -> Result<Vec<String>> {
+    let sitemap_url = if start_url.path().ends_with(".xml") || start_url.path().ends_with(".xml.gz") {
+        start_url.clone()
     } else {
-        None
+        start_url.join("/sitemap.xml")?
     };
 
-    // Process custom selectors if provided
-    let custom_selectors = process_custom_selectors(&document, &args.selector)?;
+    let fetch = |url: Url| async move {
+        let client = build_client(args, select_user_agent(args, url.as_str()), url.as_str())?;
+        let (_, body) = fetch_document_bytes(&client, url.as_str(), args, limits).await?;
+        Ok::<_, anyhow::Error>(parse_sitemap_locs(&decode_sitemap_body(&body, &url)?, &url))
+    };
 
-    Ok(ScrapedData {
-        url: url.to_string(),
-        status_code,
-        title,
-        headings,
-        paragraphs,
-        links,
-        images,
-        tables,
-        code_blocks,
-        metadata,
-        custom_selectors,
-        depth,
-    })
-}
+    let (mut pages, children) = fetch(sitemap_url.clone()).await?;
+    for child in children {
+        match Url::parse(&child) {
+            Ok(child_url) => match fetch(child_url).await {
+                Ok((child_pages, _)) => pages.extend(child_pages),
+                Err(e) => log::warn!("Failed to read sitemap {}: {}", child, e),
+            },
+            Err(e) => log::warn!("Invalid sitemap URL {}: {}", child, e),
+        }
+    }
 
-/// Extract metadata from the HTML document
-fn extract_metadata(document: &Html) -> Metadata {
-    let meta_selector = Selector::parse("meta").unwrap();
-    let link_selector = Selector::parse("link").unwrap();
+    if pages.is_empty() {
+        return Err(anyhow::anyhow!("No page URLs found in sitemap {}", sitemap_url));
+    }
+    log::info!("🗺️  Sitemap {} lists {} page(s)", sitemap_url, pages.len());
+    Ok(pages)
+}
 
-    let mut metadata = Metadata {
-        description: None,
-        keywords: None,
-        author: None,
-        og_title: None,
-        og_description: None,
-        og_image: None,
-        og_url: None,
-        canonical_url: None,
-        favicon: None,
+/// Scrape a single website, abandoned after --page-timeout (retries included) when set
+/// The caller paces the first request; `limits` paces any retries and holds the retry budget
+async fn scrape_website(
+    url: &str,
+    args: &Args,
+    depth: Option<usize>,
+    limits: Option<&RunLimits>,
+) -> Result<ScrapedData> {
+    let Some(secs) = args.page_timeout else {
+        return scrape_with_retries(url, args, depth, limits).await;
     };
 
-    // Extract meta tags
-    for element in document.select(&meta_selector) {
-        let name = element.value().attr("name").or_else(|| element.value().attr("property"));
-        let content = element.value().attr("content");
+    // Extraction is synchronous, so the deadline is enforced at the fetch's await points;
+    // --max-parse-bytes bounds the parsing side
+    tokio::time::timeout(
+        Duration::from_secs(secs),
+        scrape_with_retries(url, args, depth, limits),
+    )
+    .await
+    .unwrap_or_else(|_| {
+        log::warn!("⏱️  Abandoning {} after --page-timeout of {}s", url, secs);
+        Err(ScraperError::Timeout(secs).into())
+    })
+}
 
-        if let (Some(name), Some(content)) = (name, content) {
-            match name.to_lowercase().as_str() {
-                "description" => metadata.description = Some(content.to_string()),
-                "keywords" => metadata.keywords = Some(content.to_string()),
-                "author" => metadata.author = Some(content.to_string()),
-                "og:title" => metadata.og_title = Some(content.to_string()),
-                "og:description" => metadata.og_description = Some(content.to_string()),
-                "og:image" => metadata.og_image = Some(content.to_string()),
-                "og:url" => metadata.og_url = Some(content.to_string()),
-                _ => {}
-            }
-        }
+/// One page, with transient failures retried
+async fn scrape_with_retries(
+    url: &str,
+    args: &Args,
+    depth: Option<usize>,
+    limits: Option<&RunLimits>,
+) -> Result<ScrapedData> {
+    let (mut data, retries) = retry_transient(url, args, limits, || scrape_page(url, args, depth, limits)).await?;
+    if args.retries > 0 {
+        data.retries_used = Some(u8::try_from(retries).unwrap_or(u8::MAX));
     }
+    Ok(data)
+}
 
-    // Extract canonical URL and favicon
-    for element in document.select(&link_selector) {
-        let rel = element.value().attr("rel");
-        let href = element.value().attr("href");
-
-        if let (Some(rel), Some(href)) = (rel, href) {
-            match rel.to_lowercase().as_str() {
-                "canonical" => metadata.canonical_url = Some(href.to_string()),
-                "icon" | "shortcut icon" => metadata.favicon = Some(href.to_string()),
-                _ => {}
+/// Run a request until it succeeds or fails for good: transient failures are retried up to
+/// --retries times with exponential backoff, each retry paced by `limits`; the last error is
+/// returned as-is
+/// Returns the result along with the number of retries it took
+async fn retry_transient<T, F, Fut>(
+    url: &str,
+    args: &Args,
+    limits: Option<&RunLimits>,
+    mut request: F,
+) -> Result<(T, usize)>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let retry_budget = limits.and_then(|l| l.retry_budget.as_ref());
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(e)
+                if attempt < args.retries
+                    && is_transient(&e, &args.retry_status)
+                    && retry_allowed(retry_budget, url) =>
+            {
+                attempt += 1;
+                let delay = match limits {
+                    Some(limits) => retry_delay(args.retry_backoff, attempt, &mut *limits.rng.lock().unwrap()),
+                    None => retry_delay(args.retry_backoff, attempt, &mut seeded_rng(args)),
+                };
+                log::info!(
+                    "🔁 Retry {}/{} for {} in {}ms ({})",
+                    attempt,
+                    args.retries,
+                    url,
+                    delay.as_millis(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                if let Some(limits) = limits {
+                    limits.pace(url).await;
+                }
             }
+            result => return result.map(|value| (value, attempt)),
         }
     }
+}
 
-    metadata
+/// Whether a failure is worth retrying: timeouts, connection failures, exchanges cut off
+/// mid-way and the --retry-status codes; DNS/TLS failures, client setup errors and other
+/// statuses (401, 403, 404, ...) are permanent
+fn is_transient(error: &anyhow::Error, retry_status: &HashSet<u16>) -> bool {
+    let interrupted = matches!(error.downcast_ref::<ScraperError>(), Some(ScraperError::Interrupted(_)));
+    interrupted
+        || matches!(error_kind(error), ErrorKind::Timeout | ErrorKind::Connect)
+        || retry_status.contains(&error_status_code(error))
 }
 
-/// Output results in the requested format
-fn output_results(results: &[ScrapedData], args: &Args) -> Result<()> {
-    // Handle per-page output mode
-    if args.output_per_page {
-        // Validation in main() ensures args.output is Some when output_per_page is true
-        let output_prefix = args.output.as_ref().unwrap();
+/// Backoff before retry number `attempt` (1-based): `base_ms` doubled per earlier attempt,
+/// plus up to half of that again as jitter so parallel runs don't retry in lockstep
+fn retry_delay(base_ms: u64, attempt: usize, rng: &mut impl Rng) -> Duration {
+    let backoff = base_ms.saturating_mul(1 << (attempt.saturating_sub(1)).min(16));
+    let jitter = rng.gen_range(0..=backoff / 2);
+    Duration::from_millis(backoff + jitter)
+}
 
-        // Determine file extension based on format
-        let extension = match args.format.to_lowercase().as_str() {
-            "json" => "json",
-            "csv" => "csv",
-            "text" | "txt" => "txt",
-            other => {
-                log::error!("Unknown format: {}", other);
-                return Err(anyhow::anyhow!(
-                    "Unknown format '{}'. Use: json, csv, or text",
-                    other
-                ));
+/// One page: a HEAD check or a full fetch, with the --retry-on-anti-bot retry
+async fn scrape_page(
+    url: &str,
+    args: &Args,
+    depth: Option<usize>,
+    limits: Option<&RunLimits>,
+) -> Result<ScrapedData> {
+    let user_agent = select_user_agent(args, url);
+
+    if args.head_only {
+        return check_status(url, args, depth, user_agent).await;
+    }
+
+    match scrape_with_user_agent(url, args, depth, user_agent, limits).await {
+        Err(e)
+            if args.retry_on_anti_bot
+                && matches!(
+                    e.downcast_ref::<ScraperError>(),
+                    Some(ScraperError::AntiBotDetected(_))
+                )
+                && retry_allowed(limits.and_then(|l| l.retry_budget.as_ref()), url) =>
+        {
+            let retry_agent = next_user_agent(user_agent);
+            log::warn!(
+                "🔄 Retrying {} once with a different user agent: {}",
+                url,
+                retry_agent
+            );
+            if let Some(limits) = limits {
+                limits.pace(url).await;
             }
-        };
-
-        log::info!("💾 Writing {} pages to individual files with prefix '{}'", results.len(), output_prefix);
-
-        // Write each result to a separate file
-        for (index, data) in results.iter().enumerate() {
-            let filename = format!("{}_{:03}.{}", output_prefix, index + 1, extension);
-
-            // Format single result
-            let output_str = match args.format.to_lowercase().as_str() {
-                "json" => format_json(&[data.clone()])?,
-                "csv" => format_csv(&[data.clone()])?,
-                "text" | "txt" => format_text(&[data.clone()]),
-                _ => unreachable!(), // Already validated above
-            };
-
-            std::fs::write(&filename, &output_str)?;
-            log::info!("  ✓ Saved: {}", filename);
+            scrape_with_user_agent(url, args, depth, retry_agent, limits).await
         }
+        result => result,
+    }
+}
 
-        log::info!("✅ All {} pages saved successfully", results.len());
-        return Ok(());
+/// --head-only: record status, final URL, content type and size without downloading the body
+/// Falls back to a GET (whose body is never read) for servers that refuse HEAD
+async fn check_status(
+    url: &str,
+    args: &Args,
+    depth: Option<usize>,
+    user_agent: &str,
+) -> Result<ScrapedData> {
+    let client = build_client(args, user_agent, url)?;
+    let mut response = client.head(url).send().await?;
+    if matches!(response.status().as_u16(), 405 | 501) {
+        log::debug!("HEAD not allowed for {}, falling back to GET", url);
+        response = client.get(url).send().await?;
     }
 
-    // Standard output mode - all results in one file/stdout
-    let output_str = match args.format.to_lowercase().as_str() {
-        "json" => format_json(results)?,
-        "csv" => format_csv(results)?,
-        "text" | "txt" => format_text(results),
-        other => {
-            log::error!("Unknown format: {}", other);
-            return Err(anyhow::anyhow!(
-                "Unknown format '{}'. Use: json, csv, or text",
-                other
-            ));
-        }
+    let header = |name: reqwest::header::HeaderName| {
+        response.headers().get(name)?.to_str().ok().map(str::to_string)
+    };
+    let final_url = if Url::parse(url).ok().as_ref() != Some(response.url()) {
+        Some(response.url().to_string())
+    } else {
+        None
     };
 
-    // Write to file or stdout
-    if let Some(output_file) = &args.output {
-        std::fs::write(output_file, &output_str)?;
-        log::info!("💾 Output saved to: {}", output_file);
-    } else if !args.quiet {
-        println!("{}", output_str);
+    Ok(ScrapedData {
+        url: url.to_string(),
+        final_url,
+        status_code: response.status().as_u16(),
+        content_type: header(reqwest::header::CONTENT_TYPE),
+        content_length: header(reqwest::header::CONTENT_LENGTH).and_then(|len| len.parse().ok()),
+        resolved_ip: peer_ip(&response, args),
+        depth,
+        ..Default::default()
+    })
+}
+
+/// IP address the response came from, for --capture-ip (None when the connection info is
+/// unavailable)
+fn peer_ip(response: &reqwest::Response, args: &Args) -> Option<String> {
+    if !args.capture_ip {
+        return None;
     }
+    let ip = response.remote_addr().map(|addr| addr.ip().to_string());
+    if ip.is_none() {
+        log::debug!("No peer address available for {}", response.url());
+    }
+    ip
+}
 
-    Ok(())
+/// Classify a failed request as a timeout, connection or network error
+fn request_error(e: reqwest::Error, url: &str, args: &Args) -> anyhow::Error {
+    let error = if e.is_timeout() {
+        ScraperError::Timeout(args.timeout)
+    } else if e.is_connect() {
+        ScraperError::ConnectFailed(
+            connect_error_kind(&e),
+            format!("Connection failed to {}: {}", url, e),
+        )
+    } else if e.is_request() {
+        ScraperError::Interrupted(format!("Request error for {}: {}", url, e))
+    } else {
+        return ScraperError::HttpError(e).into();
+    };
+    // Keep the reqwest error and its causes in the chain for --verbose-errors
+    anyhow::Error::new(e).context(error)
 }
 
-/// Format results as JSON
-fn format_json(results: &[ScrapedData]) -> Result<String> {
-    Ok(serde_json::to_string_pretty(results)?)
+/// Read a response body; a slow-streaming body shouldn't stall the run, so --read-timeout
+/// bounds just this part
+async fn read_body<T>(
+    body: impl std::future::Future<Output = reqwest::Result<T>>,
+    url: &str,
+    args: &Args,
+) -> Result<T> {
+    let body = match args.read_timeout {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), body)
+            .await
+            .map_err(|_| {
+                log::warn!("⏱️  Body of {} did not finish within {}s", url, secs);
+                ScraperError::Timeout(secs)
+            })?,
+        None => body.await,
+    };
+    Ok(body.map_err(|e| {
+        ScraperError::Interrupted(format!("Failed to read response body from {}: {}", url, e))
+    })?)
 }
 
-/// Format results as CSV
-fn format_csv(results: &[ScrapedData]) -> Result<String> {
-    let mut writer = csv::Writer::from_writer(vec![]);
+/// Fetch and extract a single page using the given user agent
+async fn scrape_with_user_agent(
+    url: &str,
+    args: &Args,
+    depth: Option<usize>,
+    user_agent: &str,
+    limits: Option<&RunLimits>,
+) -> Result<ScrapedData> {
+    log::debug!("Fetching: {}", url);
 
-    // Write header
-    writer.write_record(&[
-        "url",
-        "status_code",
-        "title",
-        "headings_count",
-        "paragraphs_count",
-        "links_count",
-        "images_count",
-        "tables_count",
-        "code_blocks_count",
-        "depth",
-    ])?;
+    let client = build_client(args, user_agent, url)?;
 
-    // Write data rows
-    for data in results {
-        writer.write_record(&[
-            &data.url,
-            &data.status_code.to_string(),
-            &data.title.clone().unwrap_or_default(),
-            &data.headings.len().to_string(),
-            &data.paragraphs.len().to_string(),
-            &data.links.len().to_string(),
-            &data.images.len().to_string(),
-            &data.tables.len().to_string(),
-            &data.code_blocks.len().to_string(),
-            &data.depth.map(|d| d.to_string()).unwrap_or_default(),
-        ])?;
-    }
+    // Fetch the page with enhanced error handling
+    let response = client.get(url).send().await.map_err(|e| request_error(e, url, args))?;
 
-    Ok(String::from_utf8(writer.into_inner()?)?)
-}
+    let status_code = response.status().as_u16();
+    let base_url = Url::parse(url)?;
+    let final_url = if response.url() != &base_url {
+        Some(response.url().to_string())
+    } else {
+        None
+    };
 
-/// Truncate text to a maximum length with ellipsis
-fn truncate_text(text: &str, max_len: usize) -> String {
-    if text.len() > max_len {
-        format!("{}...", &text[..max_len])
+    // Check HTTP status code and provide detailed error messages
+    classify_http_status(status_code, url)?;
+
+    // Cache validators, kept for later diffing and conditional requests
+    let header_value = |name: reqwest::header::HeaderName| {
+        args.capture_cache_headers
+            .then(|| response.headers().get(name)?.to_str().ok().map(str::to_string))
+            .flatten()
+    };
+    let etag = header_value(reqwest::header::ETAG);
+    let last_modified = header_value(reqwest::header::LAST_MODIFIED);
+    let resolved_ip = peer_ip(&response, args);
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+
+    let html = read_body(response.text(), url, args).await?;
+    // A 200 with nothing at all in it is a flaky edge node, not an empty page
+    if args.retry_on_empty_body && status_code == 200 && html.is_empty() {
+        return Err(ScraperError::Interrupted(format!("Empty response body from {}", url)).into());
+    }
+    let content_bytes = args.capture_size.then_some(html.len());
+
+    // --prefer-amp: extract from the lighter AMP variant, keeping the original as the page URL
+    let amp_link = if args.prefer_amp {
+        extract_amphtml_url(&Html::parse_document(&html), &base_url).filter(|amp| amp != url)
     } else {
-        text.to_string()
+        None
+    };
+    if let Some(amp_url) = amp_link {
+        match fetch_document(&client, &amp_url, args, limits).await {
+            Ok((_, amp_html)) => {
+                log::info!("⚡ Using AMP version of {}: {}", url, amp_url);
+                return Ok(ScrapedData {
+                    url: url.to_string(),
+                    final_url,
+                    amp_url: Some(amp_url.clone()),
+                    etag,
+                    last_modified,
+                    resolved_ip,
+                    content_bytes,
+                    retry_after,
+                    body_bytes: (html.len() + amp_html.len()) as u64,
+                    ..extract_page(&amp_url, &amp_html, status_code, args, depth)?
+                });
+            }
+            Err(e) => log::warn!("AMP version {} failed ({}), using {}", amp_url, e, url),
+        }
     }
-}
 
-/// Format a list with a preview limit
-fn format_text_list<F>(
-    output: &mut String,
-    title: &str,
-    items: &[String],
-    preview_limit: usize,
-    format_fn: F,
-) where
-    F: Fn(&str) -> String,
-{
-    if items.is_empty() {
-        return;
-    }
+    Ok(ScrapedData {
+        final_url,
+        etag,
+        last_modified,
+        resolved_ip,
+        content_bytes,
+        retry_after,
+        body_bytes: html.len() as u64,
+        ..extract_page(url, &html, status_code, args, depth)?
+    })
+}
 
-    output.push_str(&format!("\n{} ({}):\n", title, items.len()));
-    for item in items.iter().take(preview_limit) {
-        output.push_str(&format_fn(item));
-    }
-    if items.len() > preview_limit {
-        output.push_str(&format!(
-            "  ... and {} more\n",
-            items.len() - preview_limit
-        ));
-    }
+/// rel="amphtml" alternate declared by a page, resolved to absolute
+fn extract_amphtml_url(document: &Html, base_url: &Url) -> Option<String> {
+    let link_selector = Selector::parse("link[rel][href]").unwrap();
+    document
+        .select(&link_selector)
+        .find(|el| {
+            el.value()
+                .attr("rel")
+                .is_some_and(|rel| rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("amphtml")))
+        })
+        .and_then(|el| normalize_url(base_url, el.value().attr("href")?.trim()))
 }
 
-/// Format metadata section for text output
-fn format_text_metadata(metadata: &Metadata) -> String {
-    let mut output = String::from("\nMetadata:\n");
+/// GET a supporting document (AMP variant, feed, sitemap) under the same controls as a page:
+/// paced by `limits`, body bounded by --read-timeout and transient failures retried
+/// Returns the URL the document was served from along with its text
+async fn fetch_document(
+    client: &reqwest::Client,
+    url: &str,
+    args: &Args,
+    limits: Option<&RunLimits>,
+) -> Result<(Url, String)> {
+    fetch_document_with(client, url, args, limits, reqwest::Response::text).await
+}
 
-    if let Some(desc) = &metadata.description {
-        output.push_str(&format!("  Description: {}\n", desc));
-    }
-    if let Some(keywords) = &metadata.keywords {
-        output.push_str(&format!("  Keywords: {}\n", keywords));
-    }
-    if let Some(author) = &metadata.author {
-        output.push_str(&format!("  Author: {}\n", author));
-    }
-    if let Some(og_title) = &metadata.og_title {
-        output.push_str(&format!("  OG Title: {}\n", og_title));
-    }
-    if let Some(og_image) = &metadata.og_image {
-        output.push_str(&format!("  OG Image: {}\n", og_image));
-    }
+/// Like fetch_document, but returning the raw body bytes (for gzip-compressed sitemaps)
+async fn fetch_document_bytes(
+    client: &reqwest::Client,
+    url: &str,
+    args: &Args,
+    limits: Option<&RunLimits>,
+) -> Result<(Url, Vec<u8>)> {
+    let (served_from, body) = fetch_document_with(client, url, args, limits, reqwest::Response::bytes).await?;
+    Ok((served_from, body.to_vec()))
+}
 
-    output
+async fn fetch_document_with<T, R, Fut>(
+    client: &reqwest::Client,
+    url: &str,
+    args: &Args,
+    limits: Option<&RunLimits>,
+    read: R,
+) -> Result<(Url, T)>
+where
+    R: Fn(reqwest::Response) -> Fut,
+    Fut: std::future::Future<Output = reqwest::Result<T>>,
+{
+    if let Some(limits) = limits {
+        limits.pace(url).await;
+    }
+    let (document, _) = retry_transient(url, args, limits, || async {
+        let response = client.get(url).send().await.map_err(|e| request_error(e, url, args))?;
+        classify_http_status(response.status().as_u16(), url)?;
+        let served_from = response.url().clone();
+        Ok((served_from, read_body(read(response), url, args).await?))
+    })
+    .await?;
+    Ok(document)
 }
 
-/// Format custom selectors section for text output
-fn format_text_custom_selectors(custom_selectors: &[CustomSelectorResult]) -> String {
-    let mut output = String::from("\nCustom Selectors:\n");
+/// Run the extraction pipeline over a page's HTML (fetched, or read back by --replay)
+fn extract_page(
+    url: &str,
+    html: &str,
+    status_code: u16,
+    args: &Args,
+    depth: Option<usize>,
+) -> Result<ScrapedData> {
+    let base_url = Url::parse(url)?;
 
-    for result in custom_selectors {
-        output.push_str(&format!(
-            "  '{}' ({} matches):\n",
-            result.selector,
-            result.matches.len()
-        ));
-        for (i, match_text) in result.matches.iter().take(3).enumerate() {
-            output.push_str(&format!("    {}. {}\n", i + 1, match_text));
+    // Bound parser work on huge documents (the head, and so the title, comes first)
+    let parse_input = match args.max_parse_bytes {
+        Some(max) if html.len() > max => {
+            log::warn!(
+                "✂️  {} is {} bytes; parsing only the first {} (--max-parse-bytes)",
+                url,
+                html.len(),
+                max
+            );
+            truncate_at_char_boundary(html, max)
         }
-        if result.matches.len() > 3 {
-            output.push_str(&format!(
-                "    ... and {} more\n",
-                result.matches.len() - 3
-            ));
+        _ => html,
+    };
+    let document = Html::parse_document(parse_input);
+
+    if let Some(target) = &args.dump_dom {
+        if let Err(e) = dump_dom(&document, url, target) {
+            log::warn!("Failed to dump DOM for {}: {}", url, e);
         }
     }
 
-    output
-}
+    // Browsers never render <template> contents, so neither do we unless asked
+    let document = if args.include_templates { document } else { strip_templates(document) };
 
-/// Format results as plain text
-fn format_text(results: &[ScrapedData]) -> String {
-    let mut output = String::new();
+    // Extract content using helper functions
+    let title = extract_title(&document);
 
-    for (i, data) in results.iter().enumerate() {
-        if i > 0 {
-            output.push_str("\n\n");
-            output.push_str(&"=".repeat(80));
-            output.push_str("\n\n");
-        }
+    // Detect anti-bot protection features
+    if let Some(anti_bot_msg) = detect_anti_bot_features(html, title.as_deref()) {
+        log::warn!("Anti-bot detection for {}: {}", url, anti_bot_msg);
+        return Err(ScraperError::AntiBotDetected(anti_bot_msg).into());
+    }
 
-        // Basic info
-        output.push_str(&format!("URL: {}\n", data.url));
-        output.push_str(&format!("Status: {}\n", data.status_code));
+    // Syndicated copies (AMP, CDN mirrors) should resolve links as the original page would
+    let link_base = if args.resolve_against_canonical {
+        extract_canonical_url(&document, &base_url).unwrap_or_else(|| base_url.clone())
+    } else {
+        base_url.clone()
+    };
 
-        if let Some(depth) = data.depth {
-            output.push_str(&format!("Depth: {}\n", depth));
+    // --root-selector narrows content extraction to one container; head data still comes
+    // from the whole page
+    let root = match &args.root_selector {
+        Some(selector) => narrow_to_root(&document, selector, url)?,
+        None => None,
+    };
+    let content = root.as_ref().unwrap_or(&document);
+
+    // Each extractor is guarded so one failing on odd markup doesn't lose the whole page
+    let headings = guarded_extract("headings", url, || extract_headings(content));
+    let paragraphs = guarded_extract("paragraphs", url, || extract_paragraphs(content));
+    let mut links = guarded_extract("links", url, || extract_links(content, &link_base));
+    if let Some(max) = args.max_link_text {
+        for link in &mut links {
+            link.text = truncate_with_ellipsis(&link.text, max);
         }
+    }
+    let (links, internal_links, external_links) = if args.separate_external {
+        let (internal, external) = split_external_links(links, &base_url);
+        (Vec::new(), internal, external)
+    } else {
+        (links, Vec::new(), Vec::new())
+    };
+    let mut images = guarded_extract("images", url, || extract_images(content, &link_base));
+    if args.background_images {
+        images.extend(guarded_extract("background images", url, || {
+            extract_background_images(content, &link_base)
+        }));
+    }
+    let primary_image = guarded_extract("primary image", url, || {
+        extract_primary_image(&document, &link_base)
+    });
+    let tables = guarded_extract("tables", url, || extract_tables(content));
+    let code_blocks = guarded_extract("code blocks", url, || extract_code_blocks(content));
+    let (abbreviations, times) = if args.semantic_inline {
+        guarded_extract("semantic inline", url, || extract_semantic_inline(content))
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let app_state = if args.extract_app_state {
+        guarded_extract("app state", url, || extract_app_state(&document))
+    } else {
+        None
+    };
+    let scripts = if args.collect_scripts {
+        guarded_extract("scripts", url, || extract_assets(&document, "script[src]", "src", &base_url))
+    } else {
+        Vec::new()
+    };
+    let stylesheets = if args.collect_stylesheets {
+        guarded_extract("stylesheets", url, || {
+            extract_assets(&document, STYLESHEET_SELECTOR, "href", &base_url)
+        })
+    } else {
+        Vec::new()
+    };
+    let sections = if args.section_stats {
+        guarded_extract("sections", url, || extract_sections(content))
+    } else {
+        Vec::new()
+    };
+    let (dom_node_count, dom_max_depth) = if args.dom_stats {
+        let (count, max_depth) = guarded_extract("dom stats", url, || dom_stats(&document));
+        (Some(count), Some(max_depth))
+    } else {
+        (None, None)
+    };
+    let svg = if args.svg {
+        guarded_extract("svg", url, || extract_svg(content, &link_base))
+    } else {
+        Vec::new()
+    };
+    let contacts = if args.contacts {
+        guarded_extract("contacts", url, || extract_contacts(content))
+    } else {
+        Vec::new()
+    };
 
-        if let Some(title) = &data.title {
-            output.push_str(&format!("Title: {}\n", title));
+    let (next_page, prev_page) = guarded_extract("pagination", url, || {
+        extract_pagination(&document, &link_base)
+    });
+
+    // Extract metadata if requested
+    let metadata = if args.metadata {
+        guarded_extract("metadata", url, || Some(extract_metadata(&document))).map(|mut m| {
+            m.next_url = next_page.clone();
+            m.prev_url = prev_page;
+            m.resource_hints = extract_resource_hints(&document, &base_url);
+            m
+        })
+    } else {
+        None
+    };
+
+    let all_meta = if args.all_meta {
+        guarded_extract("all meta", url, || extract_all_meta(&document))
+    } else {
+        Vec::new()
+    };
+
+    // Process custom selectors if provided
+    let custom_selectors =
+        process_custom_selectors(content, &args.selector, SelectorOptions::from_args(args))?;
+    let named_selectors = process_named_selectors(content, &args.selector_name, args)?;
+    let requirement_failures = check_requirements(&document, title.as_deref(), args)?;
+    let canonical = if args.collapse_canonical || args.dedup_by_declared_canonical {
+        extract_canonical_url(&document, &base_url).map(|u| dedup_key(u.as_str()))
+    } else {
+        None
+    };
+    let audit = if args.audit {
+        guarded_extract("audit", url, || audit_page(&document, title.as_deref(), &base_url))
+    } else {
+        Vec::new()
+    };
+
+    Ok(ScrapedData {
+        url: url.to_string(),
+        final_url: None,
+        upgraded_from: None,
+        amp_url: None,
+        source_urls: Vec::new(),
+        status_code,
+        etag: None,
+        last_modified: None,
+        content_type: None,
+        content_length: None,
+        content_bytes: None,
+        resolved_ip: None,
+        title,
+        headings,
+        sections,
+        paragraphs,
+        links,
+        internal_links,
+        external_links,
+        images,
+        primary_image,
+        tables,
+        code_blocks,
+        abbreviations,
+        times,
+        svg,
+        contacts,
+        app_state,
+        scripts,
+        stylesheets,
+        dom_node_count,
+        dom_max_depth,
+        metadata,
+        all_meta,
+        custom_selectors,
+        named_selectors,
+        depth,
+        retries_used: None,
+        error: None,
+        error_kind: None,
+        audit,
+        user_data: None,
+        ingested_at: None,
+        next_page,
+        requirement_failures,
+        canonical,
+        retry_after: None,
+        body_bytes: 0,
+    })
+}
+
+/// Per-page SEO audit: missing title/description, canonical problems, meta refresh,
+/// and images without alt text
+fn audit_page(document: &Html, title: Option<&str>, page_url: &Url) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let attr_is = |el: &scraper::ElementRef, name: &str, value: &str| {
+        el.value().attr(name).is_some_and(|v| v.trim().eq_ignore_ascii_case(value))
+    };
+
+    if title.is_none_or(|t| t.trim().is_empty()) {
+        warnings.push("Missing <title>".to_string());
+    }
+
+    let meta_selector = Selector::parse("meta").unwrap();
+    let has_description = document.select(&meta_selector).any(|el| {
+        attr_is(&el, "name", "description")
+            && el.value().attr("content").is_some_and(|c| !c.trim().is_empty())
+    });
+    if !has_description {
+        warnings.push("Missing meta description".to_string());
+    }
+
+    if let Some(refresh) = document
+        .select(&meta_selector)
+        .find(|el| attr_is(el, "http-equiv", "refresh"))
+    {
+        warnings.push(format!(
+            "Meta refresh redirect: {}",
+            refresh.value().attr("content").unwrap_or("").trim()
+        ));
+    }
+
+    let link_selector = Selector::parse("link[rel]").unwrap();
+    let canonicals: Vec<&str> = document
+        .select(&link_selector)
+        .filter(|el| attr_is(el, "rel", "canonical"))
+        .filter_map(|el| el.value().attr("href"))
+        .collect();
+    if canonicals.len() > 1 {
+        warnings.push(format!("Multiple canonical tags ({})", canonicals.len()));
+    }
+    if let Some(canonical) = canonicals.first().and_then(|href| page_url.join(href.trim()).ok()) {
+        if canonical.host_str() != page_url.host_str() {
+            warnings.push(format!("Canonical points off-domain: {}", canonical));
+        }
+    }
+
+    let img_selector = Selector::parse("img").unwrap();
+    let missing_alt = document
+        .select(&img_selector)
+        .filter(|el| el.value().attr("alt").is_none())
+        .count();
+    if missing_alt > 0 {
+        warnings.push(format!("{} image(s) without alt text", missing_alt));
+    }
+
+    warnings
+}
+
+/// Title used to compare pages: whitespace collapsed, case folded
+fn normalize_title(title: &str) -> String {
+    clean_text(title).to_lowercase()
+}
+
+/// Groups of result indices whose (normalized, non-empty) titles are identical,
+/// in order of first appearance; only groups with two or more pages are returned
+fn duplicate_title_groups(results: &[ScrapedData]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, data) in results.iter().enumerate() {
+        let Some(title) = data.title.as_deref().map(normalize_title).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        match groups.iter_mut().find(|(key, _)| *key == title) {
+            Some((_, members)) => members.push(index),
+            None => groups.push((title, vec![index])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// Whether a page's declared canonical (or, lacking one, its own URL) belongs to a page already
+/// stored, for --dedup-by-declared-canonical; records the keys of pages that are kept
+fn is_canonical_duplicate(data: &ScrapedData, kept: &mut HashSet<String>) -> bool {
+    let own = dedup_key(data.final_url.as_deref().unwrap_or(&data.url));
+    let key = data.canonical.clone().unwrap_or_else(|| own.clone());
+    if kept.contains(&key) {
+        return true;
+    }
+    kept.insert(key);
+    kept.insert(own);
+    false
+}
+
+/// Merge pages that declare the same canonical URL (--collapse-canonical)
+/// Each group keeps the canonical page itself if it was captured, else its first member, at the
+/// position of the group's first page; pages without a canonical pass through untouched
+fn collapse_by_canonical(results: Vec<ScrapedData>) -> Vec<ScrapedData> {
+    let mut groups: Vec<(Option<String>, Vec<ScrapedData>)> = Vec::new();
+    for data in results {
+        match &data.canonical {
+            Some(canonical) => match groups.iter_mut().find(|(key, _)| key.as_ref() == Some(canonical)) {
+                Some((_, members)) => members.push(data),
+                None => groups.push((Some(canonical.clone()), vec![data])),
+            },
+            None => groups.push((None, vec![data])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(canonical, mut members)| {
+            if members.len() == 1 {
+                return members.remove(0);
+            }
+            let source_urls: Vec<String> = members.iter().map(|data| data.url.clone()).collect();
+            let keep = members
+                .iter()
+                .position(|data| {
+                    let served = data.final_url.as_deref().unwrap_or(&data.url);
+                    Some(dedup_key(served)) == canonical
+                })
+                .unwrap_or(0);
+            log::info!("🔗 Collapsed {} pages into {}", members.len(), members[keep].url);
+            ScrapedData {
+                source_urls,
+                ..members.swap_remove(keep)
+            }
+        })
+        .collect()
+}
+
+/// Run-wide audit checks that need every page: currently duplicate titles
+fn audit_across_pages(results: &mut [ScrapedData]) {
+    for group in duplicate_title_groups(results) {
+        for &index in &group {
+            let others: Vec<String> = group
+                .iter()
+                .filter(|&&other| other != index)
+                .map(|&other| results[other].url.clone())
+                .collect();
+            results[index]
+                .audit
+                .push(format!("Duplicate title shared with: {}", others.join(", ")));
+        }
+    }
+}
+
+/// Human-readable --report-duplicate-titles summary: one block per shared title
+fn duplicate_title_report(results: &[ScrapedData]) -> String {
+    let groups = duplicate_title_groups(results);
+    if groups.is_empty() {
+        return "No duplicate titles found\n".to_string();
+    }
+
+    let mut report = format!("Duplicate titles ({} cluster(s)):\n", groups.len());
+    for group in groups {
+        let title = results[group[0]].title.as_deref().unwrap_or_default().trim();
+        report.push_str(&format!("\n\"{}\" ({} pages)\n", title, group.len()));
+        for index in group {
+            report.push_str(&format!("  - {}\n", results[index].url));
+        }
+    }
+    report
+}
+
+/// Named selector values per page URL, as stored in a --diff-selectors snapshot
+type SelectorSnapshot = BTreeMap<String, BTreeMap<String, serde_json::Value>>;
+
+/// A named selector whose value differs from the snapshot (None = absent on that side)
+#[derive(Debug, PartialEq)]
+struct SelectorChange {
+    url: String,
+    name: String,
+    old: Option<serde_json::Value>,
+    new: Option<serde_json::Value>,
+}
+
+/// Read a --diff-selectors snapshot; a missing file is an empty snapshot (first run)
+fn load_selector_snapshot(path: &str) -> Result<SelectorSnapshot> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid selector snapshot {}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SelectorSnapshot::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Compare each page's named selector values with the snapshot, then store the new values
+/// Failed pages are skipped so a transient error doesn't wipe the stored values
+fn diff_selector_snapshot(snapshot: &mut SelectorSnapshot, results: &[ScrapedData]) -> Vec<SelectorChange> {
+    let mut changes = Vec::new();
+    for data in results.iter().filter(|data| data.error.is_none()) {
+        let previous = snapshot.insert(data.url.clone(), data.named_selectors.clone());
+        // A page seen for the first time is a baseline, not a change
+        let Some(previous) = previous else { continue };
+
+        let names: BTreeSet<&String> = previous.keys().chain(data.named_selectors.keys()).collect();
+        for name in names {
+            let (old, new) = (previous.get(name), data.named_selectors.get(name));
+            if old != new {
+                changes.push(SelectorChange {
+                    url: data.url.clone(),
+                    name: name.clone(),
+                    old: old.cloned(),
+                    new: new.cloned(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// Human-readable summary of --diff-selectors changes
+fn selector_change_report(changes: &[SelectorChange]) -> String {
+    if changes.is_empty() {
+        return "No selector values changed\n".to_string();
+    }
+
+    let show = |value: &Option<serde_json::Value>| match value {
+        Some(value) => value.to_string(),
+        None => "(absent)".to_string(),
+    };
+    let mut report = format!("Selector values changed ({}):\n", changes.len());
+    for change in changes {
+        report.push_str(&format!(
+            "  {} [{}]: {} -> {}\n",
+            change.url,
+            change.name,
+            show(&change.old),
+            show(&change.new)
+        ));
+    }
+    report
+}
+
+/// Remove `<template>` elements, whose contents the parser keeps as ordinary descendants
+/// `Html::select` walks every parsed node, detached ones included, so the pruned tree is
+/// re-parsed (only when the page has templates at all)
+fn strip_templates(mut document: Html) -> Html {
+    let template_selector = Selector::parse("template").unwrap();
+    let templates: Vec<_> = document.select(&template_selector).map(|el| el.id()).collect();
+    if templates.is_empty() {
+        return document;
+    }
+    for id in templates {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+    Html::parse_document(&document.html())
+}
+
+/// Re-parse the first element matching `selector` as its own document, for --root-selector
+/// Returns None (keeping the whole page) when nothing matches
+fn narrow_to_root(document: &Html, selector: &str, url: &str) -> Result<Option<Html>> {
+    let root_selector = Selector::parse(selector)
+        .map_err(|e| ScraperError::InvalidSelector(format!("'{}': {}", selector, e)))?;
+
+    match document.select(&root_selector).next() {
+        Some(root) => Ok(Some(Html::parse_fragment(&root.inner_html()))),
+        None => {
+            log::warn!("Root selector '{}' matched nothing on {}; using the whole page", selector, url);
+            Ok(None)
+        }
+    }
+}
+
+/// Check the page against --require-title and --require-selector, describing each unmet one
+fn check_requirements(document: &Html, title: Option<&str>, args: &Args) -> Result<Vec<String>> {
+    let mut failures = Vec::new();
+
+    if args.require_title && title.is_none_or(|t| t.trim().is_empty()) {
+        failures.push("missing or empty <title>".to_string());
+    }
+
+    for selector_str in &args.require_selector {
+        let selector = Selector::parse(selector_str)
+            .map_err(|e| ScraperError::InvalidSelector(format!("'{}': {}", selector_str, e)))?;
+        if document.select(&selector).next().is_none() {
+            failures.push(format!("required selector '{}' matched nothing", selector_str));
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Report pages that failed --require-title / --require-selector, one line per problem
+/// Returns an error (so the process exits nonzero) when any page failed
+fn report_requirement_failures(results: &[ScrapedData]) -> Result<()> {
+    let failed: Vec<&ScrapedData> = results
+        .iter()
+        .filter(|data| !data.requirement_failures.is_empty())
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    for data in &failed {
+        for failure in &data.requirement_failures {
+            eprintln!("❌ {}: {}", data.url, failure);
+        }
+    }
+    Err(anyhow::anyhow!(
+        "{} of {} page(s) failed validation",
+        failed.len(),
+        results.len()
+    ))
+}
+
+/// Run an extractor, logging and substituting an empty result if it panics
+fn guarded_extract<T: Default>(name: &str, url: &str, extract: impl FnOnce() -> T) -> T {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(extract)).unwrap_or_else(|_| {
+        log::error!("Failed to extract {} from {}; leaving it empty", name, url);
+        T::default()
+    })
+}
+
+/// Longest URL-derived stem kept in a --dump-dom file name, well under filesystem name limits
+const DOM_DUMP_STEM_MAX: usize = 120;
+
+/// File name for a page's --dump-dom output, derived from its URL
+/// The readable stem is lossy and capped, so a hash of the full URL after `~` (which the stem
+/// never contains) keeps distinct URLs in distinct files
+fn dom_dump_filename(url: &str) -> String {
+    let stem = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let stem: String = stem
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .take(DOM_DUMP_STEM_MAX)
+        .collect();
+    format!("{}~{:08x}.html", stem, fnv1a_32(url.as_bytes()))
+}
+
+/// 32-bit FNV-1a, used where a short hash must stay the same across runs and toolchains
+fn fnv1a_32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0x811c_9dc5, |hash, &b| (hash ^ u32::from(b)).wrapping_mul(0x0100_0193))
+}
+
+/// Write the serialized parse tree (what selectors actually run against) for --dump-dom
+fn dump_dom(document: &Html, url: &str, target: &str) -> Result<()> {
+    let dom = document.html();
+
+    if target == "-" {
+        eprintln!("<!-- DOM: {} -->\n{}", url, dom);
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(target)?;
+    let filename = dom_dump_filename(url);
+    let path = std::path::Path::new(target).join(&filename);
+    std::fs::write(&path, dom)?;
+
+    // The file name is lossy, so record the exact URL for --replay
+    let entry = serde_json::json!({ "filename": filename, "url": url });
+    let mut manifest = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(std::path::Path::new(target).join(REPLAY_MANIFEST))?;
+    std::io::Write::write_all(&mut manifest, format!("{}\n", entry).as_bytes())?;
+
+    log::debug!("🧾 Dumped DOM of {} to {}", url, path.display());
+    Ok(())
+}
+
+/// Sidecar written next to --dump-dom files: one {"filename", "url"} JSON object per line
+const REPLAY_MANIFEST: &str = "manifest.jsonl";
+
+/// Best-effort URL for an archived page without a manifest entry (inverse of dom_dump_filename)
+fn url_from_dump_filename(filename: &str) -> String {
+    let stem = filename.trim_end_matches(".html").trim_end_matches(".htm");
+    let stem = stem.split_once('~').map_or(stem, |(stem, _hash)| stem);
+    match stem.split_once('_') {
+        Some((host, path)) => format!("https://{}/{}", host, path.replace('_', "/")),
+        None => format!("https://{}/", stem),
+    }
+}
+
+/// Re-run extraction over archived HTML files in `dir` (e.g. from --dump-dom) without fetching
+fn replay_archive(dir: &str, args: &Args) -> Result<Vec<ScrapedData>> {
+    let dir_path = std::path::Path::new(dir);
+
+    let mut manifest = std::collections::HashMap::new();
+    if let Ok(lines) = fs::read_to_string(dir_path.join(REPLAY_MANIFEST)) {
+        for line in lines.lines().filter(|l| !l.trim().is_empty()) {
+            let entry: serde_json::Value = serde_json::from_str(line)?;
+            if let (Some(filename), Some(url)) = (entry["filename"].as_str(), entry["url"].as_str()) {
+                manifest.insert(filename.to_string(), url.to_string());
+            }
+        }
+    }
+
+    let mut files: Vec<String> = fs::read_dir(dir_path)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".html") || name.ends_with(".htm"))
+        .collect();
+    files.sort();
+    log::info!("📼 Replaying {} archived page(s) from {}", files.len(), dir);
+
+    let mut results = Vec::new();
+    for filename in files {
+        let url = manifest
+            .get(&filename)
+            .cloned()
+            .unwrap_or_else(|| url_from_dump_filename(&filename));
+        let html = fs::read_to_string(dir_path.join(&filename))?;
+
+        match extract_page(&url, &html, 200, args, None) {
+            Ok(data) => results.push(data),
+            Err(e) => {
+                log::error!("Failed to replay {} ({}): {}", filename, url, e);
+                if args.include_errors {
+                    results.push(error_result(&url, &e, None));
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Extract metadata from the HTML document
+fn extract_metadata(document: &Html) -> Metadata {
+    let meta_selector = Selector::parse("meta").unwrap();
+    let link_selector = Selector::parse("link").unwrap();
+
+    let mut metadata = Metadata::default();
+
+    // Extract meta tags
+    for element in document.select(&meta_selector) {
+        let name = element.value().attr("name").or_else(|| element.value().attr("property"));
+        let content = element.value().attr("content");
+
+        if let (Some(name), Some(content)) = (name, content) {
+            match name.to_lowercase().as_str() {
+                "description" => metadata.description = Some(content.to_string()),
+                "keywords" => metadata.keywords = Some(content.to_string()),
+                "author" => metadata.author = Some(content.to_string()),
+                "og:title" => metadata.og_title = Some(content.to_string()),
+                "og:description" => metadata.og_description = Some(content.to_string()),
+                "og:image" => metadata.og_image = Some(content.to_string()),
+                "og:url" => metadata.og_url = Some(content.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    metadata.geo = extract_geo(document);
+
+    // Extract canonical URL and favicon
+    for element in document.select(&link_selector) {
+        let rel = element.value().attr("rel");
+        let href = element.value().attr("href");
+
+        if let (Some(rel), Some(href)) = (rel, href) {
+            match rel.to_lowercase().as_str() {
+                "canonical" => metadata.canonical_url = Some(href.to_string()),
+                "icon" | "shortcut icon" => metadata.favicon = Some(href.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    metadata
+}
+
+/// Every `<meta>` with a key attribute and content, in document order
+fn extract_all_meta(document: &Html) -> Vec<MetaTag> {
+    let meta_selector = Selector::parse("meta[content]").unwrap();
+    document
+        .select(&meta_selector)
+        .filter_map(|el| {
+            let key = ["name", "property", "http-equiv", "itemprop"]
+                .iter()
+                .find_map(|attr| el.value().attr(attr))?;
+            Some(MetaTag {
+                key: key.trim().to_string(),
+                value: el.value().attr("content")?.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `rel` values that announce resources or origins the page will need
+const RESOURCE_HINT_RELS: &[&str] = &["preload", "prefetch", "preconnect", "dns-prefetch"];
+
+/// Collect resource hint <link>s, one entry per matching rel token
+fn extract_resource_hints(document: &Html, base_url: &Url) -> Vec<ResourceHint> {
+    let link_selector = Selector::parse("link[rel][href]").unwrap();
+    let mut hints = Vec::new();
+
+    for element in document.select(&link_selector) {
+        let Some(href) = element.value().attr("href").map(str::trim).filter(|h| !h.is_empty()) else {
+            continue;
+        };
+        let Some(href) = normalize_url(base_url, href) else { continue };
+        let rels = element.value().attr("rel").unwrap_or_default().to_lowercase();
+        for rel in rels.split_whitespace().filter(|r| RESOURCE_HINT_RELS.contains(r)) {
+            hints.push(ResourceHint { rel: rel.to_string(), href: href.clone() });
         }
+    }
+
+    hints
+}
+
+/// Extract location data: geo.position/ICBM and geo.placename meta tags,
+/// then schema.org PostalAddress and GeoCoordinates from JSON-LD or microdata
+fn extract_geo(document: &Html) -> Option<Geo> {
+    let meta_selector = Selector::parse("meta[name]").unwrap();
+    let mut geo = Geo::default();
+    let mut placename = None;
+
+    for element in document.select(&meta_selector) {
+        let name = element.value().attr("name").unwrap_or("").to_lowercase();
+        let Some(content) = element.value().attr("content") else { continue };
+
+        match name.as_str() {
+            "geo.position" | "icbm" => {
+                let mut parts = content.split([';', ',']).map(|p| p.trim().parse::<f64>().ok());
+                if let (Some(Some(lat)), Some(Some(lon))) = (parts.next(), parts.next()) {
+                    geo.lat.get_or_insert(lat);
+                    geo.lon.get_or_insert(lon);
+                }
+            }
+            "geo.placename" if !content.trim().is_empty() => {
+                placename = Some(content.trim().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    // JSON-LD blocks
+    let script_selector = Selector::parse(r#"script[type="application/ld+json"]"#).unwrap();
+    for script in document.select(&script_selector) {
+        let json = script.text().collect::<String>();
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(&json) {
+            collect_json_ld_geo(&value, &mut geo);
+        }
+    }
+
+    // Microdata
+    let item_selector = Selector::parse("[itemscope][itemtype]").unwrap();
+    for item in document.select(&item_selector) {
+        let item_type = item.value().attr("itemtype").unwrap_or("");
+        let prop = |name: &str| {
+            let selector = Selector::parse(&format!(r#"[itemprop="{}"]"#, name)).ok()?;
+            let element = item.select(&selector).next()?;
+            let value = match element.value().attr("content") {
+                Some(content) => content.to_string(),
+                None => element.text().collect::<String>(),
+            };
+            let value = clean_text(&value);
+            (!value.is_empty()).then_some(value)
+        };
+
+        if item_type.ends_with("/PostalAddress") && geo.address.is_none() {
+            let parts: Vec<String> = POSTAL_ADDRESS_PARTS.iter().filter_map(|p| prop(p)).collect();
+            if !parts.is_empty() {
+                geo.address = Some(parts.join(", "));
+            }
+        } else if item_type.ends_with("/GeoCoordinates") && geo.lat.is_none() {
+            let lat = prop("latitude").and_then(|v| v.parse().ok());
+            let lon = prop("longitude").and_then(|v| v.parse().ok());
+            if let (Some(lat), Some(lon)) = (lat, lon) {
+                geo.lat = Some(lat);
+                geo.lon = Some(lon);
+            }
+        }
+    }
+
+    if geo.address.is_none() {
+        geo.address = placename;
+    }
+
+    (geo != Geo::default()).then_some(geo)
+}
+
+/// schema.org PostalAddress properties, in display order
+const POSTAL_ADDRESS_PARTS: &[&str] = &[
+    "streetAddress",
+    "addressLocality",
+    "addressRegion",
+    "postalCode",
+    "addressCountry",
+];
+
+/// Walk a JSON-LD value looking for PostalAddress and GeoCoordinates nodes
+fn collect_json_ld_geo(value: &serde_json::Value, geo: &mut Geo) {
+    use serde_json::Value;
+
+    // Numbers and numeric strings are both common in the wild
+    fn as_f64(value: Option<&Value>) -> Option<f64> {
+        match value? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn as_text(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            // e.g. "addressCountry": {"@type": "Country", "name": "SE"}
+            Value::Object(map) => map.get("name").and_then(as_text),
+            _ => None,
+        }
+    }
+
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_json_ld_geo(item, geo)),
+        Value::Object(map) => {
+            match map.get("@type").and_then(Value::as_str) {
+                Some("PostalAddress") if geo.address.is_none() => {
+                    let parts: Vec<String> = POSTAL_ADDRESS_PARTS
+                        .iter()
+                        .filter_map(|p| map.get(*p).and_then(as_text))
+                        .collect();
+                    if !parts.is_empty() {
+                        geo.address = Some(parts.join(", "));
+                    }
+                }
+                Some("GeoCoordinates") if geo.lat.is_none() => {
+                    if let (Some(lat), Some(lon)) =
+                        (as_f64(map.get("latitude")), as_f64(map.get("longitude")))
+                    {
+                        geo.lat = Some(lat);
+                        geo.lon = Some(lon);
+                    }
+                }
+                _ => {}
+            }
+            map.values().for_each(|child| collect_json_ld_geo(child, geo));
+        }
+        _ => {}
+    }
+}
+
+/// Output results in the requested format
+fn output_results(results: &[ScrapedData], args: &Args) -> Result<()> {
+    let kept: Vec<ScrapedData>;
+    let results = match &args.keep_status {
+        Some(codes) => {
+            let codes = parse_status_list(codes)?;
+            kept = results
+                .iter()
+                .filter(|data| codes.contains(&data.status_code))
+                .cloned()
+                .collect();
+            log::info!("🔎 Keeping {} of {} page(s) by status code", kept.len(), results.len());
+            &kept
+        }
+        None => results,
+    };
+
+    if let Some(path) = &args.merge_into {
+        let fields = args.fields.as_deref().map(parse_field_list).transpose()?;
+        merge_into_file(results, path, fields.as_deref())?;
+        log::info!("💾 Merged {} page(s) into: {}", results.len(), path);
+        return Ok(());
+    }
+
+    // Handle per-page output mode
+    if args.output_per_page {
+        // Validation in main() ensures args.output is Some when output_per_page is true
+        let output_prefix = args.output.as_ref().unwrap();
+
+        // Determine file extension based on format
+        let extension = match args.format.to_lowercase().as_str() {
+            "json" => "json",
+            "ndjson" => "ndjson",
+            "csv" => "csv",
+            "text" | "txt" => "txt",
+            "parquet" => "parquet",
+            "sitemap" => "xml",
+            "mermaid" => "mmd",
+            "urls" => "txt",
+            other => {
+                log::error!("Unknown format: {}", other);
+                return Err(anyhow::anyhow!(
+                    "Unknown format '{}'. Use: json, ndjson, csv, text, parquet, sitemap, mermaid, or urls",
+                    other
+                ));
+            }
+        };
+
+        log::info!("💾 Writing {} pages to individual files with prefix '{}'", results.len(), output_prefix);
+
+        // Write each result to a separate file, remembering which page went where
+        let mut manifest = Vec::new();
+        for (index, data) in results.iter().enumerate() {
+            let mut filename = format!("{}_{:03}.{}", output_prefix, index + 1, extension);
+            if args.gzip_output && extension != "parquet" {
+                filename.push_str(".gz");
+            }
+            manifest.push(serde_json::json!({
+                "filename": filename,
+                "url": data.url,
+                "status_code": data.status_code,
+                "title": data.title,
+            }));
+
+            if extension == "parquet" {
+                write_parquet(std::slice::from_ref(data), &filename)?;
+                log::info!("  ✓ Saved: {}", filename);
+                continue;
+            }
+
+            // Format single result
+            let output_str = format_results(
+                std::slice::from_ref(data),
+                args,
+                Palette::new(&args.color, false),
+            )?;
+
+            write_output_file(&filename, &output_str, args.gzip_output)?;
+            log::info!("  ✓ Saved: {}", filename);
+        }
+
+        let manifest_file = format!("{}_manifest.json", output_prefix);
+        std::fs::write(&manifest_file, serde_json::to_string_pretty(&manifest)?)?;
+        log::info!("  ✓ Saved: {} (file → URL index)", manifest_file);
+
+        log::info!("✅ All {} pages saved successfully", results.len());
+        return Ok(());
+    }
+
+    // Parquet is written straight to the output file (main() ensures --output is set)
+    if !args.tree && !args.links_only && args.format.eq_ignore_ascii_case("parquet") {
+        let output_file = args.output.as_ref().unwrap();
+        write_parquet(results, output_file)?;
+        log::info!("💾 Output saved to: {}", output_file);
+        return Ok(());
+    }
+
+    // Standard output mode - all results in one file/stdout
+    let output_str = if args.tree {
+        format_tree(results)
+    } else if args.links_only {
+        format_link_lines(results)?
+    } else {
+        let to_terminal = args.output.is_none() && std::io::stdout().is_terminal();
+        format_results(results, args, Palette::new(&args.color, to_terminal))?
+    };
+
+    // Write to file or stdout
+    if let Some(output_file) = &args.output {
+        write_output_file(output_file, &output_str, args.gzip_output || output_file.ends_with(".gz"))?;
+        log::info!("💾 Output saved to: {}", output_file);
+    } else if !args.quiet {
+        println!("{}", output_str);
+    }
+
+    Ok(())
+}
+
+/// Write formatted output to a file, gzip-compressed when requested
+fn write_output_file(path: &str, contents: &str, gzip: bool) -> Result<()> {
+    if !gzip {
+        fs::write(path, contents)?;
+        return Ok(());
+    }
+    let mut encoder =
+        flate2::write::GzEncoder::new(fs::File::create(path)?, flate2::Compression::default());
+    encoder.write_all(contents.as_bytes())?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Format results in the requested --format, applying any --fields projection
+fn format_results(results: &[ScrapedData], args: &Args, palette: Palette) -> Result<String> {
+    let fields = args.fields.as_deref().map(parse_field_list).transpose()?;
+
+    match (args.format.to_lowercase().as_str(), fields) {
+        ("json", Some(fields)) => {
+            let mut projected = project_fields(results, &fields)?;
+            if args.omit_empty {
+                projected.iter_mut().for_each(strip_empty);
+            }
+            Ok(serde_json::to_string_pretty(&projected)?)
+        }
+        ("json", None) if args.flat_json => {
+            let mut value = serde_json::Value::Array(results.iter().map(flatten_result).collect());
+            if args.omit_empty {
+                strip_empty(&mut value);
+            }
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        ("json", None) if args.omit_empty => {
+            let mut value = serde_json::to_value(results)?;
+            strip_empty(&mut value);
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        ("json", None) => format_json(results),
+        ("ndjson", _) => results.iter().map(|data| Ok(ndjson_line(data, args)? + "\n")).collect(),
+        ("csv", Some(fields)) => format_csv_projected(&project_fields(results, &fields)?, &fields),
+        ("csv", None) => format_csv(results, args.csv_title_max),
+        ("text" | "txt", _) => Ok(format_text(results, palette)),
+        ("sitemap", _) => Ok(format_sitemap(results)),
+        ("mermaid", _) => Ok(format_mermaid(results)),
+        ("urls", _) => Ok(format_urls(results, args.include_link_urls)),
+        (other, _) => {
+            log::error!("Unknown format: {}", other);
+            Err(anyhow::anyhow!(
+                "Unknown format '{}'. Use: json, ndjson, csv, text, parquet, sitemap, mermaid, or urls",
+                other
+            ))
+        }
+    }
+}
+
+/// Upsert results into a JSON object keyed by URL, creating the file if needed
+/// Existing keys keep their position, so the file stays stable across runs
+fn merge_into_file(results: &[ScrapedData], path: &str, fields: Option<&[String]>) -> Result<()> {
+    let mut merged = match fs::read_to_string(path) {
+        Ok(existing) if !existing.trim().is_empty() => match serde_json::from_str(&existing)? {
+            serde_json::Value::Object(map) => map,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "{} is not a JSON object keyed by URL; refusing to merge into it",
+                    path
+                ))
+            }
+        },
+        Ok(_) => serde_json::Map::new(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::Map::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let values = match fields {
+        Some(fields) => project_fields(results, fields)?,
+        None => results.iter().map(serde_json::to_value).collect::<Result<_, _>>()?,
+    };
+    for (data, value) in results.iter().zip(values) {
+        merged.insert(data.url.clone(), value);
+    }
+
+    fs::write(path, serde_json::to_string_pretty(&merged)?)?;
+    Ok(())
+}
+
+/// Parse a comma-separated --fields list, rejecting names that aren't ScrapedData fields
+fn parse_field_list(fields: &str) -> Result<Vec<String>> {
+    let fields: Vec<String> = fields
+        .split(',')
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+
+    if fields.is_empty() {
+        return Err(anyhow::anyhow!("--fields needs at least one field name"));
+    }
+    if let Some(unknown) = fields.iter().find(|f| !SCRAPED_DATA_FIELDS.contains(&f.as_str())) {
+        return Err(anyhow::anyhow!(
+            "Unknown field '{}'. Available fields: {}",
+            unknown,
+            SCRAPED_DATA_FIELDS.join(", ")
+        ));
+    }
+
+    Ok(fields)
+}
+
+/// Keep only the requested fields of each result, in the requested order
+/// Fields omitted from serialization (empty or unset) come out as null
+fn project_fields(results: &[ScrapedData], fields: &[String]) -> Result<Vec<serde_json::Value>> {
+    results
+        .iter()
+        .map(|data| {
+            let mut full = serde_json::to_value(data)?;
+            let projected: serde_json::Map<String, serde_json::Value> = fields
+                .iter()
+                .map(|field| {
+                    let value = full.get_mut(field).map(serde_json::Value::take);
+                    (field.clone(), value.unwrap_or(serde_json::Value::Null))
+                })
+                .collect();
+            Ok(serde_json::Value::Object(projected))
+        })
+        .collect()
+}
+
+/// Format projected results as CSV, one column per field
+/// Nested values (links, images, ...) are embedded as JSON strings
+fn format_csv_projected(projected: &[serde_json::Value], fields: &[String]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(fields)?;
+
+    for row in projected {
+        let cells: Vec<String> = fields
+            .iter()
+            .map(|field| match &row[field] {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect();
+        writer.write_record(&cells)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Format results as JSON
+fn format_json(results: &[ScrapedData]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(results)?)
+}
+
+/// One page as a compact JSON line for --format ndjson, honoring --fields, --flat-json and
+/// --omit-empty like the JSON array output
+fn ndjson_line(data: &ScrapedData, args: &Args) -> Result<String> {
+    let fields = args.fields.as_deref().map(parse_field_list).transpose()?;
+    let mut value = match fields {
+        Some(fields) => project_fields(std::slice::from_ref(data), &fields)?.remove(0),
+        None if args.flat_json => flatten_result(data),
+        None => serde_json::to_value(data)?,
+    };
+    if args.omit_empty {
+        strip_empty(&mut value);
+    }
+    Ok(serde_json::to_string(&value)?)
+}
+
+/// Format successfully fetched (HTTP 200) pages as an XML sitemap `urlset`
+/// `lastmod` is included for pages with a captured Last-Modified header
+fn format_sitemap(results: &[ScrapedData]) -> String {
+    let mut output = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    let mut seen = HashSet::new();
+    for data in results.iter().filter(|d| d.status_code == 200 && d.error.is_none()) {
+        let loc = data.final_url.as_deref().unwrap_or(&data.url);
+        if !seen.insert(loc) {
+            continue;
+        }
+        output.push_str("  <url>\n");
+        output.push_str(&format!("    <loc>{}</loc>\n", xml_escape(loc)));
+        if let Some(lastmod) = data.last_modified.as_deref().and_then(http_date_to_w3c) {
+            output.push_str(&format!("    <lastmod>{}</lastmod>\n", lastmod));
+        }
+        output.push_str("  </url>\n");
+    }
+
+    output.push_str("</urlset>\n");
+    output
+}
+
+/// Every link of every page as a compact JSON object per line, tagged with the page it was
+/// found on (--links-only)
+fn format_link_lines(results: &[ScrapedData]) -> Result<String> {
+    let mut output = String::new();
+    for data in results {
+        for link in data.all_links() {
+            let line = serde_json::json!({
+                "source_url": data.url,
+                "text": link.text,
+                "url": link.url,
+                "depth": data.depth,
+            });
+            output.push_str(&serde_json::to_string(&line)?);
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+/// Sorted, deduplicated page URLs one per line (usable as a --url-file), plus every link
+/// URL when `include_links` is set
+fn format_urls(results: &[ScrapedData], include_links: bool) -> String {
+    let mut urls: BTreeSet<&str> = results.iter().map(|d| d.url.as_str()).collect();
+    if include_links {
+        urls.extend(results.iter().flat_map(|d| d.all_links()).map(|l| l.url.as_str()));
+    }
+    urls.into_iter().map(|url| format!("{}\n", url)).collect()
+}
+
+/// Longest page label in --format mermaid before it is cut with an ellipsis
+const MERMAID_LABEL_MAX: usize = 40;
+
+/// Format the crawl's link structure as a Mermaid `graph TD`
+/// Nodes are captured pages labeled by title (else URL); edges are links between captured pages
+fn format_mermaid(results: &[ScrapedData]) -> String {
+    let mut output = String::from("graph TD\n");
+
+    let keys: Vec<String> = results.iter().map(|data| dedup_key(&data.url)).collect();
+    for (index, data) in results.iter().enumerate() {
+        let label = data.title.as_deref().map(str::trim).filter(|t| !t.is_empty()).unwrap_or(&data.url);
+        let mut short: String = label.chars().take(MERMAID_LABEL_MAX).collect();
+        if label.chars().count() > MERMAID_LABEL_MAX {
+            short.push('…');
+        }
+        // Mermaid labels are quoted, so quotes must use its entity syntax
+        output.push_str(&format!("    n{}[\"{}\"]\n", index, short.replace('"', "#quot;")));
+    }
+
+    for (index, data) in results.iter().enumerate() {
+        let mut targets = Vec::new();
+        for link in data.all_links() {
+            let key = dedup_key(&link.url);
+            if let Some(target) = keys.iter().position(|k| *k == key) {
+                if target != index && !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        for target in targets {
+            output.push_str(&format!("    n{} --> n{}\n", index, target));
+        }
+    }
+
+    output
+}
+
+/// Escape the five XML special characters
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Convert an HTTP date ("Wed, 21 Oct 2015 07:28:00 GMT") to a W3C date ("2015-10-21")
+fn http_date_to_w3c(date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let mut parts = date.split_whitespace().skip(1);
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month_name)? + 1;
+    let year: u32 = parts.next()?.parse().ok()?;
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// One page as a flat JSON object for --flat-json: scalar fields, list counts, and the main
+/// lists joined into newline-delimited strings
+fn flatten_result(data: &ScrapedData) -> serde_json::Value {
+    let join = |items: Vec<&str>| items.join("\n");
+    serde_json::json!({
+        "url": data.url,
+        "final_url": data.final_url,
+        "status_code": data.status_code,
+        "title": data.title,
+        "description": data.metadata.as_ref().and_then(|m| m.description.clone()),
+        "depth": data.depth,
+        "error": data.error,
+        "headings_count": data.headings.len(),
+        "paragraphs_count": data.paragraphs.len(),
+        "links_count": data.all_links().count(),
+        "images_count": data.images.len(),
+        "tables_count": data.tables.len(),
+        "code_blocks_count": data.code_blocks.len(),
+        "headings": join(data.headings.iter().map(String::as_str).collect()),
+        "links": join(data.all_links().map(|link| link.url.as_str()).collect()),
+        "images": join(data.images.iter().map(|image| image.src.as_str()).collect()),
+    })
+}
+
+/// Recursively drop null and empty-array members from JSON objects, for --omit-empty
+fn strip_empty(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !(v.is_null() || v.as_array().is_some_and(Vec::is_empty)));
+            map.values_mut().for_each(strip_empty);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_empty),
+        _ => {}
+    }
+}
+
+/// Format results as CSV
+fn format_csv(results: &[ScrapedData], title_max: Option<usize>) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    // Write header
+    writer.write_record([
+        "url",
+        "status_code",
+        "title",
+        "headings_count",
+        "paragraphs_count",
+        "links_count",
+        "images_count",
+        "tables_count",
+        "code_blocks_count",
+        "depth",
+        "content_bytes",
+    ])?;
+
+    // Write data rows
+    for data in results {
+        writer.write_record([
+            &data.url,
+            &data.status_code.to_string(),
+            &csv_title(data.title.as_deref().unwrap_or_default(), title_max),
+            &data.headings.len().to_string(),
+            &data.paragraphs.len().to_string(),
+            &data.all_links().count().to_string(),
+            &data.images.len().to_string(),
+            &data.tables.len().to_string(),
+            &data.code_blocks.len().to_string(),
+            &data.depth.map(|d| d.to_string()).unwrap_or_default(),
+            &data.content_bytes.map(|b| b.to_string()).unwrap_or_default(),
+        ])?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Title as a single CSV line: line breaks collapsed to spaces, cut to `max` characters
+fn csv_title(title: &str, max: Option<usize>) -> String {
+    let single_line = clean_text(title);
+    match max {
+        Some(max) => single_line.chars().take(max).collect(),
+        None => single_line,
+    }
+}
+
+/// ANSI styling for text output; every style is a no-op when disabled
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// Resolve a --color mode; "auto" colors only when writing to a terminal and NO_COLOR is unset
+    fn new(mode: &str, to_terminal: bool) -> Self {
+        let enabled = match mode {
+            "always" => true,
+            "never" => false,
+            _ => to_terminal && std::env::var_os("NO_COLOR").is_none(),
+        };
+        Palette { enabled }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    fn blue(&self, text: &str) -> String {
+        self.paint("34", text)
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+
+    /// Section header such as "Links (12):" with the count dimmed
+    fn section(&self, title: &str, count: usize) -> String {
+        format!("\n{} {}:\n", title, self.dim(&format!("({})", count)))
+    }
+}
+
+/// Write the flat summary columns (the same ones as the CSV output) to a Parquet file
+fn write_parquet(results: &[ScrapedData], path: &str) -> Result<()> {
+    use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let schema = Arc::new(parse_message_type(
+        "message scraped_page {
+            REQUIRED BYTE_ARRAY url (UTF8);
+            REQUIRED INT32 status_code;
+            OPTIONAL BYTE_ARRAY title (UTF8);
+            REQUIRED INT64 headings_count;
+            REQUIRED INT64 paragraphs_count;
+            REQUIRED INT64 links_count;
+            REQUIRED INT64 images_count;
+            REQUIRED INT64 tables_count;
+            REQUIRED INT64 code_blocks_count;
+            OPTIONAL INT64 depth;
+            OPTIONAL INT64 content_bytes;
+        }",
+    )?);
+
+    let file = fs::File::create(path)
+        .map_err(|e| anyhow::anyhow!("Failed to create Parquet file '{}': {}", path, e))?;
+    let mut writer =
+        SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build()))?;
+    let mut row_group = writer.next_row_group()?;
+
+    // Optional columns take values for present entries plus a definition level per row
+    let titles: Vec<ByteArray> = results
+        .iter()
+        .filter_map(|d| d.title.as_deref().map(ByteArray::from))
+        .collect();
+    let title_levels: Vec<i16> = results.iter().map(|d| d.title.is_some() as i16).collect();
+    let depths: Vec<i64> = results.iter().filter_map(|d| d.depth.map(|v| v as i64)).collect();
+    let depth_levels: Vec<i16> = results.iter().map(|d| d.depth.is_some() as i16).collect();
+    let sizes: Vec<i64> = results
+        .iter()
+        .filter_map(|d| d.content_bytes.map(|v| v as i64))
+        .collect();
+    let size_levels: Vec<i16> = results.iter().map(|d| d.content_bytes.is_some() as i16).collect();
+    let counts: [Vec<i64>; 6] = [
+        results.iter().map(|d| d.headings.len() as i64).collect(),
+        results.iter().map(|d| d.paragraphs.len() as i64).collect(),
+        results.iter().map(|d| d.all_links().count() as i64).collect(),
+        results.iter().map(|d| d.images.len() as i64).collect(),
+        results.iter().map(|d| d.tables.len() as i64).collect(),
+        results.iter().map(|d| d.code_blocks.len() as i64).collect(),
+    ];
+
+    let mut column_index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match column_index {
+            0 => {
+                let urls: Vec<ByteArray> =
+                    results.iter().map(|d| ByteArray::from(d.url.as_str())).collect();
+                column.typed::<ByteArrayType>().write_batch(&urls, None, None)?;
+            }
+            1 => {
+                let statuses: Vec<i32> = results.iter().map(|d| d.status_code as i32).collect();
+                column.typed::<Int32Type>().write_batch(&statuses, None, None)?;
+            }
+            2 => {
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&titles, Some(&title_levels), None)?;
+            }
+            3..=8 => {
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&counts[column_index - 3], None, None)?;
+            }
+            9 => {
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&depths, Some(&depth_levels), None)?;
+            }
+            _ => {
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&sizes, Some(&size_levels), None)?;
+            }
+        }
+        column.close()?;
+        column_index += 1;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Truncate text to a maximum length with ellipsis
+fn truncate_text(text: &str, max_len: usize) -> String {
+    if text.len() > max_len {
+        format!("{}...", &text[..max_len])
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` cut to at most `max_chars` characters, the last of which is "…" when anything was cut
+fn truncate_with_ellipsis(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let keep = max_chars.saturating_sub(1);
+    let end = text.char_indices().nth(keep).map_or(text.len(), |(i, _)| i);
+    format!("{}…", &text[..end])
+}
+
+/// Longest prefix of `text` that is at most `max_bytes` long and ends on a char boundary
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Format a list with a preview limit
+fn format_text_list<F>(
+    output: &mut String,
+    title: &str,
+    items: &[String],
+    preview_limit: usize,
+    palette: Palette,
+    format_fn: F,
+) where
+    F: Fn(&str) -> String,
+{
+    if items.is_empty() {
+        return;
+    }
+
+    output.push_str(&palette.section(title, items.len()));
+    for item in items.iter().take(preview_limit) {
+        output.push_str(&format_fn(item));
+    }
+    if items.len() > preview_limit {
+        output.push_str(&format!(
+            "  ... and {} more\n",
+            items.len() - preview_limit
+        ));
+    }
+}
+
+/// Format metadata section for text output
+fn format_text_metadata(metadata: &Metadata) -> String {
+    let mut output = String::from("\nMetadata:\n");
+
+    if let Some(desc) = &metadata.description {
+        output.push_str(&format!("  Description: {}\n", desc));
+    }
+    if let Some(keywords) = &metadata.keywords {
+        output.push_str(&format!("  Keywords: {}\n", keywords));
+    }
+    if let Some(author) = &metadata.author {
+        output.push_str(&format!("  Author: {}\n", author));
+    }
+    if let Some(og_title) = &metadata.og_title {
+        output.push_str(&format!("  OG Title: {}\n", og_title));
+    }
+    if let Some(og_image) = &metadata.og_image {
+        output.push_str(&format!("  OG Image: {}\n", og_image));
+    }
+    if let Some(geo) = &metadata.geo {
+        if let (Some(lat), Some(lon)) = (geo.lat, geo.lon) {
+            output.push_str(&format!("  Geo: {}, {}\n", lat, lon));
+        }
+        if let Some(address) = &geo.address {
+            output.push_str(&format!("  Address: {}\n", address));
+        }
+    }
+
+    output
+}
+
+/// Format custom selectors section for text output
+fn format_text_custom_selectors(custom_selectors: &[CustomSelectorResult]) -> String {
+    let mut output = String::from("\nCustom Selectors:\n");
+
+    for result in custom_selectors {
+        output.push_str(&format!(
+            "  '{}' ({} matches):\n",
+            result.selector,
+            result.count.unwrap_or(result.matches.len())
+        ));
+        for (i, match_text) in result.matches.iter().take(3).enumerate() {
+            output.push_str(&format!("    {}. {}\n", i + 1, match_text));
+            if let Some(path) = result.paths.get(i) {
+                output.push_str(&format!("       @ {}\n", path));
+            }
+        }
+        if result.matches.len() > 3 {
+            output.push_str(&format!(
+                "    ... and {} more\n",
+                result.matches.len() - 3
+            ));
+        }
+    }
+
+    output
+}
+
+/// Format results as plain text
+fn format_text(results: &[ScrapedData], palette: Palette) -> String {
+    let mut output = String::new();
+
+    for (i, data) in results.iter().enumerate() {
+        if i > 0 {
+            output.push_str("\n\n");
+            output.push_str(&"=".repeat(80));
+            output.push_str("\n\n");
+        }
+
+        // Basic info
+        output.push_str(&format!("URL: {}\n", palette.blue(&data.url)));
+        output.push_str(&format!("Status: {}\n", data.status_code));
+
+        if let Some(depth) = data.depth {
+            output.push_str(&format!("Depth: {}\n", depth));
+        }
+
+        if let Some(title) = &data.title {
+            output.push_str(&format!("Title: {}\n", palette.bold(title)));
+        }
+
+        if let Some(error) = &data.error {
+            output.push_str(&format!("Error: {}\n", error));
+        }
+
+        // Headings
+        format_text_list(
+            &mut output,
+            "Headings",
+            &data.headings,
+            data.headings.len(), // Show all headings
+            palette,
+            |heading| format!("  - {}\n", heading),
+        );
+
+        // Paragraphs with truncation
+        if !data.paragraphs.is_empty() {
+            output.push_str(&palette.section("Paragraphs", data.paragraphs.len()));
+            for (i, para) in data.paragraphs.iter().take(5).enumerate() {
+                output.push_str(&format!("  {}. {}\n", i + 1, truncate_text(para, 100)));
+            }
+            if data.paragraphs.len() > 5 {
+                output.push_str(&format!("  ... and {} more\n", data.paragraphs.len() - 5));
+            }
+        }
+
+        // Links
+        for (title, links) in [
+            ("Links", &data.links),
+            ("Internal Links", &data.internal_links),
+            ("External Links", &data.external_links),
+        ] {
+            if links.is_empty() {
+                continue;
+            }
+            output.push_str(&palette.section(title, links.len()));
+            for link in links.iter().take(10) {
+                let download = match link.download.as_deref() {
+                    Some("") => " [download]".to_string(),
+                    Some(filename) => format!(" [download: {}]", filename),
+                    None => String::new(),
+                };
+                output.push_str(&format!(
+                    "  - {} ({}){}\n",
+                    link.text,
+                    palette.blue(&link.url),
+                    download
+                ));
+            }
+            if links.len() > 10 {
+                output.push_str(&format!("  ... and {} more\n", links.len() - 10));
+            }
+        }
+
+        // Images
+        if !data.images.is_empty() {
+            output.push_str(&palette.section("Images", data.images.len()));
+            for img in data.images.iter().take(5) {
+                output.push_str(&format!(
+                    "  - {} ({}){}\n",
+                    if img.alt.is_empty() {
+                        "No alt text"
+                    } else {
+                        &img.alt
+                    },
+                    palette.blue(&img.src),
+                    if img.background { " [background]" } else { "" }
+                ));
+            }
+            if data.images.len() > 5 {
+                output.push_str(&format!("  ... and {} more\n", data.images.len() - 5));
+            }
+        }
+
+        // Tables
+        if !data.tables.is_empty() {
+            output.push_str(&palette.section("Tables", data.tables.len()));
+            for (i, table) in data.tables.iter().take(3).enumerate() {
+                output.push_str(&format!("  Table {}:\n", i + 1));
+                if !table.headers.is_empty() {
+                    output.push_str(&format!("    Headers: {}\n", table.headers.join(", ")));
+                }
+                output.push_str(&format!("    Rows: {}\n", table.rows.len()));
+            }
+            if data.tables.len() > 3 {
+                output.push_str(&format!("  ... and {} more\n", data.tables.len() - 3));
+            }
+        }
+
+        // Code Blocks
+        if !data.code_blocks.is_empty() {
+            output.push_str(&palette.section("Code Blocks", data.code_blocks.len()));
+            for (i, code) in data.code_blocks.iter().take(3).enumerate() {
+                let lang = code
+                    .language
+                    .as_ref()
+                    .map(|l| format!(" ({})", l))
+                    .unwrap_or_default();
+                output.push_str(&format!(
+                    "  {}. {}{}\n",
+                    i + 1,
+                    truncate_text(&code.content, 60),
+                    lang
+                ));
+            }
+            if data.code_blocks.len() > 3 {
+                output.push_str(&format!(
+                    "  ... and {} more\n",
+                    data.code_blocks.len() - 3
+                ));
+            }
+        }
+
+        // Metadata
+        if let Some(metadata) = &data.metadata {
+            output.push_str(&format_text_metadata(metadata));
+        }
+
+        // Custom selectors
+        if !data.custom_selectors.is_empty() {
+            output.push_str(&format_text_custom_selectors(&data.custom_selectors));
+        }
+
+        // Audit warnings
+        if !data.audit.is_empty() {
+            output.push_str(&palette.section("Audit", data.audit.len()));
+            for warning in &data.audit {
+                output.push_str(&format!("  ⚠ {}\n", warning));
+            }
+        }
+    }
+
+    output
+}
+
+/// Find the crawl parent of each result: the earlier page whose next-page link it is
+/// (pagination keeps its depth), else the first page one level shallower that links to it,
+/// else an earlier page at the same depth that links to it
+/// Returns one entry per result, `None` for roots (start page, non-crawl results, or orphans)
+fn crawl_parents(results: &[ScrapedData]) -> Vec<Option<usize>> {
+    let link_sets: Vec<HashSet<String>> = results
+        .iter()
+        .map(|data| data.all_links().map(|link| dedup_key(&link.url)).collect())
+        .collect();
+
+    results
+        .iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let depth = data.depth?;
+            let key = dedup_key(&data.url);
+            // Same-depth parents must come earlier, so pages linking each other can't form a cycle
+            let earlier_peers = || {
+                results[..index]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, candidate)| candidate.depth == Some(depth))
+            };
+            earlier_peers()
+                .find(|(_, candidate)| {
+                    candidate.next_page.as_deref().map(dedup_key).as_ref() == Some(&key)
+                })
+                .map(|(i, _)| i)
+                .or_else(|| {
+                    let shallower = depth.checked_sub(1)?;
+                    results.iter().enumerate().position(|(i, candidate)| {
+                        candidate.depth == Some(shallower) && link_sets[i].contains(&key)
+                    })
+                })
+                .or_else(|| earlier_peers().find(|(i, _)| link_sets[*i].contains(&key)).map(|(i, _)| i))
+        })
+        .collect()
+}
+
+/// Append one page and its crawl children to the tree output
+fn push_tree_node(
+    output: &mut String,
+    results: &[ScrapedData],
+    parents: &[Option<usize>],
+    index: usize,
+    level: usize,
+) {
+    let data = &results[index];
+    let title = data.title.as_deref().filter(|t| !t.is_empty()).unwrap_or("(untitled)");
+    output.push_str(&format!("{}- {} ({})\n", "  ".repeat(level), title, data.url));
+
+    for (child, parent) in parents.iter().enumerate() {
+        if *parent == Some(index) {
+            push_tree_node(output, results, parents, child, level + 1);
+        }
+    }
+}
+
+/// Format results as an indented tree following the crawl's link graph
+fn format_tree(results: &[ScrapedData]) -> String {
+    let parents = crawl_parents(results);
+    let mut output = String::new();
+
+    for (index, parent) in parents.iter().enumerate() {
+        if parent.is_none() {
+            push_tree_node(&mut output, results, &parents, index, 0);
+        }
+    }
+
+    output
+}
+
+// ========== Tests ==========
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Helper function to create a base URL for testing
+    fn test_base_url() -> Url {
+        Url::parse("https://example.com/path/page.html").unwrap()
+    }
+
+    fn test_base_url_simple() -> Url {
+        Url::parse("https://example.com").unwrap()
+    }
+
+    // Helper to build CLI args the same way main() does
+    fn test_args(extra: &[&str]) -> Args {
+        let mut argv = vec!["simple-web-scraper", "--delay", "0", "--quiet"];
+        argv.extend_from_slice(extra);
+        Args::parse_from(argv)
+    }
+
+    // ========== Mock HTTP Server ==========
+
+    /// Request as seen by the mock server
+    struct MockRequest {
+        method: String,
+        path: String,
+        /// Header names are lowercased
+        headers: std::collections::HashMap<String, String>,
+    }
+
+    /// Canned response returned by the mock server
+    struct MockResponse {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl MockResponse {
+        fn html(body: &str) -> Self {
+            MockResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "text/html; charset=utf-8".to_string())],
+                body: body.as_bytes().to_vec(),
+            }
+        }
+
+        fn status(status: u16) -> Self {
+            MockResponse {
+                status,
+                headers: Vec::new(),
+                body: Vec::new(),
+            }
+        }
+
+        fn redirect(location: &str) -> Self {
+            MockResponse::status(302).with_header("Location", location)
+        }
+
+        fn with_header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+    }
+
+    /// Spawn a local HTTP/1.1 server answering every request with `handler`
+    /// Returns the base URL (e.g. "http://127.0.0.1:41234")
+    async fn spawn_mock_server<F>(handler: F) -> String
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler = std::sync::Arc::new(handler);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    // Read until the end of the request headers
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                        }
+                    }
+
+                    let head = String::from_utf8_lossy(&buf);
+                    let mut lines = head.lines();
+                    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+                    let request = MockRequest {
+                        method: request_line.next().unwrap_or_default().to_string(),
+                        path: request_line.next().unwrap_or_default().to_string(),
+                        headers: lines
+                            .filter_map(|line| line.split_once(':'))
+                            .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+                            .collect(),
+                    };
+
+                    let response = handler(&request);
+                    let mut out = format!("HTTP/1.1 {} Mock\r\n", response.status);
+                    for (name, value) in &response.headers {
+                        out.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    out.push_str(&format!(
+                        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.body.len()
+                    ));
+                    let _ = socket.write_all(out.as_bytes()).await;
+                    if request.method != "HEAD" {
+                        let _ = socket.write_all(&response.body).await;
+                    }
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    // ========== URL Normalization Tests ==========
+
+    #[test]
+    fn test_normalize_url_absolute_https() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "https://other.com/page");
+        assert_eq!(result, Some("https://other.com/page".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_absolute_http() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "http://other.com/page");
+        assert_eq!(result, Some("http://other.com/page".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_protocol_relative() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "//cdn.example.com/image.jpg");
+        assert_eq!(result, Some("https://cdn.example.com/image.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_relative_path() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "other-page.html");
+        assert_eq!(result, Some("https://example.com/path/other-page.html".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_absolute_path() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "/images/photo.jpg");
+        assert_eq!(result, Some("https://example.com/images/photo.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_parent_directory() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "../other/page.html");
+        assert_eq!(result, Some("https://example.com/other/page.html".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_with_fragment() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "/page#section");
+        assert_eq!(result, Some("https://example.com/page#section".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_url_with_query_params() {
+        let base = test_base_url();
+        let result = normalize_url(&base, "/search?q=test&lang=en");
+        assert_eq!(result, Some("https://example.com/search?q=test&lang=en".to_string()));
+    }
+
+    // ========== Domain Checking Tests ==========
+
+    #[test]
+    fn test_is_same_domain_exact_match() {
+        assert!(is_same_domain("https://example.com/page", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_with_subdomain() {
+        assert!(!is_same_domain("https://blog.example.com/page", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_different_domain() {
+        assert!(!is_same_domain("https://other.com/page", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_with_path() {
+        assert!(is_same_domain("https://example.com/path/to/page", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_invalid_url() {
+        assert!(!is_same_domain("not-a-url", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_http_vs_https() {
+        assert!(is_same_domain("http://example.com/page", "example.com"));
+    }
+
+    #[test]
+    fn test_is_same_domain_ip_and_port() {
+        assert!(is_same_domain("http://127.0.0.1:8080/a", "127.0.0.1:8080"));
+        assert!(!is_same_domain("http://127.0.0.1:9090/a", "127.0.0.1:8080"));
+        assert!(!is_same_domain("not-a-url", ""));
+    }
+
+    // ========== Title Extraction Tests ==========
+
+    #[test]
+    fn test_extract_title_present() {
+        let html = r#"<html><head><title>Test Page Title</title></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let title = extract_title(&document);
+        assert_eq!(title, Some("Test Page Title".to_string()));
+    }
+
+    #[test]
+    fn test_extract_title_with_whitespace() {
+        let html = r#"<html><head><title>  Trimmed Title  </title></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let title = extract_title(&document);
+        assert_eq!(title, Some("Trimmed Title".to_string()));
+    }
+
+    #[test]
+    fn test_extract_title_missing() {
+        let html = r#"<html><head></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let title = extract_title(&document);
+        assert_eq!(title, None);
+    }
+
+    #[test]
+    fn test_extract_title_empty() {
+        let html = r#"<html><head><title></title></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let title = extract_title(&document);
+        assert_eq!(title, Some("".to_string()));
+    }
+
+    // ========== Headings Extraction Tests ==========
+
+    #[test]
+    fn test_extract_headings_all_levels() {
+        let html = r#"
+            <html><body>
+                <h1>Heading 1</h1>
+                <h2>Heading 2</h2>
+                <h3>Heading 3</h3>
+                <h4>Heading 4</h4>
+                <h5>Heading 5</h5>
+                <h6>Heading 6</h6>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let headings = extract_headings(&document);
+        assert_eq!(headings.len(), 6);
+        assert_eq!(headings[0], "Heading 1");
+        assert_eq!(headings[5], "Heading 6");
+    }
+
+    #[test]
+    fn test_extract_headings_empty() {
+        let html = r#"<html><body><p>No headings here</p></body></html>"#;
+        let document = Html::parse_document(html);
+        let headings = extract_headings(&document);
+        assert_eq!(headings.len(), 0);
+    }
+
+    #[test]
+    fn test_extract_headings_filters_empty() {
+        let html = r#"
+            <html><body>
+                <h1>Valid Heading</h1>
+                <h2>   </h2>
+                <h3></h3>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let headings = extract_headings(&document);
+        assert_eq!(headings.len(), 1);
+        assert_eq!(headings[0], "Valid Heading");
+    }
+
+    #[test]
+    fn test_extract_headings_trims_whitespace() {
+        let html = r#"<html><body><h1>  Trimmed  </h1></body></html>"#;
+        let document = Html::parse_document(html);
+        let headings = extract_headings(&document);
+        assert_eq!(headings[0], "Trimmed");
+    }
+
+    // ========== Paragraphs Extraction Tests ==========
+
+    #[test]
+    fn test_extract_paragraphs_multiple() {
+        let html = r#"
+            <html><body>
+                <p>First paragraph</p>
+                <p>Second paragraph</p>
+                <p>Third paragraph</p>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let paragraphs = extract_paragraphs(&document);
+        assert_eq!(paragraphs.len(), 3);
+        assert_eq!(paragraphs[0], "First paragraph");
+    }
+
+    #[test]
+    fn test_extract_paragraphs_filters_empty() {
+        let html = r#"
+            <html><body>
+                <p>Valid paragraph</p>
+                <p></p>
+                <p>   </p>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let paragraphs = extract_paragraphs(&document);
+        assert_eq!(paragraphs.len(), 1);
+        assert_eq!(paragraphs[0], "Valid paragraph");
+    }
+
+    #[test]
+    fn test_extract_paragraphs_none() {
+        let html = r#"<html><body><div>Not a paragraph</div></body></html>"#;
+        let document = Html::parse_document(html);
+        let paragraphs = extract_paragraphs(&document);
+        assert_eq!(paragraphs.len(), 0);
+    }
+
+    // ========== Links Extraction Tests ==========
+
+    #[test]
+    fn test_extract_links_absolute() {
+        let html = r#"
+            <html><body>
+                <a href="https://example.com/page">Link Text</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].text, "Link Text");
+        assert_eq!(links[0].url, "https://example.com/page");
+    }
+
+    #[test]
+    fn test_extract_links_relative() {
+        let html = r#"
+            <html><body>
+                <a href="/about">About</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].text, "About");
+        assert_eq!(links[0].url, "https://example.com/about");
+    }
+
+    #[test]
+    fn test_extract_links_empty_text_uses_href() {
+        let html = r#"
+            <html><body>
+                <a href="/contact"></a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].text, "/contact");
+    }
+
+    #[test]
+    fn test_extract_links_empty_text_uses_aria_label() {
+        let html = r#"
+            <html><body>
+                <a href="/search" aria-label="Search"><svg><path d="M0 0"></path></svg></a>
+                <a href="/cart" title="Cart"><i class="icon-cart"></i></a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links[0].text, "Search");
+        assert_eq!(links[1].text, "Cart");
+    }
+
+    #[test]
+    fn test_extract_links_no_href() {
+        let html = r#"
+            <html><body>
+                <a>No href attribute</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 0);
+    }
+
+    #[test]
+    fn test_extract_links_protocol_relative() {
+        let html = r#"
+            <html><body>
+                <a href="//cdn.example.com/page">CDN Link</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://cdn.example.com/page");
+    }
+
+    #[test]
+    fn test_extract_links_download_filename() {
+        let html = r#"
+            <html><body>
+                <a href="file.zip" download="report.zip">Get report</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://example.com/file.zip");
+        assert_eq!(links[0].download, Some("report.zip".to_string()));
+    }
+
+    #[test]
+    fn test_extract_links_download_boolean_and_absent() {
+        let html = r#"
+            <html><body>
+                <a href="/data.csv" download>Data</a>
+                <a href="/page">Page</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let links = extract_links(&document, &base_url);
+
+        assert_eq!(links[0].download, Some("".to_string()));
+        assert_eq!(links[1].download, None);
+    }
+
+    #[test]
+    fn test_extract_canonical_url_relative() {
+        let html = r#"<html><head><link rel="canonical" href="/x"></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let base = Url::parse("https://example.com/amp/x").unwrap();
+
+        assert_eq!(
+            extract_canonical_url(&document, &base).map(|u| u.to_string()),
+            Some("https://example.com/x".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_against_canonical() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html(
+                r#"<html><head><link rel="canonical" href="/x/"><link rel="next" href="page/2"></head>
+                <body><a href="related">Related</a><img src="hero.jpg"></body></html>"#,
+            )
+        })
+        .await;
+        let amp_url = format!("{}/amp/x/", base);
+
+        let args = test_args(&["--resolve-against-canonical", &amp_url]);
+        let data = scrape_website(&amp_url, &args, None, None).await.unwrap();
+        assert_eq!(data.links[0].url, format!("{}/x/related", base));
+        assert_eq!(data.images[0].src, format!("{}/x/hero.jpg", base));
+        assert_eq!(data.next_page, Some(format!("{}/x/page/2", base)));
+
+        let args = test_args(&[&amp_url]);
+        let data = scrape_website(&amp_url, &args, None, None).await.unwrap();
+        assert_eq!(data.links[0].url, format!("{}/amp/x/related", base));
+        assert_eq!(data.next_page, Some(format!("{}/amp/x/page/2", base)));
+    }
+
+    // ========== Images Extraction Tests ==========
+
+    #[test]
+    fn test_extract_images_absolute() {
+        let html = r#"
+            <html><body>
+                <img src="https://example.com/image.jpg" alt="Test Image">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].alt, "Test Image");
+        assert_eq!(images[0].src, "https://example.com/image.jpg");
+    }
+
+    #[test]
+    fn test_extract_images_relative() {
+        let html = r#"
+            <html><body>
+                <img src="/images/photo.jpg" alt="Photo">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].src, "https://example.com/images/photo.jpg");
+    }
+
+    #[test]
+    fn test_extract_images_no_alt() {
+        let html = r#"
+            <html><body>
+                <img src="https://example.com/image.jpg">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].alt, "");
+    }
+
+    #[test]
+    fn test_extract_images_empty_alt_uses_aria_label_or_title() {
+        let html = r#"
+            <html><body>
+                <img src="/a.png" alt="" aria-label="Company logo">
+                <img src="/b.png" title="Team photo">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images[0].alt, "Company logo");
+        assert_eq!(images[1].alt, "Team photo");
+    }
+
+    #[test]
+    fn test_extract_images_protocol_relative() {
+        let html = r#"
+            <html><body>
+                <img src="//cdn.example.com/image.jpg" alt="CDN Image">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].src, "https://cdn.example.com/image.jpg");
+    }
+
+    #[test]
+    fn test_extract_images_no_src() {
+        let html = r#"
+            <html><body>
+                <img alt="No source">
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base_url = test_base_url_simple();
+        let images = extract_images(&document, &base_url);
+
+        assert_eq!(images.len(), 0);
+    }
+
+    // ========== Metadata Extraction Tests ==========
+
+    #[test]
+    fn test_extract_metadata_complete() {
+        let html = r#"
+            <html><head>
+                <meta name="description" content="Test description">
+                <meta name="keywords" content="test, keywords">
+                <meta name="author" content="Test Author">
+                <meta property="og:title" content="OG Title">
+                <meta property="og:description" content="OG Description">
+                <meta property="og:image" content="https://example.com/og.jpg">
+                <meta property="og:url" content="https://example.com">
+                <link rel="canonical" href="https://example.com/canonical">
+                <link rel="icon" href="/favicon.ico">
+            </head><body></body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let metadata = extract_metadata(&document);
+
+        assert_eq!(metadata.description, Some("Test description".to_string()));
+        assert_eq!(metadata.keywords, Some("test, keywords".to_string()));
+        assert_eq!(metadata.author, Some("Test Author".to_string()));
+        assert_eq!(metadata.og_title, Some("OG Title".to_string()));
+        assert_eq!(metadata.og_description, Some("OG Description".to_string()));
+        assert_eq!(metadata.og_image, Some("https://example.com/og.jpg".to_string()));
+        assert_eq!(metadata.og_url, Some("https://example.com".to_string()));
+        assert_eq!(metadata.canonical_url, Some("https://example.com/canonical".to_string()));
+        assert_eq!(metadata.favicon, Some("/favicon.ico".to_string()));
+    }
+
+    #[test]
+    fn test_extract_metadata_empty() {
+        let html = r#"<html><head></head><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let metadata = extract_metadata(&document);
+
+        assert_eq!(metadata.description, None);
+        assert_eq!(metadata.keywords, None);
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.og_title, None);
+    }
+
+    #[test]
+    fn test_extract_metadata_partial() {
+        let html = r#"
+            <html><head>
+                <meta name="description" content="Just description">
+                <meta property="og:title" content="Just OG title">
+            </head><body></body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let metadata = extract_metadata(&document);
+
+        assert_eq!(metadata.description, Some("Just description".to_string()));
+        assert_eq!(metadata.og_title, Some("Just OG title".to_string()));
+        assert_eq!(metadata.keywords, None);
+        assert_eq!(metadata.author, None);
+    }
+
+    #[test]
+    fn test_extract_all_meta_keeps_every_tag() {
+        let html = r##"<html><head>
+            <meta charset="utf-8">
+            <meta name="theme-color" content="#00bb55">
+            <meta property="article:tag" content="rust">
+            <meta http-equiv="content-language" content="sv">
+        </head><body></body></html>"##;
+        let all_meta = extract_all_meta(&Html::parse_document(html));
+
+        let pairs: Vec<(&str, &str)> =
+            all_meta.iter().map(|m| (m.key.as_str(), m.value.as_str())).collect();
+        assert_eq!(
+            pairs,
+            vec![("theme-color", "#00bb55"), ("article:tag", "rust"), ("content-language", "sv")]
+        );
+    }
+
+    #[test]
+    fn test_extract_resource_hints() {
+        let html = r#"<html><head>
+            <link rel="preload" href="/fonts/inter.woff2" as="font">
+            <link rel="dns-prefetch" href="//cdn.example.net">
+            <link rel="stylesheet" href="/site.css">
+        </head><body></body></html>"#;
+        let base = Url::parse("https://example.com/blog/").unwrap();
+        let hints = extract_resource_hints(&Html::parse_document(html), &base);
+
+        assert_eq!(
+            hints,
+            vec![
+                ResourceHint {
+                    rel: "preload".to_string(),
+                    href: "https://example.com/fonts/inter.woff2".to_string(),
+                },
+                ResourceHint {
+                    rel: "dns-prefetch".to_string(),
+                    href: "https://cdn.example.net".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_geo_from_meta_tags() {
+        let html = r#"<html><head>
+            <meta name="geo.position" content="59.3293;18.0686">
+            <meta name="geo.placename" content="Stockholm">
+        </head></html>"#;
+        let document = Html::parse_document(html);
+        let geo = extract_metadata(&document).geo.unwrap();
+
+        assert_eq!(geo.lat, Some(59.3293));
+        assert_eq!(geo.lon, Some(18.0686));
+        assert_eq!(geo.address, Some("Stockholm".to_string()));
+    }
+
+    #[test]
+    fn test_extract_geo_from_json_ld_postal_address() {
+        let html = r#"<html><head><script type="application/ld+json">
+            {"@context": "https://schema.org", "@type": "Store", "name": "Shop",
+             "address": {"@type": "PostalAddress", "streetAddress": "Drottninggatan 1",
+                         "addressLocality": "Stockholm", "postalCode": "111 51",
+                         "addressCountry": {"@type": "Country", "name": "SE"}},
+             "geo": {"@type": "GeoCoordinates", "latitude": "59.33", "longitude": 18.06}}
+        </script></head></html>"#;
+        let document = Html::parse_document(html);
+        let geo = extract_metadata(&document).geo.unwrap();
+
+        assert_eq!(
+            geo.address,
+            Some("Drottninggatan 1, Stockholm, 111 51, SE".to_string())
+        );
+        assert_eq!(geo.lat, Some(59.33));
+        assert_eq!(geo.lon, Some(18.06));
+    }
+
+    #[test]
+    fn test_extract_geo_from_microdata_and_absent() {
+        let html = r#"<div itemscope itemtype="https://schema.org/PostalAddress">
+            <span itemprop="streetAddress">1 Main St</span>
+            <span itemprop="addressLocality">Springfield</span>
+        </div>"#;
+        let geo = extract_geo(&Html::parse_document(html)).unwrap();
+        assert_eq!(geo.address, Some("1 Main St, Springfield".to_string()));
+        assert_eq!(geo.lat, None);
+
+        let document = Html::parse_document("<html><head><title>x</title></head></html>");
+        assert_eq!(extract_metadata(&document).geo, None);
+    }
+
+    #[test]
+    fn test_extract_metadata_shortcut_icon() {
+        let html = r#"
+            <html><head>
+                <link rel="shortcut icon" href="/favicon.png">
+            </head><body></body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let metadata = extract_metadata(&document);
+
+        assert_eq!(metadata.favicon, Some("/favicon.png".to_string()));
+    }
+
+    #[test]
+    fn test_extract_pagination_link_and_anchor() {
+        let html = r#"
+            <html><head><link rel="prev" href="/list?page=1"></head><body>
+                <a rel="next nofollow" href="/list?page=3">Next</a>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let base = Url::parse("https://example.com/list?page=2").unwrap();
+
+        let (next, prev) = extract_pagination(&document, &base);
+        assert_eq!(next, Some("https://example.com/list?page=3".to_string()));
+        assert_eq!(prev, Some("https://example.com/list?page=1".to_string()));
+    }
+
+    // ========== Custom Selectors Tests ==========
+
+    #[test]
+    fn test_process_custom_selectors_valid() {
+        let html = r#"
+            <html><body>
+                <div class="item">Item 1</div>
+                <div class="item">Item 2</div>
+                <div class="item">Item 3</div>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".item".to_string()];
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].selector, ".item");
+        assert_eq!(results[0].matches.len(), 3);
+        assert_eq!(results[0].matches[0], "Item 1");
+    }
+
+    #[test]
+    fn test_process_custom_selectors_multiple() {
+        let html = r#"
+            <html><body>
+                <h1>Heading</h1>
+                <p class="intro">Intro paragraph</p>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let selectors = vec!["h1".to_string(), ".intro".to_string()];
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].matches[0], "Heading");
+        assert_eq!(results[1].matches[0], "Intro paragraph");
+    }
+
+    #[test]
+    fn test_process_custom_selectors_no_matches() {
+        let html = r#"<html><body><p>Content</p></body></html>"#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".nonexistent".to_string()];
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches.len(), 0);
+    }
+
+    #[test]
+    fn test_process_custom_selectors_invalid() {
+        let html = r#"<html><body></body></html>"#;
+        let document = Html::parse_document(html);
+        let selectors = vec!["invalid[[[selector".to_string()];
+        let result = process_custom_selectors(&document, &selectors, SelectorOptions::default());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_process_custom_selectors_filters_empty() {
+        let html = r#"
+            <html><body>
+                <div class="item">Valid</div>
+                <div class="item">   </div>
+                <div class="item"></div>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".item".to_string()];
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+
+        assert_eq!(results[0].matches.len(), 1);
+        assert_eq!(results[0].matches[0], "Valid");
+    }
+
+    #[test]
+    fn test_process_custom_selectors_count_only() {
+        let html = r#"
+            <html><body>
+                <div class="product">Widget</div>
+                <div class="product">Gadget</div>
+                <div class="product">Gizmo</div>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".product".to_string()];
+        let options = SelectorOptions { count_only: true, ..Default::default() };
+        let results = process_custom_selectors(&document, &selectors, options).unwrap();
+
+        assert_eq!(results[0].count, Some(3));
+        assert!(results[0].matches.is_empty());
+
+        let json = serde_json::to_string(&results).unwrap();
+        assert!(!json.contains("Widget"));
+    }
+
+    #[test]
+    fn test_process_custom_selectors_dedup() {
+        let html = r#"<span class="tag">A</span><span class="tag">B</span><span class="tag">A</span>"#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".tag".to_string()];
+
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+        assert_eq!(results[0].matches, vec!["A", "B", "A"]);
+
+        let options = SelectorOptions { dedup: true, ..Default::default() };
+        let results = process_custom_selectors(&document, &selectors, options).unwrap();
+        assert_eq!(results[0].matches, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_process_custom_selectors_with_dom_path() {
+        let html = r#"<html><body>
+            <div><p class="note">First</p></div>
+            <div><span>x</span><p class="note">Second</p></div>
+        </body></html>"#;
+        let document = Html::parse_document(html);
+        let selectors = vec![".note".to_string()];
+
+        let options = SelectorOptions { with_dom_path: true, ..Default::default() };
+        let results = process_custom_selectors(&document, &selectors, options).unwrap();
+        assert_eq!(
+            results[0].paths,
+            vec![
+                "html > body > div:nth-child(1) > p",
+                "html > body > div:nth-child(2) > p:nth-child(2)"
+            ]
+        );
+
+        // Each path selects exactly its own match
+        for (path, text) in results[0].paths.iter().zip(&results[0].matches) {
+            let selector = Selector::parse(path).unwrap();
+            let found: Vec<_> = document.select(&selector).collect();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].text().collect::<String>(), *text);
+        }
+
+        let results = process_custom_selectors(&document, &selectors, SelectorOptions::default()).unwrap();
+        assert!(results[0].paths.is_empty());
+    }
+
+    // ========== Crawl Queue Tests ==========
+
+    #[test]
+    fn test_should_add_to_crawl_queue_same_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "https://example.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, Some("https://example.com/page".to_string()));
+    }
+
+    #[test]
+    fn test_should_add_to_crawl_queue_scopes_ip_and_port() {
+        let base_url = Url::parse("http://127.0.0.1:8080/").unwrap();
+        let base_host = url_host_key(base_url.as_str());
+        let none = HashSet::new();
+        let check = |link: &str| {
+            should_add_to_crawl_queue(link, &base_url, &base_host, &none, &none, &none, false)
+        };
+
+        assert_eq!(check("/about"), Some("http://127.0.0.1:8080/about".to_string()));
+        assert_eq!(check("http://127.0.0.1:9090/about"), None);
+        assert_eq!(check("http://127.0.0.1/about"), None);
+    }
+
+    #[test]
+    fn test_should_add_to_crawl_queue_different_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "https://other.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_should_add_to_crawl_queue_already_visited() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let mut visited = HashSet::new();
+        visited.insert("https://example.com/page".to_string());
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "https://example.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_should_add_to_crawl_queue_relative_url() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "/about",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, Some("https://example.com/about".to_string()));
+    }
+
+    #[test]
+    fn test_should_add_to_crawl_queue_relative_different_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        // This should resolve to example.com domain
+        let result = should_add_to_crawl_queue(
+            "../page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert!(result.is_some());
+        assert!(result.unwrap().starts_with("https://example.com"));
+    }
+
+    // ========== Domain Filtering Tests ==========
+
+    #[test]
+    fn test_domain_filtering_allow_list_includes_allowed_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let mut allow_domains = HashSet::new();
+        allow_domains.insert("docs.example.com".to_string());
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "https://docs.example.com/api",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, Some("https://docs.example.com/api".to_string()));
+    }
+
+    #[test]
+    fn test_domain_filtering_allow_list_blocks_non_allowed_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let mut allow_domains = HashSet::new();
+        allow_domains.insert("docs.example.com".to_string());
+        let block_domains = HashSet::new();
+
+        // other.com is not in allow list, should be blocked
+        let result = should_add_to_crawl_queue(
+            "https://other.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_domain_filtering_allow_list_always_includes_base_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let mut allow_domains = HashSet::new();
+        allow_domains.insert("docs.example.com".to_string());
+        let block_domains = HashSet::new();
+
+        // Base domain should always be allowed even if not explicitly in allow list
+        let result = should_add_to_crawl_queue(
+            "https://example.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, Some("https://example.com/page".to_string()));
+    }
+
+    #[test]
+    fn test_domain_filtering_block_list_blocks_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let mut block_domains = HashSet::new();
+        block_domains.insert("ads.example.com".to_string());
+
+        let result = should_add_to_crawl_queue(
+            "https://ads.example.com/tracker",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_domain_filtering_block_list_allows_non_blocked_same_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let mut block_domains = HashSet::new();
+        block_domains.insert("ads.example.com".to_string());
+
+        // Base domain should still work
+        let result = should_add_to_crawl_queue(
+            "https://example.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, Some("https://example.com/page".to_string()));
+    }
+
+    #[test]
+    fn test_domain_filtering_cross_domain_allows_any_domain() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let block_domains = HashSet::new();
+
+        let result = should_add_to_crawl_queue(
+            "https://completely-different.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            true, // cross_domain enabled
+        );
+
+        assert_eq!(
+            result,
+            Some("https://completely-different.com/page".to_string())
+        );
+    }
+
+    #[test]
+    fn test_domain_filtering_cross_domain_respects_block_list() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let allow_domains = HashSet::new();
+        let mut block_domains = HashSet::new();
+        block_domains.insert("blocked.com".to_string());
+
+        // Even with cross-domain enabled, blocked domains should still be blocked
+        let result = should_add_to_crawl_queue(
+            "https://blocked.com/page",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            true, // cross_domain enabled
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_domain_filtering_mixed_allow_and_block() {
+        let base_url = Url::parse("https://example.com").unwrap();
+        let visited = HashSet::new();
+        let mut allow_domains = HashSet::new();
+        allow_domains.insert("docs.example.com".to_string());
+        allow_domains.insert("api.example.com".to_string());
+        let mut block_domains = HashSet::new();
+        block_domains.insert("api.example.com".to_string());
+
+        // Block list takes precedence over allow list
+        let result = should_add_to_crawl_queue(
+            "https://api.example.com/endpoint",
+            &base_url,
+            "example.com",
+            &visited,
+            &allow_domains,
+            &block_domains,
+            false,
+        );
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_parse_domain_list_comma_separated() {
+        let domains = parse_domain_list("example.com,docs.example.com,api.example.com");
+        assert_eq!(domains.len(), 3);
+        assert!(domains.contains("example.com"));
+        assert!(domains.contains("docs.example.com"));
+        assert!(domains.contains("api.example.com"));
+    }
+
+    #[test]
+    fn test_parse_domain_list_with_whitespace() {
+        let domains = parse_domain_list("  example.com  , docs.example.com , api.example.com  ");
+        assert_eq!(domains.len(), 3);
+        assert!(domains.contains("example.com"));
+        assert!(domains.contains("docs.example.com"));
+        assert!(domains.contains("api.example.com"));
+    }
+
+    #[test]
+    fn test_parse_domain_list_empty_entries() {
+        let domains = parse_domain_list("example.com,,docs.example.com,  ,api.example.com");
+        assert_eq!(domains.len(), 3);
+        assert!(domains.contains("example.com"));
+        assert!(domains.contains("docs.example.com"));
+        assert!(domains.contains("api.example.com"));
+    }
+
+    #[test]
+    fn test_parse_domain_list_case_insensitive() {
+        let domains = parse_domain_list("Example.COM,DOCS.example.com,api.EXAMPLE.com");
+        assert_eq!(domains.len(), 3);
+        // All should be lowercased
+        assert!(domains.contains("example.com"));
+        assert!(domains.contains("docs.example.com"));
+        assert!(domains.contains("api.example.com"));
+    }
+
+    // ========== URL Rewrite Tests ==========
+
+    #[test]
+    fn test_rewrite_rules_force_https() {
+        let rules = parse_rewrite_rules(&["^http://=>https://".to_string()]).unwrap();
+        assert_eq!(
+            apply_rewrite_rules("http://example.com/page", &rules),
+            "https://example.com/page"
+        );
+        assert_eq!(
+            apply_rewrite_rules("https://example.com/secure", &rules),
+            "https://example.com/secure"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_rules_applied_in_order_with_captures() {
+        let rules = parse_rewrite_rules(&[
+            "staging\\.example\\.com=>example.com".to_string(),
+            "/(en|de)/(.*)=>/$2".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            apply_rewrite_rules("https://staging.example.com/de/about", &rules),
+            "https://example.com/about"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_rules_invalid() {
+        assert!(parse_rewrite_rules(&["no-separator".to_string()]).is_err());
+        assert!(parse_rewrite_rules(&["([=>x".to_string()]).is_err());
+    }
+
+    // ========== Text Formatting Helper Tests ==========
+
+    #[test]
+    fn test_truncate_text_short() {
+        let text = "Short text";
+        let result = truncate_text(text, 100);
+        assert_eq!(result, "Short text");
+    }
+
+    #[test]
+    fn test_truncate_text_long() {
+        let text = "This is a very long piece of text that should be truncated at the specified length with ellipsis added";
+        let result = truncate_text(text, 20);
+        assert_eq!(result, "This is a very long ...");
+        assert_eq!(result.len(), 23); // 20 chars + "..."
+    }
+
+    #[test]
+    fn test_truncate_text_exact_length() {
+        let text = "12345678901234567890"; // exactly 20 chars
+        let result = truncate_text(text, 20);
+        assert_eq!(result, "12345678901234567890");
+    }
+
+    #[test]
+    fn test_format_text_metadata() {
+        let metadata = Metadata {
+            description: Some("Test description".to_string()),
+            keywords: Some("test, rust".to_string()),
+            author: Some("Author Name".to_string()),
+            og_title: Some("OG Title".to_string()),
+            og_description: None,
+            og_image: Some("https://example.com/image.jpg".to_string()),
+            ..Default::default()
+        };
+
+        let result = format_text_metadata(&metadata);
+        assert!(result.contains("Description: Test description"));
+        assert!(result.contains("Keywords: test, rust"));
+        assert!(result.contains("Author: Author Name"));
+        assert!(result.contains("OG Title: OG Title"));
+        assert!(result.contains("OG Image: https://example.com/image.jpg"));
+    }
+
+    #[test]
+    fn test_format_text_custom_selectors() {
+        let selectors = vec![
+            CustomSelectorResult {
+                selector: ".item".to_string(),
+                matches: vec!["Match 1".to_string(), "Match 2".to_string()],
+                count: None,
+                paths: vec![],
+            },
+        ];
+
+        let result = format_text_custom_selectors(&selectors);
+        assert!(result.contains("'.item' (2 matches)"));
+        assert!(result.contains("1. Match 1"));
+        assert!(result.contains("2. Match 2"));
+    }
+
+    #[test]
+    fn test_format_text_custom_selectors_truncated() {
+        let selectors = vec![
+            CustomSelectorResult {
+                selector: ".item".to_string(),
+                matches: vec![
+                    "Match 1".to_string(),
+                    "Match 2".to_string(),
+                    "Match 3".to_string(),
+                    "Match 4".to_string(),
+                ],
+                count: None,
+                paths: vec![],
+            },
+        ];
+
+        let result = format_text_custom_selectors(&selectors);
+        assert!(result.contains("... and 1 more"));
+    }
+
+    #[test]
+    fn test_format_text_color_never_has_no_escape_codes() {
+        let data = vec![crawled_page("https://example.com/", "Home", 0, &["https://example.com/a"])];
+        let result = format_text(&data, Palette::new("never", true));
+
+        assert!(!result.contains('\x1b'));
+        assert!(result.contains("Title: Home"));
+        assert!(result.contains("Links (1):"));
+    }
+
+    #[test]
+    fn test_format_text_color_always_has_escape_codes() {
+        let data = vec![crawled_page("https://example.com/", "Home", 0, &["https://example.com/a"])];
+        let result = format_text(&data, Palette::new("always", false));
+
+        assert!(result.contains("Title: \x1b[1mHome\x1b[0m"));
+        assert!(result.contains("\x1b[34mhttps://example.com/\x1b[0m"));
+        assert!(result.contains("Links \x1b[2m(1)\x1b[0m:"));
+    }
+
+    #[test]
+    fn test_palette_auto_only_on_terminal() {
+        assert!(!Palette::new("auto", false).enabled);
+        assert!(Palette::new("always", false).enabled);
+        assert!(!Palette::new("never", true).enabled);
+    }
+
+    // ========== Tables Extraction Tests ==========
+
+    #[test]
+    fn test_extract_tables_with_headers() {
+        let html = r#"
+            <html><body>
+                <table>
+                    <tr><th>Name</th><th>Age</th></tr>
+                    <tr><td>Alice</td><td>30</td></tr>
+                    <tr><td>Bob</td><td>25</td></tr>
+                </table>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].headers, vec!["Name", "Age"]);
+        assert_eq!(tables[0].rows.len(), 2);
+        assert_eq!(tables[0].rows[0], vec!["Alice", "30"]);
+        assert_eq!(tables[0].rows[1], vec!["Bob", "25"]);
+    }
+
+    #[test]
+    fn test_extract_tables_cleans_cell_whitespace() {
+        let html = r#"
+            <html><body>
+                <table>
+                    <tr><th>
+                        Product
+                        name
+                    </th></tr>
+                    <tr><td>
+                        Rust in
+                            Action
+                    </td></tr>
+                </table>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables[0].headers, vec!["Product name"]);
+        assert_eq!(tables[0].rows, vec![vec!["Rust in Action"]]);
+    }
+
+    #[test]
+    fn test_extract_tables_without_headers() {
+        let html = r#"
+            <html><body>
+                <table>
+                    <tr><td>Data 1</td><td>Data 2</td></tr>
+                    <tr><td>Data 3</td><td>Data 4</td></tr>
+                </table>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].headers.len(), 0);
+        assert_eq!(tables[0].rows.len(), 2);
+        assert_eq!(tables[0].rows[0], vec!["Data 1", "Data 2"]);
+    }
+
+    #[test]
+    fn test_extract_tables_multiple() {
+        let html = r#"
+            <html><body>
+                <table>
+                    <tr><th>Column 1</th></tr>
+                    <tr><td>Value 1</td></tr>
+                </table>
+                <table>
+                    <tr><td>Table 2</td></tr>
+                </table>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].headers, vec!["Column 1"]);
+        assert_eq!(tables[1].rows.len(), 1);
+    }
+
+    #[test]
+    fn test_extract_tables_none() {
+        let html = r#"<html><body><p>No tables here</p></body></html>"#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables.len(), 0);
+    }
+
+    #[test]
+    fn test_extract_tables_empty() {
+        let html = r#"<html><body><table></table></body></html>"#;
+        let document = Html::parse_document(html);
+        let tables = extract_tables(&document);
+
+        assert_eq!(tables.len(), 0);
+    }
+
+    // ========== Code Blocks Extraction Tests ==========
+
+    #[test]
+    fn test_extract_code_blocks_pre_code() {
+        let html = r#"
+            <html><body>
+                <pre><code>function hello() {
+    console.log("Hello");
+}</code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert!(code_blocks[0].content.contains("function hello()"));
+        assert_eq!(code_blocks[0].language, None);
+    }
+
+    #[test]
+    fn test_extract_code_blocks_with_language() {
+        let html = r#"
+            <html><body>
+                <pre><code class="language-rust">fn main() {
+    println!("Hello");
+}</code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert!(code_blocks[0].content.contains("fn main()"));
+        assert_eq!(code_blocks[0].language, Some("rust".to_string()));
+    }
+
+    #[test]
+    fn test_extract_code_blocks_lang_prefix() {
+        let html = r#"
+            <html><body>
+                <pre><code class="lang-python">def hello():
+    print("Hello")</code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert!(code_blocks[0].content.contains("def hello()"));
+        assert_eq!(code_blocks[0].language, Some("python".to_string()));
+    }
+
+    #[test]
+    fn test_extract_code_blocks_reconstructs_highlighted_lines() {
+        // Prism-style tokens, one wrapper per line with no newline between them, a <br>,
+        // and a line-number gutter
+        let html = r#"<html><body><pre class="line-numbers"><code class="language-rust"><div class="line"><span class="token keyword">fn</span> <span class="token function">main</span><span class="token punctuation">()</span> <span class="token punctuation">{</span></div><div class="line">    <span class="token macro">println!</span><span class="token punctuation">(</span><span class="token string">"hi"</span><span class="token punctuation">);</span></div><div class="line"><span class="token punctuation">}</span></div><span class="line-numbers-rows"><span></span><span></span><span></span></span></code></pre>
+            <pre><code>let a = 1;<br>let b = 2;</code></pre></body></html>"#;
+        let code_blocks = extract_code_blocks(&Html::parse_document(html));
+
+        assert_eq!(code_blocks[0].content, "fn main() {\n    println!(\"hi\");\n}");
+        assert_eq!(code_blocks[1].content, "let a = 1;\nlet b = 2;");
+    }
+
+    #[test]
+    fn test_extract_code_blocks_language_on_pre() {
+        let html = r#"
+            <html><body>
+                <pre class="language-go"><code>package main</code></pre>
+                <pre data-lang="sql"><code>SELECT 1;</code></pre>
+                <pre class="language-go"><code class="language-rust">fn main() {}</code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks[0].language, Some("go".to_string()));
+        assert_eq!(code_blocks[1].language, Some("sql".to_string()));
+        assert_eq!(code_blocks[2].language, Some("rust".to_string()));
+    }
+
+    #[test]
+    fn test_extract_code_blocks_pre_only() {
+        let html = r#"
+            <html><body>
+                <pre>Plain preformatted text</pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert_eq!(code_blocks[0].content, "Plain preformatted text");
+        assert_eq!(code_blocks[0].language, None);
+    }
+
+    #[test]
+    fn test_extract_code_blocks_inline_code() {
+        let html = r#"
+            <html><body>
+                <p>Use the <code>print()</code> function</p>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert_eq!(code_blocks[0].content, "print()");
+        assert_eq!(code_blocks[0].language, None);
+    }
+
+    #[test]
+    fn test_extract_code_blocks_multiple() {
+        let html = r#"
+            <html><body>
+                <pre><code>code block 1</code></pre>
+                <pre><code>code block 2</code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 2);
+        assert_eq!(code_blocks[0].content, "code block 1");
+        assert_eq!(code_blocks[1].content, "code block 2");
+    }
+
+    #[test]
+    fn test_extract_code_blocks_none() {
+        let html = r#"<html><body><p>No code blocks here</p></body></html>"#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 0);
+    }
+
+    #[test]
+    fn test_extract_code_blocks_filters_empty() {
+        let html = r#"
+            <html><body>
+                <pre><code>Valid code</code></pre>
+                <pre><code>   </code></pre>
+                <pre><code></code></pre>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let code_blocks = extract_code_blocks(&document);
+
+        assert_eq!(code_blocks.len(), 1);
+        assert_eq!(code_blocks[0].content, "Valid code");
+    }
+
+    // ========== Semantic Inline Tests ==========
+
+    #[test]
+    fn test_extract_semantic_inline_abbr() {
+        let html = r#"<p>Written in <abbr title="HyperText Markup Language">HTML</abbr> and <abbr>CSS</abbr></p>"#;
+        let (abbreviations, times) = extract_semantic_inline(&Html::parse_document(html));
+
+        assert_eq!(
+            abbreviations,
+            vec![Abbreviation {
+                text: "HTML".to_string(),
+                expansion: "HyperText Markup Language".to_string()
+            }]
+        );
+        assert!(times.is_empty());
+    }
+
+    #[test]
+    fn test_extract_svg_title_and_use_reference() {
+        let html = r##"<html><body>
+            <svg role="img"><title>Shopping cart</title><use href="#icon"></use></svg>
+            <svg><image xlink:href="/img/logo.png"></image></svg>
+            <svg><path d="M0 0"></path></svg>
+        </body></html>"##;
+        let base = Url::parse("https://example.com/shop/").unwrap();
+        let svg = extract_svg(&Html::parse_document(html), &base);
+
+        assert_eq!(
+            svg,
+            vec![
+                SvgGraphic {
+                    title: Some("Shopping cart".to_string()),
+                    references: vec!["https://example.com/shop/#icon".to_string()],
+                },
+                SvgGraphic {
+                    title: None,
+                    references: vec!["https://example.com/img/logo.png".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_content_only_with_include_templates() {
+        let html = r#"<html><body>
+            <p>Rendered</p>
+            <template id="row"><ul><li><p>Item one</p></li><li><p>Item two</p></li></ul></template>
+        </body></html>"#;
+
+        let data = extract_page("https://a.com", html, 200, &test_args(&["https://a.com"]), None).unwrap();
+        assert_eq!(data.paragraphs, vec!["Rendered"]);
+
+        let args = test_args(&["--include-templates", "https://a.com"]);
+        let data = extract_page("https://a.com", html, 200, &args, None).unwrap();
+        assert_eq!(data.paragraphs, vec!["Rendered", "Item one", "Item two"]);
+    }
+
+    #[test]
+    fn test_extract_contacts_parses_mailto_and_tel() {
+        let html = r#"<html><body>
+            <a href="mailto:a@B.com?subject=Hi">Email us</a>
+            <a href="tel:+1-555-123-4567">Call</a>
+            <a href="tel:(555)%20123-4567;ext=89">Office</a>
+            <a href="MAILTO:a@b.COM">Email again</a>
+            <a href="https://example.com/">Home</a>
+        </body></html>"#;
+        let contacts = extract_contacts(&Html::parse_document(html));
+
+        assert_eq!(
+            contacts,
+            vec![
+                Contact {
+                    kind: ContactKind::Email,
+                    raw: "mailto:a@B.com?subject=Hi".to_string(),
+                    normalized: "a@b.com".to_string(),
+                    params: BTreeMap::from([("subject".to_string(), "Hi".to_string())]),
+                },
+                Contact {
+                    kind: ContactKind::Phone,
+                    raw: "tel:+1-555-123-4567".to_string(),
+                    normalized: "+15551234567".to_string(),
+                    params: BTreeMap::new(),
+                },
+                Contact {
+                    kind: ContactKind::Phone,
+                    raw: "tel:(555)%20123-4567;ext=89".to_string(),
+                    normalized: "5551234567".to_string(),
+                    params: BTreeMap::from([("ext".to_string(), "89".to_string())]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_contact_href_rejects_empty_addresses() {
+        assert_eq!(parse_contact_href("mailto:?subject=Hi"), None);
+        assert_eq!(parse_contact_href("tel:"), None);
+        assert_eq!(parse_contact_href("javascript:void(0)"), None);
+    }
+
+    #[test]
+    fn test_extract_app_state_next_data() {
+        let html = r#"<html><body><div id="__next"></div>
+            <script id="__NEXT_DATA__" type="application/json">
+                {"props":{"pageProps":{"product":{"id":42,"name":"Lamp"}}},"page":"/p/[id]"}
+            </script>
+        </body></html>"#;
+        let state = extract_app_state(&Html::parse_document(html)).unwrap();
+
+        assert_eq!(state["__NEXT_DATA__"]["page"], "/p/[id]");
+        assert_eq!(state["__NEXT_DATA__"]["props"]["pageProps"]["product"]["name"], "Lamp");
+    }
+
+    #[test]
+    fn test_extract_app_state_window_global() {
+        let html = r#"<html><head><script>
+            window.__INITIAL_STATE__ = {"cart":{"items":2}};
+            window.analytics = {"id": 1};
+        </script></head><body></body></html>"#;
+        let state = extract_app_state(&Html::parse_document(html)).unwrap();
+
+        assert_eq!(state["__INITIAL_STATE__"]["cart"]["items"], 2);
+        assert!(state.get("analytics").is_none());
+        assert!(extract_app_state(&Html::parse_document("<p>static</p>")).is_none());
+    }
+
+    #[test]
+    fn test_extract_scripts_classifies_third_party() {
+        let html = r#"<html><head>
+            <script src="/static/app.js"></script>
+            <script src="https://www.googletagmanager.com/gtag/js?id=G-1"></script>
+            <script>inline()</script>
+        </head><body></body></html>"#;
+        let page = Url::parse("https://example.com/shop").unwrap();
+        let scripts = extract_assets(&Html::parse_document(html), "script[src]", "src", &page);
+
+        assert_eq!(
+            scripts,
+            vec![
+                Asset { url: "https://example.com/static/app.js".to_string(), third_party: false },
+                Asset {
+                    url: "https://www.googletagmanager.com/gtag/js?id=G-1".to_string(),
+                    third_party: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_stylesheets_classifies_third_party() {
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="/css/site.css">
+            <link rel="Stylesheet preload" href="https://fonts.googleapis.com/css2?family=Inter">
+            <link rel="icon" href="/favicon.ico">
+        </head><body></body></html>"#;
+        let page = Url::parse("https://example.com/").unwrap();
+        let stylesheets =
+            extract_assets(&Html::parse_document(html), STYLESHEET_SELECTOR, "href", &page);
+
+        assert_eq!(
+            stylesheets,
+            vec![
+                Asset { url: "https://example.com/css/site.css".to_string(), third_party: false },
+                Asset {
+                    url: "https://fonts.googleapis.com/css2?family=Inter".to_string(),
+                    third_party: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_sections_counts_words_per_heading() {
+        let html = r#"<html><body>
+            <p>Intro text is not in any section</p>
+            <h1>Guide</h1>
+            <h2>Setup</h2>
+            <p>Install the package first.</p>
+            <ul><li>one two</li><li><p>three</p></li></ul>
+            <h3>Details</h3>
+            <p>Four more words here.</p>
+            <h2>Usage</h2>
+            <p>Run it.</p>
+        </body></html>"#;
+        let sections = extract_sections(&Html::parse_document(html));
+
+        let section = |heading: &str, level, word_count| Section { heading: heading.to_string(), level, word_count };
+        assert_eq!(
+            sections,
+            vec![
+                section("Guide", 1, 13),
+                section("Setup", 2, 11),
+                section("Details", 3, 4),
+                section("Usage", 2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dom_stats_counts_elements_and_depth() {
+        // html, head, title, body, div, ul, li, li, a = 9 elements; html > body > div > ul > li > a
+        let html = r#"<html><head><title>T</title></head><body><div><ul>
+            <li>one</li><li><a href="/">two</a></li>
+        </ul></div></body></html>"#;
+        assert_eq!(dom_stats(&Html::parse_document(html)), (9, 6));
+    }
+
+    #[test]
+    fn test_extract_semantic_inline_time() {
+        let html = r#"<p>Posted <time datetime="2024-01-01">Jan 1</time>, updated <time>2024-02-01</time></p>"#;
+        let (_, times) = extract_semantic_inline(&Html::parse_document(html));
+
+        assert_eq!(
+            times,
+            vec![
+                TimeValue { text: "Jan 1".to_string(), datetime: "2024-01-01".to_string() },
+                TimeValue { text: "2024-02-01".to_string(), datetime: "2024-02-01".to_string() },
+            ]
+        );
+    }
+
+    // ========== JSON Format Tests ==========
+
+    #[test]
+    fn test_format_json_single_result() {
+        let data = vec![ScrapedData {
+            url: "https://example.com".to_string(),
+            status_code: 200,
+            title: Some("Test".to_string()),
+            headings: vec!["H1".to_string()],
+            paragraphs: vec!["Para".to_string()],
+            ..Default::default()
+        }];
+
+        let result = format_json(&data).unwrap();
+        assert!(result.contains("https://example.com"));
+        assert!(result.contains("Test"));
+        assert!(result.contains("H1"));
+    }
+
+    #[test]
+    fn test_format_json_multiple_results() {
+        let data = vec![
+            ScrapedData {
+                url: "https://example.com/1".to_string(),
+                status_code: 200,
+                title: Some("Page 1".to_string()),
+                ..Default::default()
+            },
+            ScrapedData {
+                url: "https://example.com/2".to_string(),
+                status_code: 200,
+                title: Some("Page 2".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let result = format_json(&data).unwrap();
+        assert!(result.contains("Page 1"));
+        assert!(result.contains("Page 2"));
+    }
+
+    // ========== CSV Format Tests ==========
+
+    #[test]
+    fn test_format_csv_headers() {
+        let data = vec![ScrapedData {
+            url: "https://example.com".to_string(),
+            status_code: 200,
+            title: Some("Test".to_string()),
+            ..Default::default()
+        }];
+
+        let result = format_csv(&data, None).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines[0], "url,status_code,title,headings_count,paragraphs_count,links_count,images_count,tables_count,code_blocks_count,depth,content_bytes");
+    }
+
+    #[test]
+    fn test_format_csv_data_row() {
+        let data = vec![ScrapedData {
+            url: "https://example.com".to_string(),
+            status_code: 200,
+            title: Some("Test".to_string()),
+            headings: vec!["H1".to_string()],
+            paragraphs: vec!["P1".to_string(), "P2".to_string()],
+            depth: Some(1),
+            ..Default::default()
+        }];
+
+        let result = format_csv(&data, None).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines[1], "https://example.com,200,Test,1,2,0,0,0,0,1,");
+    }
+
+    #[test]
+    fn test_format_csv_title_single_line_and_truncated() {
+        let data = vec![ScrapedData {
+            url: "https://example.com".to_string(),
+            status_code: 200,
+            title: Some("Café\nMenü and more".to_string()),
+            ..Default::default()
+        }];
+
+        let result = format_csv(&data, Some(9)).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1], "https://example.com,200,Café Menü,0,0,0,0,0,0,,");
+    }
+
+    // ========== Field Projection Tests ==========
+
+    #[test]
+    fn test_fields_projection_json_keeps_only_requested_keys() {
+        let data = vec![
+            crawled_page("https://example.com/", "Home", 0, &["https://example.com/a"]),
+            crawled_page("https://example.com/a", "A", 1, &[]),
+        ];
+        let fields = parse_field_list("url,title").unwrap();
+        let projected = project_fields(&data, &fields).unwrap();
+
+        for page in &projected {
+            let keys: Vec<&String> = page.as_object().unwrap().keys().collect();
+            assert_eq!(keys, vec!["url", "title"]);
+        }
+        assert_eq!(projected[1]["title"], "A");
+    }
+
+    #[test]
+    fn test_flat_json_joins_lists_into_strings() {
+        let data = vec![crawled_page(
+            "https://example.com/",
+            "Home",
+            0,
+            &["https://example.com/a", "https://example.com/b"],
+        )];
+        let args = test_args(&["--flat-json", "https://example.com"]);
+        let flat: serde_json::Value =
+            serde_json::from_str(&format_results(&data, &args, Palette::new("never", false)).unwrap())
+                .unwrap();
+        let page = &flat[0];
+
+        assert_eq!(page["links"], "https://example.com/a\nhttps://example.com/b");
+        assert_eq!(page["links_count"], 2);
+        assert_eq!(page["status_code"], 200);
+        assert_eq!(page["title"], "Home");
+        assert!(page.as_object().unwrap().values().all(|v| !v.is_array() && !v.is_object()));
+    }
+
+    #[test]
+    fn test_fields_projection_csv() {
+        let data = vec![crawled_page("https://example.com/", "Home", 0, &[])];
+        let fields = parse_field_list("title, url").unwrap();
+        let csv = format_csv_projected(&project_fields(&data, &fields).unwrap(), &fields).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines, vec!["title,url", "Home,https://example.com/"]);
+    }
+
+    #[test]
+    fn test_fields_unknown_name_errors() {
+        let err = parse_field_list("url,bogus").unwrap_err();
+        assert!(err.to_string().contains("Unknown field 'bogus'"));
+    }
+
+    #[test]
+    fn test_fields_list_matches_serialized_keys() {
+        // Every serialized key must be selectable, so the field list can't drift from the struct
+        let data = ScrapedData {
+            final_url: Some(String::new()),
+            upgraded_from: Some(String::new()),
+            amp_url: Some(String::new()),
+            source_urls: vec![String::new()],
+            etag: Some(String::new()),
+            last_modified: Some(String::new()),
+            content_type: Some(String::new()),
+            content_length: Some(0),
+            content_bytes: Some(0),
+            resolved_ip: Some(String::new()),
+            internal_links: vec![Link { text: String::new(), url: String::new(), download: None }],
+            external_links: vec![Link { text: String::new(), url: String::new(), download: None }],
+            primary_image: Some(String::new()),
+            tables: vec![Table { headers: vec![], rows: vec![] }],
+            code_blocks: vec![CodeBlock { content: String::new(), language: None }],
+            abbreviations: vec![Abbreviation { text: String::new(), expansion: String::new() }],
+            times: vec![TimeValue { text: String::new(), datetime: String::new() }],
+            svg: vec![SvgGraphic { title: None, references: vec![] }],
+            sections: vec![Section { heading: String::new(), level: 1, word_count: 0 }],
+            contacts: vec![Contact {
+                kind: ContactKind::Email,
+                raw: String::new(),
+                normalized: String::new(),
+                params: BTreeMap::new(),
+            }],
+            app_state: Some(serde_json::Value::Null),
+            scripts: vec![Asset { url: String::new(), third_party: false }],
+            stylesheets: vec![Asset { url: String::new(), third_party: false }],
+            dom_node_count: Some(0),
+            dom_max_depth: Some(0),
+            metadata: Some(Metadata::default()),
+            all_meta: vec![MetaTag { key: String::new(), value: String::new() }],
+            custom_selectors: vec![CustomSelectorResult {
+                selector: String::new(),
+                matches: vec![],
+                count: None,
+                paths: vec![String::new()],
+            }],
+            named_selectors: BTreeMap::from([(String::new(), serde_json::Value::Null)]),
+            depth: Some(0),
+            retries_used: Some(0),
+            error: Some(String::new()),
+            error_kind: Some(ErrorKind::Other),
+            audit: vec![String::new()],
+            user_data: Some(BTreeMap::new()),
+            ingested_at: Some(String::new()),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&data).unwrap();
+        let keys: Vec<&str> = value.as_object().unwrap().keys().map(|k| k.as_str()).collect();
+
+        assert_eq!(keys, SCRAPED_DATA_FIELDS);
+    }
+
+    // ========== Error Handling Tests ==========
+
+    #[test]
+    fn test_classify_http_status_success() {
+        let result = classify_http_status(200, "https://example.com");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_classify_http_status_404() {
+        let result = classify_http_status(404, "https://example.com/missing");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Not Found"));
+        assert!(err.to_string().contains("/missing"));
+    }
+
+    #[test]
+    fn test_classify_http_status_403() {
+        let result = classify_http_status(403, "https://example.com");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Forbidden"));
+        assert!(err.to_string().contains("bot protection"));
+    }
+
+    #[test]
+    fn test_classify_http_status_429() {
+        let result = classify_http_status(429, "https://example.com");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Rate limited"));
+        assert!(err.to_string().contains("Too many requests"));
+    }
+
+    #[test]
+    fn test_classify_http_status_500() {
+        let result = classify_http_status(500, "https://example.com");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Internal Server Error"));
+    }
+
+    #[test]
+    fn test_classify_http_status_503() {
+        let result = classify_http_status(503, "https://example.com");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Service Unavailable"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_cloudflare() {
+        let html = r#"<html><body><div class="cf-browser-verification">Checking your browser</div></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("Cloudflare"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_cloudflare_challenge() {
+        let html = r#"<html><body><div>Just a moment...</div><script>challenge-platform</script></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("JavaScript challenge"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_recaptcha() {
+        let html = r#"<html><body><div class="g-recaptcha"></div></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("reCAPTCHA"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_hcaptcha() {
+        let html = r#"<html><body><div class="h-captcha"></div></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("hCaptcha"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_perimeterx() {
+        let html = r#"<html><body><div class="px-captcha"></div></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("PerimeterX"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_datadome() {
+        let html = r#"<html><body><script src="datadome.js"></script></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("DataDome"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_title_access_denied() {
+        let html = r#"<html><body>Content</body></html>"#;
+        let result = detect_anti_bot_features(html, Some("Access Denied - Forbidden"));
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("Access restriction detected"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_title_blocked() {
+        let html = r#"<html><body>Content</body></html>"#;
+        let result = detect_anti_bot_features(html, Some("You have been blocked"));
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("Access restriction"));
+    }
+
+    #[test]
+    fn test_detect_anti_bot_none() {
+        let html = r#"<html><body><p>Normal content here</p></body></html>"#;
+        let result = detect_anti_bot_features(html, Some("Normal Page"));
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_detect_anti_bot_cloudflare_ray_id() {
+        let html = r#"<html><body><div>Cloudflare Ray ID: abc123</div></body></html>"#;
+        let result = detect_anti_bot_features(html, None);
+        assert!(result.is_some());
+        assert!(result.unwrap().contains("Cloudflare error page"));
+    }
+
+    // ========== URL File Reading Tests ==========
+
+    #[test]
+    fn test_read_urls_from_file_valid() {
+        use std::io::Write;
+
+        // Create a temporary file with valid URLs
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_valid.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "https://example.com").unwrap();
+        writeln!(file, "https://google.com").unwrap();
+        writeln!(file, "https://rust-lang.org").unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_ok());
+        let urls = result.unwrap();
+        assert_eq!(urls.len(), 3);
+        assert_eq!(urls[0], "https://example.com");
+        assert_eq!(urls[1], "https://google.com");
+        assert_eq!(urls[2], "https://rust-lang.org");
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn test_read_urls_from_file_with_comments_and_empty_lines() {
+        use std::io::Write;
+
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_comments.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "# This is a comment").unwrap();
+        writeln!(file, "https://example.com").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "  ").unwrap();
+        writeln!(file, "# Another comment").unwrap();
+        writeln!(file, "https://google.com").unwrap();
+        writeln!(file).unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_ok());
+        let urls = result.unwrap();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[0], "https://example.com");
+        assert_eq!(urls[1], "https://google.com");
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn test_read_urls_from_file_mixed_valid_invalid() {
+        use std::io::Write;
+
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_mixed.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "https://example.com").unwrap();
+        writeln!(file, "not-a-valid-url").unwrap();
+        writeln!(file, "https://google.com").unwrap();
+        writeln!(file, "also invalid").unwrap();
+        writeln!(file, "https://rust-lang.org").unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_ok());
+        let urls = result.unwrap();
+        // Should only get valid URLs, invalid ones are logged as warnings
+        assert_eq!(urls.len(), 3);
+        assert_eq!(urls[0], "https://example.com");
+        assert_eq!(urls[1], "https://google.com");
+        assert_eq!(urls[2], "https://rust-lang.org");
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn test_read_urls_from_file_not_found() {
+        let result = read_urls_from_file("/nonexistent/path/to/urls.txt");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Failed to open URL file"));
+    }
+
+    #[test]
+    fn test_read_urls_from_file_no_valid_urls() {
+        use std::io::Write;
+
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_empty.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "# Only comments").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "# More comments").unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("No valid URLs found"));
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn test_read_urls_from_file_only_invalid_urls() {
+        use std::io::Write;
+
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_invalid.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "not-a-url").unwrap();
+        writeln!(file, "also-not-a-url").unwrap();
+        writeln!(file, "still-not-valid").unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("No valid URLs found"));
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    #[test]
+    fn test_read_urls_from_file_whitespace_handling() {
+        use std::io::Write;
+
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join("test_urls_whitespace.txt");
+        let mut file = std::fs::File::create(&file_path).unwrap();
+        writeln!(file, "  https://example.com  ").unwrap();
+        writeln!(file, "\thttps://google.com\t").unwrap();
+        writeln!(file, " \t https://rust-lang.org \t ").unwrap();
+        drop(file);
+
+        let result = read_urls_from_file(file_path.to_str().unwrap());
+        assert!(result.is_ok());
+        let urls = result.unwrap();
+        assert_eq!(urls.len(), 3);
+        // URLs should be trimmed
+        assert_eq!(urls[0], "https://example.com");
+        assert_eq!(urls[1], "https://google.com");
+        assert_eq!(urls[2], "https://rust-lang.org");
+
+        // Cleanup
+        std::fs::remove_file(&file_path).ok();
+    }
+
+    // ========== URL Canonicalization Tests ==========
+
+    #[test]
+    fn test_canonicalize_url() {
+        assert_eq!(canonicalize_url("https://X.com:443/a?utm_x=1"), "https://x.com/a");
+        assert_eq!(canonicalize_url("HTTP://a.com:80/p?id=7&fbclid=z&q=a%20b"), "http://a.com/p?id=7&q=a%20b");
+        assert_eq!(canonicalize_url("https://a.com:8443/"), "https://a.com:8443/");
+        assert_eq!(canonicalize_url("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_canonicalize_result_urls_rewrites_page_and_links() {
+        let mut data = crawled_page("https://X.com:443/a?utm_x=1", "A", 0, &["https://x.com/b?gclid=1"]);
+        canonicalize_result_urls(&mut data);
+
+        assert_eq!(data.url, "https://x.com/a");
+        assert_eq!(data.links[0].url, "https://x.com/b");
+    }
 
-        // Headings
-        format_text_list(
-            &mut output,
-            "Headings",
-            &data.headings,
-            data.headings.len(), // Show all headings
-            |heading| format!("  - {}\n", heading),
-        );
+    // ========== Multi-URL Dedup Tests ==========
 
-        // Paragraphs with truncation
-        if !data.paragraphs.is_empty() {
-            output.push_str(&format!("\nParagraphs ({}):\n", data.paragraphs.len()));
-            for (i, para) in data.paragraphs.iter().take(5).enumerate() {
-                output.push_str(&format!("  {}. {}\n", i + 1, truncate_text(para, 100)));
-            }
-            if data.paragraphs.len() > 5 {
-                output.push_str(&format!("  ... and {} more\n", data.paragraphs.len() - 5));
-            }
-        }
+    #[tokio::test]
+    async fn test_dedup_across_urls_collapses_seeds_resolving_to_same_page() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/a" | "/b" => MockResponse::redirect("/final"),
+            _ => MockResponse::html("<html><head><title>Final</title></head><body></body></html>"),
+        })
+        .await;
+        let url_a = format!("{}/a", base);
+        let url_b = format!("{}/b", base);
 
-        // Links
-        if !data.links.is_empty() {
-            output.push_str(&format!("\nLinks ({}):\n", data.links.len()));
-            for link in data.links.iter().take(10) {
-                output.push_str(&format!("  - {} ({})\n", link.text, link.url));
-            }
-            if data.links.len() > 10 {
-                output.push_str(&format!("  ... and {} more\n", data.links.len() - 10));
-            }
-        }
+        let args = test_args(&["--dedup-across-urls", &url_a, &url_b]);
+        let results = scrape_multiple(&args).await.unwrap();
 
-        // Images
-        if !data.images.is_empty() {
-            output.push_str(&format!("\nImages ({}):\n", data.images.len()));
-            for img in data.images.iter().take(5) {
-                output.push_str(&format!(
-                    "  - {} ({})\n",
-                    if img.alt.is_empty() {
-                        "No alt text"
-                    } else {
-                        &img.alt
-                    },
-                    img.src
-                ));
-            }
-            if data.images.len() > 5 {
-                output.push_str(&format!("  ... and {} more\n", data.images.len() - 5));
-            }
-        }
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, url_a);
+        assert_eq!(results[0].final_url, Some(format!("{}/final", base)));
+    }
 
-        // Tables
-        if !data.tables.is_empty() {
-            output.push_str(&format!("\nTables ({}):\n", data.tables.len()));
-            for (i, table) in data.tables.iter().take(3).enumerate() {
-                output.push_str(&format!("  Table {}:\n", i + 1));
-                if !table.headers.is_empty() {
-                    output.push_str(&format!("    Headers: {}\n", table.headers.join(", ")));
-                }
-                output.push_str(&format!("    Rows: {}\n", table.rows.len()));
-            }
-            if data.tables.len() > 3 {
-                output.push_str(&format!("  ... and {} more\n", data.tables.len() - 3));
-            }
-        }
+    #[tokio::test]
+    async fn test_without_dedup_across_urls_scrapes_each_seed() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/a" | "/b" => MockResponse::redirect("/final"),
+            _ => MockResponse::html("<html><head><title>Final</title></head><body></body></html>"),
+        })
+        .await;
+        let url_a = format!("{}/a", base);
+        let url_b = format!("{}/b", base);
 
-        // Code Blocks
-        if !data.code_blocks.is_empty() {
-            output.push_str(&format!("\nCode Blocks ({}):\n", data.code_blocks.len()));
-            for (i, code) in data.code_blocks.iter().take(3).enumerate() {
-                let lang = code
-                    .language
-                    .as_ref()
-                    .map(|l| format!(" ({})", l))
-                    .unwrap_or_default();
-                output.push_str(&format!(
-                    "  {}. {}{}\n",
-                    i + 1,
-                    truncate_text(&code.content, 60),
-                    lang
-                ));
-            }
-            if data.code_blocks.len() > 3 {
-                output.push_str(&format!(
-                    "  ... and {} more\n",
-                    data.code_blocks.len() - 3
-                ));
-            }
-        }
+        let args = test_args(&[&url_a, &url_b]);
+        let results = scrape_multiple(&args).await.unwrap();
 
-        // Metadata
-        if let Some(metadata) = &data.metadata {
-            output.push_str(&format_text_metadata(metadata));
-        }
+        assert_eq!(results.len(), 2);
+    }
 
-        // Custom selectors
-        if !data.custom_selectors.is_empty() {
-            output.push_str(&format_text_custom_selectors(&data.custom_selectors));
+    // ========== Anti-Bot Retry Tests ==========
+
+    /// Serves a reCAPTCHA challenge to Chrome user agents and real content to others
+    fn challenge_chrome_only(req: &MockRequest) -> MockResponse {
+        let ua = req.headers.get("user-agent").cloned().unwrap_or_default();
+        if ua.contains("Chrome") {
+            MockResponse::html(r#"<html><body><div class="g-recaptcha"></div></body></html>"#)
+        } else {
+            MockResponse::html("<html><head><title>Real Content</title></head><body></body></html>")
         }
     }
 
-    output
-}
+    #[test]
+    fn test_next_user_agent_differs_from_current() {
+        assert_ne!(next_user_agent(DEFAULT_USER_AGENT), DEFAULT_USER_AGENT);
+        assert_ne!(next_user_agent(FALLBACK_USER_AGENTS[0]), FALLBACK_USER_AGENTS[0]);
+    }
 
-// ========== Tests ==========
+    #[tokio::test]
+    async fn test_retry_on_anti_bot_succeeds_with_rotated_user_agent() {
+        let base = spawn_mock_server(challenge_chrome_only).await;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let args = test_args(&["--retry-on-anti-bot", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
 
-    // Helper function to create a base URL for testing
-    fn test_base_url() -> Url {
-        Url::parse("https://example.com/path/page.html").unwrap()
+        assert_eq!(data.title, Some("Real Content".to_string()));
     }
 
-    fn test_base_url_simple() -> Url {
-        Url::parse("https://example.com").unwrap()
+    #[tokio::test]
+    async fn test_anti_bot_fails_without_retry_flag() {
+        let base = spawn_mock_server(challenge_chrome_only).await;
+
+        let args = test_args(&[&base]);
+        let err = scrape_website(&base, &args, None, None).await.unwrap_err();
+
+        assert!(err.to_string().contains("reCAPTCHA"));
     }
 
-    // ========== URL Normalization Tests ==========
+    #[tokio::test]
+    async fn test_retry_budget_caps_retries_across_run() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-    #[test]
-    fn test_normalize_url_absolute_https() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "https://other.com/page");
-        assert_eq!(result, Some("https://other.com/page".to_string()));
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            challenge_chrome_only(req)
+        })
+        .await;
+
+        let first = format!("{}/a", base);
+        let second = format!("{}/b", base);
+        let args = test_args(&["--retry-on-anti-bot", "--retry-budget", "1", &first, &second]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        // The first page spends the only retry; the second fails on its first attempt
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, first);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
     }
 
     #[test]
-    fn test_normalize_url_absolute_http() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "http://other.com/page");
-        assert_eq!(result, Some("http://other.com/page".to_string()));
+    fn test_retry_budget_spend() {
+        let budget = RetryBudget::from_args(&test_args(&["--retry-budget", "2", "https://a.com"])).unwrap();
+        assert!(budget.try_spend());
+        assert!(budget.try_spend());
+        assert!(!budget.try_spend());
+
+        assert!(RetryBudget::from_args(&test_args(&["https://a.com"])).is_none());
+        assert!(retry_allowed(None, "https://a.com"));
     }
 
-    #[test]
-    fn test_normalize_url_protocol_relative() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "//cdn.example.com/image.jpg");
-        assert_eq!(result, Some("https://cdn.example.com/image.jpg".to_string()));
+    // ========== Error Output Tests ==========
+
+    #[tokio::test]
+    async fn test_include_errors_records_failed_page() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/missing" => MockResponse::status(404),
+            _ => MockResponse::html("<html><head><title>Ok</title></head><body></body></html>"),
+        })
+        .await;
+        let ok_url = format!("{}/ok", base);
+        let missing_url = format!("{}/missing", base);
+
+        let args = test_args(&["--include-errors", &ok_url, &missing_url]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].error, None);
+        assert_eq!(results[1].url, missing_url);
+        assert_eq!(results[1].status_code, 404);
+        assert!(results[1].error.as_ref().unwrap().contains("Not Found"));
+
+        let json = format_json(&results).unwrap();
+        assert!(json.contains("\"error\""));
+        assert!(json.contains("Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_include_errors_records_error_kind() {
+        // One server that never answers (timeout) and one closed port (connection refused)
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stalled_url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused_url = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+
+        let args = test_args(&["--include-errors", "-t", "1", &stalled_url, &refused_url]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results[0].error_kind, Some(ErrorKind::Timeout));
+        assert_eq!(results[1].error_kind, Some(ErrorKind::Connect));
+        let json = serde_json::to_value(&results).unwrap();
+        assert_eq!(json[0]["error_kind"], "timeout");
+        assert_eq!(json[1]["error_kind"], "connect");
+    }
+
+    #[tokio::test]
+    async fn test_error_message_verbose_includes_underlying_cause() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused_url = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+
+        let error = scrape_website(&refused_url, &test_args(&[&refused_url]), None, None)
+            .await
+            .unwrap_err();
+
+        let short = error_message(&error, false);
+        let full = error_message(&error, true);
+        assert!(full.starts_with(&short));
+        // The socket-level cause only shows up in the full chain
+        assert!(!short.to_lowercase().contains("refused"));
+        assert!(full.to_lowercase().contains("refused"), "{}", full);
+        assert_eq!(error_kind(&error), ErrorKind::Connect);
+    }
+
+    // ========== Retry Tests ==========
+
+    #[tokio::test]
+    async fn test_retries_transient_status_until_success() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => MockResponse::status(503),
+            _ => MockResponse::html("<html><head><title>Back</title></head></html>"),
+        })
+        .await;
+
+        let args = test_args(&["--retries", "2", "--retry-backoff", "1", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+
+        assert_eq!(data.title.as_deref(), Some("Back"));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert_eq!(data.retries_used, Some(2));
     }
 
-    #[test]
-    fn test_normalize_url_relative_path() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "other-page.html");
-        assert_eq!(result, Some("https://example.com/path/other-page.html".to_string()));
+    #[tokio::test]
+    async fn test_retries_used_only_reported_with_retries() {
+        let base = spawn_mock_server(|_| MockResponse::html("<html><head><title>Ok</title></head></html>")).await;
+
+        let args = test_args(&["--retries", "2", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.retries_used, Some(0));
+
+        let data = scrape_website(&base, &test_args(&[&base]), None, None).await.unwrap();
+        assert_eq!(data.retries_used, None);
+        assert!(!serde_json::to_string(&data).unwrap().contains("retries_used"));
     }
 
-    #[test]
-    fn test_normalize_url_absolute_path() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "/images/photo.jpg");
-        assert_eq!(result, Some("https://example.com/images/photo.jpg".to_string()));
+    #[tokio::test]
+    async fn test_retries_exhausted_return_final_error() {
+        let base = spawn_mock_server(|_| MockResponse::status(503)).await;
+
+        let args = test_args(&["--retries", "1", "--retry-backoff", "1", &base]);
+        let error = scrape_website(&base, &args, None, None).await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<ScraperError>(),
+            Some(ScraperError::HttpStatus(503, _))
+        ));
     }
 
-    #[test]
-    fn test_normalize_url_parent_directory() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "../other/page.html");
-        assert_eq!(result, Some("https://example.com/other/page.html".to_string()));
+    #[tokio::test]
+    async fn test_retry_status_makes_500_retryable() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(500)
+        })
+        .await;
+
+        let args = test_args(&["--retries", "2", "--retry-backoff", "1", &base]);
+        assert!(scrape_website(&base, &args, None, None).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        hits.store(0, Ordering::SeqCst);
+        let args = test_args(&["--retries", "2", "--retry-backoff", "1", "--retry-status", "500,502-504", &base]);
+        assert!(scrape_website(&base, &args, None, None).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
     }
 
     #[test]
-    fn test_normalize_url_with_fragment() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "/page#section");
-        assert_eq!(result, Some("https://example.com/page#section".to_string()));
+    fn test_is_transient_skips_permanent_network_errors() {
+        let defaults = test_args(&["https://a.com"]).retry_status;
+        let transient = |e: ScraperError| is_transient(&e.into(), &defaults);
+
+        assert!(transient(ScraperError::Timeout(30)));
+        assert!(transient(ScraperError::Interrupted("body cut short".to_string())));
+        assert!(transient(ScraperError::HttpStatus(503, String::new())));
+        assert!(!transient(ScraperError::NetworkError("Failed to build HTTP client".to_string())));
+        assert!(!transient(ScraperError::ConnectFailed(ErrorKind::Dns, String::new())));
+        assert!(!transient(ScraperError::HttpStatus(500, String::new())));
+        assert!(!transient(ScraperError::RateLimited(String::new())));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_status_not_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(404)
+        })
+        .await;
+
+        let args = test_args(&["--retries", "3", "--retry-backoff", "1", &base]);
+        assert!(scrape_website(&base, &args, None, None).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
     }
 
-    #[test]
-    fn test_normalize_url_with_query_params() {
-        let base = test_base_url();
-        let result = normalize_url(&base, "/search?q=test&lang=en");
-        assert_eq!(result, Some("https://example.com/search?q=test&lang=en".to_string()));
+    #[tokio::test]
+    async fn test_retries_respect_min_host_interval() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(503)
+        })
+        .await;
+
+        let args = test_args(&["--retries", "2", "--retry-backoff", "1", "--min-host-interval", "200", &base]);
+        let limits = RunLimits::from_args(&args);
+        let started = tokio::time::Instant::now();
+        limits.pace(&base).await;
+        assert!(scrape_website(&base, &args, None, Some(&limits)).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        // The gate hands out slots at least 200ms apart from the first release on, so after
+        // three releases its next slot for the host is 600ms or more past the start; arrival
+        // times at the mock server would also pick up scheduling jitter
+        let gate = limits.host_gate.as_ref().unwrap();
+        let next_slot = gate.next_slot.lock().await[&url_host_key(&base)];
+        assert!(next_slot - started >= Duration::from_millis(600), "{:?}", next_slot - started);
     }
 
-    // ========== Domain Checking Tests ==========
+    #[tokio::test]
+    async fn test_page_timeout_bounds_retries() {
+        let base = spawn_mock_server(|_| MockResponse::status(503)).await;
 
-    #[test]
-    fn test_is_same_domain_exact_match() {
-        assert!(is_same_domain("https://example.com/page", "example.com"));
+        // Backoffs of 300ms, 600ms, 1.2s, ... would run far past the one-second page budget
+        let args = test_args(&["--retries", "5", "--retry-backoff", "300", "--page-timeout", "1", &base]);
+        let started = std::time::Instant::now();
+        let err = scrape_website(&base, &args, None, None).await.unwrap_err();
+
+        assert!(matches!(err.downcast_ref::<ScraperError>(), Some(ScraperError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_millis(1500));
     }
 
-    #[test]
-    fn test_is_same_domain_with_subdomain() {
-        assert!(!is_same_domain("https://blog.example.com/page", "example.com"));
+    #[tokio::test]
+    async fn test_retry_on_empty_body_refetches() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => MockResponse::html(""),
+            _ => MockResponse::html("<html><head><title>Full</title></head></html>"),
+        })
+        .await;
+
+        let args = test_args(&["--retries", "1", "--retry-backoff", "1", "--retry-on-empty-body", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some("Full"));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Without the flag the empty body is accepted as an (empty) page
+        hits.store(0, Ordering::SeqCst);
+        let args = test_args(&["--retries", "1", "--retry-backoff", "1", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.title, None);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
     }
 
-    #[test]
-    fn test_is_same_domain_different_domain() {
-        assert!(!is_same_domain("https://other.com/page", "example.com"));
+    #[tokio::test]
+    async fn test_retry_on_empty_body_accepts_contentless_page() {
+        let base = spawn_mock_server(|_| MockResponse::html("<html><body></body></html>")).await;
+
+        let args = test_args(&["--retry-on-empty-body", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert!(data.headings.is_empty() && data.paragraphs.is_empty());
     }
 
     #[test]
-    fn test_is_same_domain_with_path() {
-        assert!(is_same_domain("https://example.com/path/to/page", "example.com"));
+    fn test_retry_delay_doubles_with_jitter() {
+        let mut rng = StdRng::from_entropy();
+        for (attempt, backoff) in [(1, 100), (2, 200), (3, 400)] {
+            let delay = retry_delay(100, attempt, &mut rng).as_millis() as u64;
+            assert!((backoff..=backoff + backoff / 2).contains(&delay), "{} -> {}", attempt, delay);
+        }
+        assert_eq!(retry_delay(0, 5, &mut rng), Duration::ZERO);
     }
 
     #[test]
-    fn test_is_same_domain_invalid_url() {
-        assert!(!is_same_domain("not-a-url", "example.com"));
+    fn test_seed_reproduces_retry_jitter() {
+        let delays = |args: &Args| {
+            let limits = RunLimits::from_args(args);
+            let mut rng = limits.rng.lock().unwrap();
+            (1..=6).map(|attempt| retry_delay(1000, attempt, &mut *rng)).collect::<Vec<_>>()
+        };
+        let seeded = test_args(&["--seed", "42", "https://a.com"]);
+
+        assert_eq!(delays(&seeded), delays(&seeded));
+        assert_ne!(delays(&seeded), delays(&test_args(&["--seed", "43", "https://a.com"])));
     }
 
     #[test]
-    fn test_is_same_domain_http_vs_https() {
-        assert!(is_same_domain("http://example.com/page", "example.com"));
+    fn test_error_kind_maps_scraper_errors() {
+        let kind = |e: ScraperError| error_kind(&e.into());
+        assert_eq!(kind(ScraperError::HttpStatus(404, String::new())), ErrorKind::HttpStatus);
+        assert_eq!(kind(ScraperError::RateLimited(String::new())), ErrorKind::HttpStatus);
+        assert_eq!(kind(ScraperError::AntiBotDetected(String::new())), ErrorKind::AntiBot);
+        assert_eq!(kind(ScraperError::ConnectFailed(ErrorKind::Dns, String::new())), ErrorKind::Dns);
+        assert_eq!(error_kind(&anyhow::anyhow!("boom")), ErrorKind::Other);
+    }
+
+    // ========== Concurrency Tests ==========
+
+    /// A server answering every request after `delay`, with `title` as the page title
+    async fn spawn_slow_server(delay: Duration, title: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    tokio::time::sleep(delay).await;
+                    let body = format!("<html><head><title>{}</title></head></html>", title);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_fetches_in_parallel_and_keeps_input_order() {
+        let slow_a = spawn_slow_server(Duration::from_millis(400), "Slow A").await;
+        let slow_b = spawn_slow_server(Duration::from_millis(400), "Slow B").await;
+        let fast = spawn_slow_server(Duration::ZERO, "Fast").await;
+
+        let args = test_args(&["--concurrency", "3", &slow_a, &slow_b, &fast]);
+        let started = std::time::Instant::now();
+        let results = scrape_multiple(&args).await.unwrap();
+
+        // Sequentially this would take at least 800ms
+        assert!(started.elapsed() < Duration::from_millis(750), "{:?}", started.elapsed());
+        let titles: Vec<_> = results.iter().map(|d| d.title.as_deref().unwrap()).collect();
+        assert_eq!(titles, vec!["Slow A", "Slow B", "Fast"]);
+    }
+
+    #[tokio::test]
+    async fn test_max_per_host_caps_in_flight_requests() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let in_flight = std::sync::Arc::new(AtomicUsize::new(0));
+        let peak = std::sync::Arc::new(AtomicUsize::new(0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (current, highest) = (in_flight.clone(), peak.clone());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (current, highest) = (current.clone(), highest.clone());
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    highest.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    current.fetch_sub(1, Ordering::SeqCst);
+                    let body = "<html><head><title>Ok</title></head></html>";
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        let urls: Vec<String> = (1..=6).map(|i| format!("{}/p{}", base, i)).collect();
+        let mut flags = vec!["--concurrency", "6", "--max-per-host", "2"];
+        flags.extend(urls.iter().map(String::as_str));
+        let started = std::time::Instant::now();
+        let results = scrape_multiple(&test_args(&flags)).await.unwrap();
+
+        assert_eq!(results.len(), 6);
+        assert!(peak.load(Ordering::SeqCst) <= 2, "{}", peak.load(Ordering::SeqCst));
+        // Six 100ms pages, two at a time
+        assert!(started.elapsed() >= Duration::from_millis(300), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_dedup_keeps_earlier_duplicate() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // /slow-* answer after 300ms; *-redirect pages redirect to /final, the rest declare it
+        // as their canonical URL
+        let slow_hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = slow_hits.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    if path.starts_with("/slow") {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                    }
+                    let response = if path.ends_with("-redirect") {
+                        "HTTP/1.1 301 Moved\r\nLocation: /final\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    } else {
+                        let body = format!(
+                            r#"<html><head><title>{}</title><link rel="canonical" href="/final"></head></html>"#,
+                            path
+                        );
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        let slow_redirect = format!("{}/slow-redirect", base);
+        let fast_redirect = format!("{}/fast-redirect", base);
+
+        // The in-flight repeat of the slow URL is never fetched
+        let args = test_args(&[
+            "--concurrency",
+            "3",
+            "--dedup-across-urls",
+            &slow_redirect,
+            &fast_redirect,
+            &slow_redirect,
+        ]);
+        let results = scrape_multiple(&args).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, slow_redirect);
+        assert_eq!(slow_hits.load(Ordering::SeqCst), 1);
+
+        let slow_page = format!("{}/slow-page", base);
+        let fast_page = format!("{}/fast-page", base);
+        let args = test_args(&["--concurrency", "2", "--dedup-by-declared-canonical", &slow_page, &fast_page]);
+        let results = scrape_multiple(&args).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, slow_page);
     }
 
-    // ========== Title Extraction Tests ==========
+    #[tokio::test]
+    async fn test_concurrency_skips_failed_pages() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused_url = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let ok = spawn_slow_server(Duration::from_millis(50), "Ok").await;
 
-    #[test]
-    fn test_extract_title_present() {
-        let html = r#"<html><head><title>Test Page Title</title></head><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let title = extract_title(&document);
-        assert_eq!(title, Some("Test Page Title".to_string()));
-    }
+        let args = test_args(&["--concurrency", "2", &refused_url, &ok]);
+        let results = scrape_multiple(&args).await.unwrap();
 
-    #[test]
-    fn test_extract_title_with_whitespace() {
-        let html = r#"<html><head><title>  Trimmed Title  </title></head><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let title = extract_title(&document);
-        assert_eq!(title, Some("Trimmed Title".to_string()));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title.as_deref(), Some("Ok"));
     }
 
-    #[test]
-    fn test_extract_title_missing() {
-        let html = r#"<html><head></head><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let title = extract_title(&document);
-        assert_eq!(title, None);
-    }
+    #[tokio::test]
+    async fn test_failed_pages_omitted_without_include_errors() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/missing" => MockResponse::status(404),
+            _ => MockResponse::html("<html><head><title>Ok</title></head><body></body></html>"),
+        })
+        .await;
+        let ok_url = format!("{}/ok", base);
+        let missing_url = format!("{}/missing", base);
 
-    #[test]
-    fn test_extract_title_empty() {
-        let html = r#"<html><head><title></title></head><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let title = extract_title(&document);
-        assert_eq!(title, Some("".to_string()));
+        let args = test_args(&[&ok_url, &missing_url]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, ok_url);
     }
 
-    // ========== Headings Extraction Tests ==========
+    // ========== Tree Output Tests ==========
 
-    #[test]
-    fn test_extract_headings_all_levels() {
-        let html = r#"
-            <html><body>
-                <h1>Heading 1</h1>
-                <h2>Heading 2</h2>
-                <h3>Heading 3</h3>
-                <h4>Heading 4</h4>
-                <h5>Heading 5</h5>
-                <h6>Heading 6</h6>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let headings = extract_headings(&document);
-        assert_eq!(headings.len(), 6);
-        assert_eq!(headings[0], "Heading 1");
-        assert_eq!(headings[5], "Heading 6");
+    fn crawled_page(url: &str, title: &str, depth: usize, links: &[&str]) -> ScrapedData {
+        ScrapedData {
+            url: url.to_string(),
+            status_code: 200,
+            title: Some(title.to_string()),
+            links: links
+                .iter()
+                .map(|l| Link {
+                    text: l.to_string(),
+                    url: l.to_string(),
+                    download: None,
+                })
+                .collect(),
+            depth: Some(depth),
+            ..Default::default()
+        }
     }
 
     #[test]
-    fn test_extract_headings_empty() {
-        let html = r#"<html><body><p>No headings here</p></body></html>"#;
-        let document = Html::parse_document(html);
-        let headings = extract_headings(&document);
-        assert_eq!(headings.len(), 0);
-    }
+    fn test_crawl_parents_follow_links_one_level_up() {
+        let results = vec![
+            crawled_page("https://example.com/", "Home", 0, &["https://example.com/a", "https://example.com/b"]),
+            crawled_page("https://example.com/a", "A", 1, &["https://example.com/c"]),
+            crawled_page("https://example.com/b", "B", 1, &[]),
+            crawled_page("https://example.com/c", "C", 2, &[]),
+        ];
 
-    #[test]
-    fn test_extract_headings_filters_empty() {
-        let html = r#"
-            <html><body>
-                <h1>Valid Heading</h1>
-                <h2>   </h2>
-                <h3></h3>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let headings = extract_headings(&document);
-        assert_eq!(headings.len(), 1);
-        assert_eq!(headings[0], "Valid Heading");
+        assert_eq!(crawl_parents(&results), vec![None, Some(0), Some(0), Some(1)]);
     }
 
     #[test]
-    fn test_extract_headings_trims_whitespace() {
-        let html = r#"<html><body><h1>  Trimmed  </h1></body></html>"#;
-        let document = Html::parse_document(html);
-        let headings = extract_headings(&document);
-        assert_eq!(headings[0], "Trimmed");
-    }
+    fn test_crawl_parents_chain_pagination_at_same_depth() {
+        let page = |url: &str, title: &str, depth: usize, links: &[&str], next: Option<&str>| ScrapedData {
+            next_page: next.map(str::to_string),
+            ..crawled_page(url, title, depth, links)
+        };
+        let blog = "https://example.com/blog";
+        let results = vec![
+            page("https://example.com/", "Home", 0, &[blog], Some("https://example.com/?page=2")),
+            page("https://example.com/?page=2", "Home 2", 0, &[], Some("https://example.com/?page=3")),
+            page("https://example.com/?page=3", "Home 3", 0, &[], None),
+            page(blog, "Blog", 1, &[], Some("https://example.com/blog/page/2")),
+            // Links back to page 1, which must not make the two each other's parent
+            page("https://example.com/blog/page/2", "Blog 2", 1, &[blog], None),
+        ];
 
-    // ========== Paragraphs Extraction Tests ==========
+        assert_eq!(crawl_parents(&results), vec![None, Some(0), Some(1), Some(0), Some(3)]);
 
-    #[test]
-    fn test_extract_paragraphs_multiple() {
-        let html = r#"
-            <html><body>
-                <p>First paragraph</p>
-                <p>Second paragraph</p>
-                <p>Third paragraph</p>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let paragraphs = extract_paragraphs(&document);
-        assert_eq!(paragraphs.len(), 3);
-        assert_eq!(paragraphs[0], "First paragraph");
+        let tree = format_tree(&results);
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "- Home (https://example.com/)",
+                "  - Home 2 (https://example.com/?page=2)",
+                "    - Home 3 (https://example.com/?page=3)",
+                "  - Blog (https://example.com/blog)",
+                "    - Blog 2 (https://example.com/blog/page/2)",
+            ]
+        );
     }
 
     #[test]
-    fn test_extract_paragraphs_filters_empty() {
-        let html = r#"
-            <html><body>
-                <p>Valid paragraph</p>
-                <p></p>
-                <p>   </p>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let paragraphs = extract_paragraphs(&document);
-        assert_eq!(paragraphs.len(), 1);
-        assert_eq!(paragraphs[0], "Valid paragraph");
-    }
+    fn test_format_tree_indentation_reflects_depth() {
+        let results = vec![
+            crawled_page("https://example.com/", "Home", 0, &["https://example.com/a", "https://example.com/b"]),
+            crawled_page("https://example.com/a", "A", 1, &["https://example.com/c"]),
+            crawled_page("https://example.com/b", "B", 1, &[]),
+            crawled_page("https://example.com/c", "C", 2, &[]),
+        ];
 
-    #[test]
-    fn test_extract_paragraphs_none() {
-        let html = r#"<html><body><div>Not a paragraph</div></body></html>"#;
-        let document = Html::parse_document(html);
-        let paragraphs = extract_paragraphs(&document);
-        assert_eq!(paragraphs.len(), 0);
+        let tree = format_tree(&results);
+        let lines: Vec<&str> = tree.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "- Home (https://example.com/)",
+                "  - A (https://example.com/a)",
+                "    - C (https://example.com/c)",
+                "  - B (https://example.com/b)",
+            ]
+        );
     }
 
-    // ========== Links Extraction Tests ==========
+    // ========== Extractor Guard Tests ==========
 
     #[test]
-    fn test_extract_links_absolute() {
-        let html = r#"
-            <html><body>
-                <a href="https://example.com/page">Link Text</a>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let links = extract_links(&document, &base_url);
-
-        assert_eq!(links.len(), 1);
-        assert_eq!(links[0].text, "Link Text");
-        assert_eq!(links[0].url, "https://example.com/page");
+    fn test_guarded_extract_returns_value() {
+        let result = guarded_extract("headings", "https://example.com", || vec!["H1".to_string()]);
+        assert_eq!(result, vec!["H1".to_string()]);
     }
 
     #[test]
-    fn test_extract_links_relative() {
-        let html = r#"
-            <html><body>
-                <a href="/about">About</a>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let links = extract_links(&document, &base_url);
+    fn test_guarded_extract_recovers_from_panic() {
+        let result: Vec<Table> = guarded_extract("tables", "https://example.com", || {
+            panic!("malformed table fragment")
+        });
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pathological_tables_do_not_lose_page() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html(
+                r#"<html><head><title>Broken Tables</title></head><body>
+                    <h1>Still Here</h1>
+                    <table><tr><td><table><tr><td>nested<table></td></tr></table>
+                    <table><th><td></th></td><tr><tr></table></table>
+                    <p>Paragraph survives</p>
+                </body></html>"#,
+            )
+        })
+        .await;
 
-        assert_eq!(links.len(), 1);
-        assert_eq!(links[0].text, "About");
-        assert_eq!(links[0].url, "https://example.com/about");
+        let args = test_args(&[&base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+
+        assert_eq!(data.title, Some("Broken Tables".to_string()));
+        assert_eq!(data.headings, vec!["Still Here"]);
+        assert_eq!(data.paragraphs, vec!["Paragraph survives"]);
     }
 
-    #[test]
-    fn test_extract_links_empty_text_uses_href() {
-        let html = r#"
-            <html><body>
-                <a href="/contact"></a>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let links = extract_links(&document, &base_url);
+    // ========== Rate Limiter Tests ==========
 
-        assert_eq!(links.len(), 1);
-        assert_eq!(links[0].text, "/contact");
-    }
+    #[tokio::test]
+    async fn test_rate_limiter_paces_requests() {
+        let limiter = RateLimiter::new(2.0);
+        let start = std::time::Instant::now();
 
-    #[test]
-    fn test_extract_links_no_href() {
-        let html = r#"
-            <html><body>
-                <a>No href attribute</a>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let links = extract_links(&document, &base_url);
+        for _ in 0..10 {
+            limiter.acquire().await;
+        }
 
-        assert_eq!(links.len(), 0);
+        // First permit is immediate, the other nine are spaced 500ms apart
+        assert!(start.elapsed() >= Duration::from_millis(4500));
     }
 
-    #[test]
-    fn test_extract_links_protocol_relative() {
-        let html = r#"
-            <html><body>
-                <a href="//cdn.example.com/page">CDN Link</a>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let links = extract_links(&document, &base_url);
+    #[tokio::test]
+    async fn test_host_interval_gate_spaces_same_host_only() {
+        let gate = HostIntervalGate::new(Duration::from_millis(300));
+        let start = std::time::Instant::now();
 
-        assert_eq!(links.len(), 1);
-        assert_eq!(links[0].url, "https://cdn.example.com/page");
+        gate.wait("https://a.example/1").await;
+        gate.wait("https://b.example/1").await;
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        gate.wait("https://a.example/2").await;
+        assert!(start.elapsed() >= Duration::from_millis(300));
     }
 
-    // ========== Images Extraction Tests ==========
+    #[tokio::test]
+    async fn test_min_host_interval_holds_under_high_rps() {
+        let base = spawn_mock_server(|_| MockResponse::html("<html><body></body></html>")).await;
+        let url_a = format!("{}/a", base);
+        let url_b = format!("{}/b", base);
 
-    #[test]
-    fn test_extract_images_absolute() {
-        let html = r#"
-            <html><body>
-                <img src="https://example.com/image.jpg" alt="Test Image">
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let images = extract_images(&document, &base_url);
+        // --rps 100 alone would allow the second request after 10ms
+        let args = test_args(&["--rps", "100", "--min-host-interval", "400", &url_a, &url_b]);
+        let start = std::time::Instant::now();
+        let results = scrape_multiple(&args).await.unwrap();
 
-        assert_eq!(images.len(), 1);
-        assert_eq!(images[0].alt, "Test Image");
-        assert_eq!(images[0].src, "https://example.com/image.jpg");
+        assert_eq!(results.len(), 2);
+        assert!(start.elapsed() >= Duration::from_millis(400));
     }
 
-    #[test]
-    fn test_extract_images_relative() {
-        let html = r#"
-            <html><body>
-                <img src="/images/photo.jpg" alt="Photo">
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let images = extract_images(&document, &base_url);
+    #[tokio::test]
+    async fn test_rps_supersedes_delay() {
+        let base = spawn_mock_server(|_| MockResponse::html("<html><body></body></html>")).await;
+        let url_a = format!("{}/a", base);
+        let url_b = format!("{}/b", base);
 
-        assert_eq!(images.len(), 1);
-        assert_eq!(images[0].src, "https://example.com/images/photo.jpg");
+        // A 10s --delay would dominate if it were still applied
+        let args = Args::parse_from([
+            "simple-web-scraper", "--quiet", "--delay", "10000", "--rps", "20", &url_a, &url_b,
+        ]);
+        let start = std::time::Instant::now();
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(start.elapsed() < Duration::from_secs(5));
     }
 
-    #[test]
-    fn test_extract_images_no_alt() {
-        let html = r#"
-            <html><body>
-                <img src="https://example.com/image.jpg">
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let images = extract_images(&document, &base_url);
+    // ========== Pagination Crawl Tests ==========
 
-        assert_eq!(images.len(), 1);
-        assert_eq!(images[0].alt, "");
+    fn paginated_site(req: &MockRequest) -> MockResponse {
+        match req.path.as_str() {
+            "/p1" => MockResponse::html(
+                r#"<html><head><title>Page 1</title><link rel="next" href="/p2"></head><body></body></html>"#,
+            ),
+            "/p2" => MockResponse::html(
+                r#"<html><head><title>Page 2</title></head><body><a rel="next" href="/p3">Next</a></body></html>"#,
+            ),
+            _ => MockResponse::html("<html><head><title>Page 3</title></head><body></body></html>"),
+        }
     }
 
-    #[test]
-    fn test_extract_images_protocol_relative() {
-        let html = r#"
-            <html><body>
-                <img src="//cdn.example.com/image.jpg" alt="CDN Image">
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let images = extract_images(&document, &base_url);
+    #[tokio::test]
+    async fn test_follow_pagination_ignores_depth_limit() {
+        let base = spawn_mock_server(paginated_site).await;
+        let start = format!("{}/p1", base);
 
-        assert_eq!(images.len(), 1);
-        assert_eq!(images[0].src, "https://cdn.example.com/image.jpg");
+        let args = test_args(&["--crawl", "--max-depth", "1", "--follow-pagination", "--metadata", &start]);
+        let results = crawl_website(&args).await.unwrap();
+
+        let titles: Vec<_> = results.iter().filter_map(|r| r.title.clone()).collect();
+        assert_eq!(titles, vec!["Page 1", "Page 2", "Page 3"]);
+        let metadata = results[1].metadata.as_ref().unwrap();
+        assert_eq!(metadata.next_url, Some(format!("{}/p3", base)));
     }
 
-    #[test]
-    fn test_extract_images_no_src() {
-        let html = r#"
-            <html><body>
-                <img alt="No source">
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let base_url = test_base_url_simple();
-        let images = extract_images(&document, &base_url);
+    #[tokio::test]
+    async fn test_pagination_not_followed_by_default() {
+        let base = spawn_mock_server(paginated_site).await;
+        let start = format!("{}/p1", base);
 
-        assert_eq!(images.len(), 0);
+        let args = test_args(&["--crawl", "--max-depth", "1", &start]);
+        let results = crawl_website(&args).await.unwrap();
+
+        assert_eq!(results.len(), 1);
     }
 
-    // ========== Metadata Extraction Tests ==========
+    // ========== Stop-On-Status Tests ==========
 
     #[test]
-    fn test_extract_metadata_complete() {
-        let html = r#"
-            <html><head>
-                <meta name="description" content="Test description">
-                <meta name="keywords" content="test, keywords">
-                <meta name="author" content="Test Author">
-                <meta property="og:title" content="OG Title">
-                <meta property="og:description" content="OG Description">
-                <meta property="og:image" content="https://example.com/og.jpg">
-                <meta property="og:url" content="https://example.com">
-                <link rel="canonical" href="https://example.com/canonical">
-                <link rel="icon" href="/favicon.ico">
-            </head><body></body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let metadata = extract_metadata(&document);
+    fn test_host_status_tracker_resets_on_other_status() {
+        let mut tracker = HostStatusTracker::new(parse_status_list("403,429").unwrap(), 2);
 
-        assert_eq!(metadata.description, Some("Test description".to_string()));
-        assert_eq!(metadata.keywords, Some("test, keywords".to_string()));
-        assert_eq!(metadata.author, Some("Test Author".to_string()));
-        assert_eq!(metadata.og_title, Some("OG Title".to_string()));
-        assert_eq!(metadata.og_description, Some("OG Description".to_string()));
-        assert_eq!(metadata.og_image, Some("https://example.com/og.jpg".to_string()));
-        assert_eq!(metadata.og_url, Some("https://example.com".to_string()));
-        assert_eq!(metadata.canonical_url, Some("https://example.com/canonical".to_string()));
-        assert_eq!(metadata.favicon, Some("/favicon.ico".to_string()));
+        tracker.record("https://example.com/a", 403);
+        tracker.record("https://example.com/b", 200);
+        tracker.record("https://example.com/c", 429);
+        assert!(!tracker.is_halted("https://example.com/d"));
+
+        tracker.record("https://example.com/e", 403);
+        assert!(tracker.is_halted("https://example.com/f"));
+        assert!(!tracker.is_halted("https://other.com/"));
     }
 
     #[test]
-    fn test_extract_metadata_empty() {
-        let html = r#"<html><head></head><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let metadata = extract_metadata(&document);
+    fn test_parse_status_list_invalid() {
+        assert!(parse_status_list("403,abc").is_err());
+        assert!(parse_status_list("499-400").is_err());
+    }
 
-        assert_eq!(metadata.description, None);
-        assert_eq!(metadata.keywords, None);
-        assert_eq!(metadata.author, None);
-        assert_eq!(metadata.og_title, None);
+    #[test]
+    fn test_parse_status_list_ranges() {
+        let codes = parse_status_list("200, 500-503").unwrap();
+        assert_eq!(codes.len(), 5);
+        assert!(codes.contains(&200) && codes.contains(&500) && codes.contains(&503));
+        assert!(!codes.contains(&504));
     }
 
     #[test]
-    fn test_extract_metadata_partial() {
-        let html = r#"
-            <html><head>
-                <meta name="description" content="Just description">
-                <meta property="og:title" content="Just OG title">
-            </head><body></body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let metadata = extract_metadata(&document);
+    fn test_keep_status_filters_output() {
+        let path = std::env::temp_dir().join("test_keep_status_filters_output.json");
+        let page = |url: &str, status_code| ScrapedData {
+            url: url.to_string(),
+            status_code,
+            ..Default::default()
+        };
+        let results = vec![
+            page("https://example.com/ok", 200),
+            page("https://example.com/missing", 404),
+            page("https://example.com/also-ok", 200),
+            page("https://example.com/down", 503),
+        ];
 
-        assert_eq!(metadata.description, Some("Just description".to_string()));
-        assert_eq!(metadata.og_title, Some("Just OG title".to_string()));
-        assert_eq!(metadata.keywords, None);
-        assert_eq!(metadata.author, None);
+        let args = test_args(&[
+            "--keep-status",
+            "200",
+            "-o",
+            path.to_str().unwrap(),
+            "https://example.com",
+        ]);
+        output_results(&results, &args).unwrap();
+
+        let kept: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let urls: Vec<&str> = kept
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["url"].as_str().unwrap())
+            .collect();
+        assert_eq!(urls, vec!["https://example.com/ok", "https://example.com/also-ok"]);
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn test_extract_metadata_shortcut_icon() {
-        let html = r#"
-            <html><head>
-                <link rel="shortcut icon" href="/favicon.png">
-            </head><body></body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let metadata = extract_metadata(&document);
+    fn test_gzip_output_round_trips() {
+        use std::io::Read;
 
-        assert_eq!(metadata.favicon, Some("/favicon.png".to_string()));
+        let path = std::env::temp_dir().join("test_gzip_output_round_trips.json.gz");
+        let results = vec![ScrapedData {
+            url: "https://example.com/".to_string(),
+            status_code: 200,
+            title: Some("Home".to_string()),
+            ..Default::default()
+        }];
+        let args = test_args(&["-o", path.to_str().unwrap(), "https://example.com"]);
+        output_results(&results, &args).unwrap();
+
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, format_json(&results).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
     }
 
-    // ========== Custom Selectors Tests ==========
+    #[tokio::test]
+    async fn test_stop_on_status_halts_host_after_threshold() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-    #[test]
-    fn test_process_custom_selectors_valid() {
-        let html = r#"
-            <html><body>
-                <div class="item">Item 1</div>
-                <div class="item">Item 2</div>
-                <div class="item">Item 3</div>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let selectors = vec![".item".to_string()];
-        let results = process_custom_selectors(&document, &selectors).unwrap();
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(403)
+        })
+        .await;
+        let urls: Vec<String> = (1..=5).map(|i| format!("{}/page{}", base, i)).collect();
 
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].selector, ".item");
-        assert_eq!(results[0].matches.len(), 3);
-        assert_eq!(results[0].matches[0], "Item 1");
+        let mut argv = vec!["--stop-on-status", "403", "--stop-on-status-threshold", "3", "--include-errors"];
+        argv.extend(urls.iter().map(|u| u.as_str()));
+        let results = scrape_multiple(&test_args(&argv)).await.unwrap();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert_eq!(results.len(), 3);
     }
 
+    // ========== Parquet Output Tests ==========
+
     #[test]
-    fn test_process_custom_selectors_multiple() {
-        let html = r#"
-            <html><body>
-                <h1>Heading</h1>
-                <p class="intro">Intro paragraph</p>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let selectors = vec!["h1".to_string(), ".intro".to_string()];
-        let results = process_custom_selectors(&document, &selectors).unwrap();
+    fn test_write_parquet_round_trip() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
 
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].matches[0], "Heading");
-        assert_eq!(results[1].matches[0], "Intro paragraph");
+        let mut untitled = crawled_page("https://example.com/a", "", 1, &[]);
+        untitled.title = None;
+        let mut home = crawled_page("https://example.com/", "Home", 0, &["https://example.com/a"]);
+        home.content_bytes = Some(2048);
+        let results = vec![home, untitled];
+
+        let path = std::env::temp_dir().join("test_write_parquet_round_trip.parquet");
+        write_parquet(&results, path.to_str().unwrap()).unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|r| r.unwrap()).collect();
+        let first = rows[0].to_string();
+        assert!(first.contains("url: \"https://example.com/\""));
+        assert!(first.contains("title: \"Home\""));
+        assert!(first.contains("links_count: 1"));
+        assert!(first.contains("content_bytes: 2048"));
+        let second = rows[1].to_string();
+        assert!(second.contains("title: null"));
+        assert!(second.contains("depth: 1"));
+        assert!(second.contains("content_bytes: null"));
+
+        std::fs::remove_file(&path).ok();
     }
 
+    // ========== Provenance Tests ==========
+
     #[test]
-    fn test_process_custom_selectors_no_matches() {
-        let html = r#"<html><body><p>Content</p></body></html>"#;
-        let document = Html::parse_document(html);
-        let selectors = vec![".nonexistent".to_string()];
-        let results = process_custom_selectors(&document, &selectors).unwrap();
+    fn test_parse_user_data_tags_and_run_id() {
+        let tags = vec!["source=news".to_string(), "team = data".to_string()];
+        let user_data = parse_user_data(&tags, Some("run-42")).unwrap().unwrap();
 
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].matches.len(), 0);
+        assert_eq!(user_data["source"], "news");
+        assert_eq!(user_data["team"], "data");
+        assert_eq!(user_data["run_id"], "run-42");
+        assert_eq!(parse_user_data(&[], None).unwrap(), None);
+        assert!(parse_user_data(&["novalue".to_string()], None).is_err());
     }
 
     #[test]
-    fn test_process_custom_selectors_invalid() {
-        let html = r#"<html><body></body></html>"#;
-        let document = Html::parse_document(html);
-        let selectors = vec!["invalid[[[selector".to_string()];
-        let result = process_custom_selectors(&document, &selectors);
+    fn test_user_data_serialized_on_each_record() {
+        let user_data = parse_user_data(&["source=news".to_string()], Some("run-42")).unwrap();
+        let results: Vec<ScrapedData> = ["https://example.com/a", "https://example.com/b"]
+            .iter()
+            .map(|url| ScrapedData {
+                url: url.to_string(),
+                user_data: user_data.clone(),
+                ingested_at: Some("2024-01-01T00:00:00Z".to_string()),
+                ..Default::default()
+            })
+            .collect();
 
-        assert!(result.is_err());
+        let json: serde_json::Value = serde_json::from_str(&format_json(&results).unwrap()).unwrap();
+        for record in json.as_array().unwrap() {
+            assert_eq!(record["user_data"]["source"], "news");
+            assert_eq!(record["user_data"]["run_id"], "run-42");
+            assert_eq!(record["ingested_at"], "2024-01-01T00:00:00Z");
+        }
     }
 
     #[test]
-    fn test_process_custom_selectors_filters_empty() {
-        let html = r#"
-            <html><body>
-                <div class="item">Valid</div>
-                <div class="item">   </div>
-                <div class="item"></div>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let selectors = vec![".item".to_string()];
-        let results = process_custom_selectors(&document, &selectors).unwrap();
-
-        assert_eq!(results[0].matches.len(), 1);
-        assert_eq!(results[0].matches[0], "Valid");
+    fn test_format_rfc3339() {
+        let at = |secs| std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(format_rfc3339(at(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(at(951_782_400)), "2000-02-29T00:00:00Z");
+        assert_eq!(format_rfc3339(at(1_704_067_199)), "2023-12-31T23:59:59Z");
     }
 
-    // ========== Crawl Queue Tests ==========
+    // ========== Mobile Preset Tests ==========
 
     #[test]
-    fn test_should_add_to_crawl_queue_same_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
+    fn test_mobile_preset_user_agent_and_headers() {
+        let args = test_args(&["--mobile", "https://example.com"]);
+        assert!(base_user_agent(&args).contains("Mobile"));
+        assert_eq!(request_headers(&args)["sec-ch-ua-mobile"], "?1");
+
+        let args = test_args(&["https://example.com"]);
+        assert!(!base_user_agent(&args).contains("Mobile"));
+        assert!(request_headers(&args).is_empty());
+
+        // An explicit user agent still wins over the preset
+        let args = test_args(&["--mobile", "-u", "MyBot/1.0", "https://example.com"]);
+        assert_eq!(base_user_agent(&args), "MyBot/1.0");
+    }
+
+    #[tokio::test]
+    async fn test_mobile_preset_sent_to_server() {
+        let base = spawn_mock_server(|req| {
+            let ua = req.headers.get("user-agent").cloned().unwrap_or_default();
+            let hint = req.headers.get("sec-ch-ua-mobile").cloned().unwrap_or_default();
+            MockResponse::html(&format!("<html><head><title>{} | {}</title></head></html>", hint, ua))
+        })
+        .await;
 
-        let result = should_add_to_crawl_queue(
-            "https://example.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
-        );
+        let args = test_args(&["--mobile", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        let title = data.title.unwrap();
 
-        assert_eq!(result, Some("https://example.com/page".to_string()));
+        assert!(title.starts_with("?1 | "));
+        assert!(title.contains("Mobile"));
     }
 
-    #[test]
-    fn test_should_add_to_crawl_queue_different_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
+    #[tokio::test]
+    async fn test_lang_sets_accept_language() {
+        let base = spawn_mock_server(|req| {
+            let lang = req.headers.get("accept-language").cloned().unwrap_or_default();
+            MockResponse::html(&format!("<html><head><title>[{}]</title></head></html>", lang))
+        })
+        .await;
 
-        let result = should_add_to_crawl_queue(
-            "https://other.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
-        );
+        let args = test_args(&["--lang", "en-US,en;q=0.9", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some("[en-US,en;q=0.9]"));
 
-        assert_eq!(result, None);
+        let data = scrape_website(&base, &test_args(&[&base]), None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some("[]"));
     }
 
-    #[test]
-    fn test_should_add_to_crawl_queue_already_visited() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let mut visited = HashSet::new();
-        visited.insert("https://example.com/page".to_string());
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
+    // ========== DOM Dump Tests ==========
 
-        let result = should_add_to_crawl_queue(
-            "https://example.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+    #[test]
+    fn test_dom_dump_filename() {
+        // Published FNV-1a test vector, so dump names stay stable across releases
+        assert_eq!(fnv1a_32(b"a"), 0xe40c_292c);
+        let hashed = |url: &str| format!("{:08x}", fnv1a_32(url.as_bytes()));
+        assert_eq!(
+            dom_dump_filename("https://example.com/"),
+            format!("example.com~{}.html", hashed("https://example.com/"))
+        );
+        assert_eq!(
+            dom_dump_filename("https://example.com/a/b?page=2"),
+            format!("example.com_a_b_page_2~{}.html", hashed("https://example.com/a/b?page=2"))
         );
-
-        assert_eq!(result, None);
     }
 
-    #[test]
-    fn test_should_add_to_crawl_queue_relative_url() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
+    #[tokio::test]
+    async fn test_dump_dom_writes_normalized_html() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html("<title>Broken</title><ul><li>One<li>Two</ul><p>Unclosed <b>bold")
+        })
+        .await;
+        let dir = std::env::temp_dir().join("test_dump_dom_writes_normalized_html");
+        let _ = std::fs::remove_dir_all(&dir);
 
-        let result = should_add_to_crawl_queue(
-            "/about",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
-        );
+        let args = test_args(&["--dump-dom", dir.to_str().unwrap(), &base]);
+        scrape_website(&base, &args, None, None).await.unwrap();
 
-        assert_eq!(result, Some("https://example.com/about".to_string()));
+        let dumped = std::fs::read_to_string(dir.join(dom_dump_filename(&base))).unwrap();
+        assert!(dumped.starts_with("<html><head><title>Broken</title></head><body>"));
+        assert!(dumped.contains("<li>One</li><li>Two</li></ul>"));
+        assert!(dumped.contains("<p>Unclosed <b>bold</b></p>"));
+        assert!(dumped.ends_with("</body></html>"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_should_add_to_crawl_queue_relative_different_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
-
-        // This should resolve to example.com domain
-        let result = should_add_to_crawl_queue(
-            "../page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+    fn test_dom_dump_filename_is_capped_and_unique_per_url() {
+        let long = format!("https://example.com/{}", "segment/".repeat(100));
+        let name = dom_dump_filename(&long);
+        assert!(name.len() <= DOM_DUMP_STEM_MAX + "~00000000.html".len(), "{}", name);
+        assert!(name.starts_with("example.com_segment_"));
+        assert_ne!(name, dom_dump_filename(&format!("{}more", long)));
+
+        // Both sanitize to example.com_a_b, but must not overwrite each other
+        assert_ne!(
+            dom_dump_filename("https://example.com/a/b"),
+            dom_dump_filename("https://example.com/a_b")
         );
+    }
 
-        assert!(result.is_some());
-        assert!(result.unwrap().starts_with("https://example.com"));
+    #[test]
+    fn test_dump_dom_flag_defaults_to_stderr() {
+        let args = test_args(&["--dump-dom", "--", "https://example.com"]);
+        assert_eq!(args.dump_dom.as_deref(), Some("-"));
     }
 
-    // ========== Domain Filtering Tests ==========
+    // ========== Feed Mode Tests ==========
 
-    #[test]
-    fn test_domain_filtering_allow_list_includes_allowed_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let mut allow_domains = HashSet::new();
-        allow_domains.insert("docs.example.com".to_string());
-        let block_domains = HashSet::new();
+    const TEST_RSS: &str = r#"<?xml version="1.0"?>
+        <rss version="2.0"><channel>
+          <title>News</title><link>/</link>
+          <item><title>One</title><link>/articles/1</link></item>
+          <item><title>Two</title><link><![CDATA[/articles/2?a=1&b=2]]></link></item>
+        </channel></rss>"#;
 
-        let result = should_add_to_crawl_queue(
-            "https://docs.example.com/api",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+    #[test]
+    fn test_parse_feed_links_rss() {
+        let base = Url::parse("https://example.com/feed.xml").unwrap();
+        assert_eq!(
+            parse_feed_links(TEST_RSS, &base),
+            vec!["https://example.com/articles/1", "https://example.com/articles/2?a=1&b=2"]
         );
-
-        assert_eq!(result, Some("https://docs.example.com/api".to_string()));
     }
 
     #[test]
-    fn test_domain_filtering_allow_list_blocks_non_allowed_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let mut allow_domains = HashSet::new();
-        allow_domains.insert("docs.example.com".to_string());
-        let block_domains = HashSet::new();
+    fn test_parse_feed_links_atom() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+          <link rel="self" href="https://example.com/atom.xml"/>
+          <entry><title>A</title>
+            <link rel="edit" href="https://example.com/edit/a"/>
+            <link rel="alternate" type="text/html" href="https://example.com/a"/>
+          </entry>
+          <entry><title>B</title><link href="/b"/></entry>
+        </feed>"#;
+        let base = Url::parse("https://example.com/atom.xml").unwrap();
 
-        // other.com is not in allow list, should be blocked
-        let result = should_add_to_crawl_queue(
-            "https://other.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+        assert_eq!(
+            parse_feed_links(atom, &base),
+            vec!["https://example.com/a", "https://example.com/b"]
         );
-
-        assert_eq!(result, None);
+        assert!(parse_feed_links("<html><body>Not a feed</body></html>", &base).is_empty());
     }
 
-    #[test]
-    fn test_domain_filtering_allow_list_always_includes_base_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let mut allow_domains = HashSet::new();
-        allow_domains.insert("docs.example.com".to_string());
-        let block_domains = HashSet::new();
+    #[tokio::test]
+    async fn test_feed_mode_seeds_crawl_with_items() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/feed.xml" => MockResponse::html(TEST_RSS),
+            path => MockResponse::html(&format!("<html><head><title>{}</title></head></html>", path)),
+        })
+        .await;
 
-        // Base domain should always be allowed even if not explicitly in allow list
-        let result = should_add_to_crawl_queue(
-            "https://example.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+        let feed = format!("{}/feed.xml", base);
+        let args = test_args(&["--crawl", "--feed-mode", "--max-depth", "0", &feed]);
+        let results = crawl_website(&args).await.unwrap();
+
+        let urls: Vec<&str> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                format!("{}/articles/1", base),
+                format!("{}/articles/2?a=1&b=2", base)
+            ]
         );
+        assert!(results.iter().all(|r| r.depth == Some(0)));
+    }
 
-        assert_eq!(result, Some("https://example.com/page".to_string()));
+    #[tokio::test]
+    async fn test_feed_mode_scrapes_articles() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/feed.xml" => MockResponse::html(TEST_RSS),
+            path => MockResponse::html(&format!("<html><head><title>{}</title></head></html>", path)),
+        })
+        .await;
+
+        let args = test_args(&["--feed-mode", &format!("{}/feed.xml", base)]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        let titles: Vec<_> = results.iter().filter_map(|r| r.title.as_deref()).collect();
+        assert_eq!(titles, vec!["/articles/1", "/articles/2?a=1&b=2"]);
+    }
+
+    #[tokio::test]
+    async fn test_feed_fetch_is_paced_and_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let feed_hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = feed_hits.clone();
+        let base = spawn_mock_server(move |req| match req.path.as_str() {
+            "/feed.xml" => match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::status(503),
+                _ => MockResponse::html(TEST_RSS),
+            },
+            path => MockResponse::html(&format!("<html><head><title>{}</title></head></html>", path)),
+        })
+        .await;
+
+        let feed = format!("{}/feed.xml", base);
+        let args = test_args(&["--feed-mode", "--retries", "1", "--retry-backoff", "1", "--min-host-interval", "150", &feed]);
+        let started = std::time::Instant::now();
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(feed_hits.load(Ordering::SeqCst), 2);
+        // Two feed requests and two articles, each waiting its turn at the host gate
+        assert!(started.elapsed() >= Duration::from_millis(450), "{:?}", started.elapsed());
     }
 
+    // ========== Crawler Trap Tests ==========
+
     #[test]
-    fn test_domain_filtering_block_list_blocks_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let mut block_domains = HashSet::new();
-        block_domains.insert("ads.example.com".to_string());
+    fn test_path_trap_repeated_segments() {
+        assert!(is_path_trap("https://example.com/x/x/x/x/x", Some(3), None));
+        assert!(!is_path_trap("https://example.com/x/y/z", Some(3), None));
+        assert!(!is_path_trap("https://example.com/x/x/x/", Some(3), None));
+        assert!(!is_path_trap("https://example.com/x/x/x/x/x", None, None));
+    }
 
-        let result = should_add_to_crawl_queue(
-            "https://ads.example.com/tracker",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
+    #[test]
+    fn test_path_trap_max_depth() {
+        assert!(is_path_trap("https://example.com/a/b/c/d", None, Some(3)));
+        assert!(!is_path_trap("https://example.com/a/b/c/", None, Some(3)));
+    }
+
+    #[tokio::test]
+    async fn test_crawl_skips_repeated_segment_links() {
+        let base = spawn_mock_server(|req| {
+            // Relative-link bug: every page links one level deeper into itself
+            MockResponse::html(&format!(
+                r#"<html><body><a href="{}loop/">again</a></body></html>"#,
+                req.path
+            ))
+        })
+        .await;
+
+        let args = test_args(&[
+            "--crawl",
+            "--max-depth",
+            "10",
+            "--max-repeated-segments",
+            "2",
+            &format!("{}/", base),
+        ]);
+        let results = crawl_website(&args).await.unwrap();
+
+        let urls: Vec<&str> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                format!("{}/", base),
+                format!("{}/loop/", base),
+                format!("{}/loop/loop/", base)
+            ]
         );
-
-        assert_eq!(result, None);
     }
 
-    #[test]
-    fn test_domain_filtering_block_list_allows_non_blocked_same_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let mut block_domains = HashSet::new();
-        block_domains.insert("ads.example.com".to_string());
+    // ========== Cache Header Tests ==========
 
-        // Base domain should still work
-        let result = should_add_to_crawl_queue(
-            "https://example.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
-        );
+    #[tokio::test]
+    async fn test_capture_cache_headers() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html("<html><head><title>Cached</title></head></html>")
+                .with_header("ETag", "\"abc123\"")
+                .with_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+        })
+        .await;
 
-        assert_eq!(result, Some("https://example.com/page".to_string()));
+        let args = test_args(&["--capture-cache-headers", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.etag.as_deref(), Some("\"abc123\""));
+        assert_eq!(data.last_modified.as_deref(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+
+        // Not captured unless asked for
+        let args = test_args(&[&base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.etag, None);
+        assert_eq!(data.last_modified, None);
     }
 
-    #[test]
-    fn test_domain_filtering_cross_domain_allows_any_domain() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let block_domains = HashSet::new();
+    // ========== Primary Image Tests ==========
 
-        let result = should_add_to_crawl_queue(
-            "https://completely-different.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            true, // cross_domain enabled
-        );
+    #[test]
+    fn test_primary_image_prefers_og_image() {
+        let html = r#"<html><head>
+            <meta property="og:image" content="/og.jpg">
+            <link rel="image_src" href="/image-src.jpg">
+        </head><body><img src="/content.jpg"></body></html>"#;
+        let base = Url::parse("https://example.com/post").unwrap();
 
         assert_eq!(
-            result,
-            Some("https://completely-different.com/page".to_string())
+            extract_primary_image(&Html::parse_document(html), &base),
+            Some("https://example.com/og.jpg".to_string())
         );
     }
 
     #[test]
-    fn test_domain_filtering_cross_domain_respects_block_list() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let allow_domains = HashSet::new();
-        let mut block_domains = HashSet::new();
-        block_domains.insert("blocked.com".to_string());
+    fn test_primary_image_falls_back_to_content_image() {
+        let base = Url::parse("https://example.com/post").unwrap();
 
-        // Even with cross-domain enabled, blocked domains should still be blocked
-        let result = should_add_to_crawl_queue(
-            "https://blocked.com/page",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            true, // cross_domain enabled
+        let html = r#"<body><img src="/pixel.gif" width="1" height="1"><img src="/first.jpg"><img src="/second.jpg"></body>"#;
+        assert_eq!(
+            extract_primary_image(&Html::parse_document(html), &base),
+            Some("https://example.com/first.jpg".to_string())
         );
 
-        assert_eq!(result, None);
+        let html = r#"<body><img src="/small.jpg" width="50" height="50"><img src="/hero.jpg" width="1200" height="600"></body>"#;
+        assert_eq!(
+            extract_primary_image(&Html::parse_document(html), &base),
+            Some("https://example.com/hero.jpg".to_string())
+        );
+
+        assert_eq!(extract_primary_image(&Html::parse_document("<p>No images</p>"), &base), None);
     }
 
-    #[test]
-    fn test_domain_filtering_mixed_allow_and_block() {
-        let base_url = Url::parse("https://example.com").unwrap();
-        let visited = HashSet::new();
-        let mut allow_domains = HashSet::new();
-        allow_domains.insert("docs.example.com".to_string());
-        allow_domains.insert("api.example.com".to_string());
-        let mut block_domains = HashSet::new();
-        block_domains.insert("api.example.com".to_string());
+    // ========== External Link Split Tests ==========
 
-        // Block list takes precedence over allow list
-        let result = should_add_to_crawl_queue(
-            "https://api.example.com/endpoint",
-            &base_url,
-            "example.com",
-            &visited,
-            &allow_domains,
-            &block_domains,
-            false,
-        );
+    #[tokio::test]
+    async fn test_separate_external_splits_links() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/" => MockResponse::html(
+                r#"<html><body>
+                    <a href="/about">About</a>
+                    <a href="/contact">Contact</a>
+                    <a href="https://other.example.org/page">Elsewhere</a>
+                </body></html>"#,
+            ),
+            _ => MockResponse::html("<html><body>Leaf</body></html>"),
+        })
+        .await;
+        let start = format!("{}/", base);
 
-        assert_eq!(result, None);
-    }
+        let args = test_args(&["--separate-external", &start]);
+        let data = scrape_website(&start, &args, None, None).await.unwrap();
 
-    #[test]
-    fn test_parse_domain_list_comma_separated() {
-        let domains = parse_domain_list("example.com,docs.example.com,api.example.com");
-        assert_eq!(domains.len(), 3);
-        assert!(domains.contains("example.com"));
-        assert!(domains.contains("docs.example.com"));
-        assert!(domains.contains("api.example.com"));
+        assert!(data.links.is_empty());
+        let internal: Vec<&str> = data.internal_links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(internal, vec![format!("{}/about", base), format!("{}/contact", base)]);
+        let external: Vec<&str> = data.external_links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(external, vec!["https://other.example.org/page"]);
+        assert_eq!(data.all_links().count(), 3);
+
+        // Crawling still follows the internal links (and only those)
+        let args = test_args(&["--crawl", "--separate-external", "--cross-domain", &start]);
+        let results = crawl_website(&args).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.url.starts_with(&base)));
     }
 
+    // ========== Parse Limit Tests ==========
+
     #[test]
-    fn test_parse_domain_list_with_whitespace() {
-        let domains = parse_domain_list("  example.com  , docs.example.com , api.example.com  ");
-        assert_eq!(domains.len(), 3);
-        assert!(domains.contains("example.com"));
-        assert!(domains.contains("docs.example.com"));
-        assert!(domains.contains("api.example.com"));
+    fn test_truncate_with_ellipsis() {
+        assert_eq!(truncate_with_ellipsis("short", 10), "short");
+        assert_eq!(truncate_with_ellipsis("exactly", 7), "exactly");
+        assert_eq!(truncate_with_ellipsis("café au lait", 5), "café…");
+        assert_eq!(truncate_with_ellipsis("日本語のテキスト", 3), "日本…");
     }
 
-    #[test]
-    fn test_parse_domain_list_empty_entries() {
-        let domains = parse_domain_list("example.com,,docs.example.com,  ,api.example.com");
-        assert_eq!(domains.len(), 3);
-        assert!(domains.contains("example.com"));
-        assert!(domains.contains("docs.example.com"));
-        assert!(domains.contains("api.example.com"));
+    #[tokio::test]
+    async fn test_max_link_text_truncates_anchor_text() {
+        let long_text = "word ".repeat(100);
+        let html = format!(r#"<html><body><a href="/long"><p>{}</p></a><a href="/short">Short</a></body></html>"#, long_text);
+        let base = spawn_mock_server(move |_| MockResponse::html(&html)).await;
+
+        let args = test_args(&["--max-link-text", "50", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+
+        assert_eq!(data.links[0].text.chars().count(), 50);
+        assert!(data.links[0].text.ends_with('…'));
+        assert!(long_text.starts_with(data.links[0].text.trim_end_matches('…')));
+        assert_eq!(data.links[1].text, "Short");
     }
 
     #[test]
-    fn test_parse_domain_list_case_insensitive() {
-        let domains = parse_domain_list("Example.COM,DOCS.example.com,api.EXAMPLE.com");
-        assert_eq!(domains.len(), 3);
-        // All should be lowercased
-        assert!(domains.contains("example.com"));
-        assert!(domains.contains("docs.example.com"));
-        assert!(domains.contains("api.example.com"));
-    }
+    fn test_truncate_at_char_boundary() {
+        assert_eq!(truncate_at_char_boundary("hello", 10), "hello");
+        assert_eq!(truncate_at_char_boundary("hello", 3), "hel");
+        // 'é' is two bytes; cutting inside it backs off to the previous boundary
+        assert_eq!(truncate_at_char_boundary("café", 4), "caf");
+    }
+
+    #[tokio::test]
+    async fn test_max_parse_bytes_truncates_large_body() {
+        let base = spawn_mock_server(|_| {
+            let filler = "<p>filler paragraph</p>".repeat(5000);
+            MockResponse::html(&format!(
+                "<html><head><title>Huge</title></head><body>{}<h1>Late heading</h1></body></html>",
+                filler
+            ))
+        })
+        .await;
 
-    // ========== Text Formatting Helper Tests ==========
+        let args = test_args(&["--max-parse-bytes", "1000", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
 
-    #[test]
-    fn test_truncate_text_short() {
-        let text = "Short text";
-        let result = truncate_text(text, 100);
-        assert_eq!(result, "Short text");
+        assert_eq!(data.title, Some("Huge".to_string()));
+        assert!(data.paragraphs.len() < 50);
+        assert!(data.headings.is_empty());
+
+        let args = test_args(&[&base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.headings, vec!["Late heading"]);
     }
 
+    // ========== Crawl Content Type Tests ==========
+
     #[test]
-    fn test_truncate_text_long() {
-        let text = "This is a very long piece of text that should be truncated at the specified length with ellipsis added";
-        let result = truncate_text(text, 20);
-        assert_eq!(result, "This is a very long ...");
-        assert_eq!(result.len(), 23); // 20 chars + "..."
+    fn test_guess_content_type() {
+        assert_eq!(guess_content_type("https://example.com/file.PDF"), Some("application/pdf"));
+        assert_eq!(guess_content_type("https://example.com/index.html?x=1"), Some("text/html"));
+        assert_eq!(guess_content_type("https://example.com/page"), None);
+        assert_eq!(guess_content_type("https://example.com/v1.2/page"), None);
     }
 
     #[test]
-    fn test_truncate_text_exact_length() {
-        let text = "12345678901234567890"; // exactly 20 chars
-        let result = truncate_text(text, 20);
-        assert_eq!(result, "12345678901234567890");
+    fn test_content_type_allowed() {
+        let allowed = vec!["text/html".to_string(), "image/*".to_string()];
+        assert!(content_type_allowed("text/html; charset=utf-8", &allowed));
+        assert!(content_type_allowed("image/png", &allowed));
+        assert!(!content_type_allowed("application/pdf", &allowed));
     }
 
     #[test]
-    fn test_format_text_metadata() {
-        let metadata = Metadata {
-            description: Some("Test description".to_string()),
-            keywords: Some("test, rust".to_string()),
-            author: Some("Author Name".to_string()),
-            og_title: Some("OG Title".to_string()),
-            og_description: None,
-            og_image: Some("https://example.com/image.jpg".to_string()),
-            og_url: None,
-            canonical_url: None,
-            favicon: None,
-        };
+    fn test_crawl_content_types_rejects_pdf_link() {
+        let allowed = vec!["text/html".to_string()];
 
-        let result = format_text_metadata(&metadata);
-        assert!(result.contains("Description: Test description"));
-        assert!(result.contains("Keywords: test, rust"));
-        assert!(result.contains("Author: Author Name"));
-        assert!(result.contains("OG Title: OG Title"));
-        assert!(result.contains("OG Image: https://example.com/image.jpg"));
+        assert!(!crawl_link_type_allowed("https://example.com/file.pdf", &allowed));
+        assert!(crawl_link_type_allowed("https://example.com/page", &allowed));
     }
 
+    #[tokio::test]
+    async fn test_verify_content_type_uses_head() {
+        let base = spawn_mock_server(|req| {
+            assert_eq!(req.method, "HEAD");
+            let content_type = if req.path == "/download" { "application/pdf" } else { "text/html" };
+            MockResponse::status(200).with_header("Content-Type", content_type)
+        })
+        .await;
+        let args = test_args(&["--crawl-content-types", "text/html", "--verify-content-type", &base]);
+        let limits = RunLimits::from_args(&args);
+        let allowed = vec!["text/html".to_string()];
+
+        assert!(!verify_link_content_type(&format!("{}/download", base), &allowed, &args, &limits).await);
+        assert!(verify_link_content_type(&format!("{}/page", base), &allowed, &args, &limits).await);
+    }
+
+    #[tokio::test]
+    async fn test_verify_content_type_heads_each_link_once_at_fetch_time() {
+        let heads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = heads.clone();
+        let base = spawn_mock_server(move |req| {
+            if req.method == "HEAD" {
+                recorder.lock().unwrap().push(req.path.clone());
+                let content_type = if req.path == "/download" { "application/pdf" } else { "text/html" };
+                return MockResponse::status(200).with_header("Content-Type", content_type);
+            }
+            MockResponse::html(
+                r#"<html><head><title>T</title></head><body>
+                <a href="/download">Get</a><a href="/page">Page</a></body></html>"#,
+            )
+        })
+        .await;
+
+        let args = test_args(&["--crawl", "--crawl-content-types", "text/html", "--verify-content-type", &base]);
+        let results = crawl_website(&args).await.unwrap();
+        assert_eq!(results.len(), 2);
+        // /page links back to /download, which is only asked about once
+        let mut asked = heads.lock().unwrap().clone();
+        asked.sort();
+        assert_eq!(asked, vec!["/download", "/page"]);
+
+        // Links left in the queue once --max-pages is reached are never checked
+        heads.lock().unwrap().clear();
+        let args = test_args(&[
+            "--crawl",
+            "--max-pages",
+            "1",
+            "--crawl-content-types",
+            "text/html",
+            "--verify-content-type",
+            &base,
+        ]);
+        assert_eq!(crawl_website(&args).await.unwrap().len(), 1);
+        assert!(heads.lock().unwrap().is_empty());
+    }
+
+    // ========== Merge Output Tests ==========
+
     #[test]
-    fn test_format_text_custom_selectors() {
-        let selectors = vec![
-            CustomSelectorResult {
-                selector: ".item".to_string(),
-                matches: vec!["Match 1".to_string(), "Match 2".to_string()],
+    fn test_merge_into_adds_key_without_disturbing_others() {
+        let path = std::env::temp_dir().join("test_merge_into_adds_key.json");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(
+            &path,
+            r#"{"https://example.com/a": {"url": "https://example.com/a", "title": "Old A"},
+                "https://example.com/b": {"url": "https://example.com/b", "title": "Old B"}}"#,
+        )
+        .unwrap();
+
+        let results = vec![
+            ScrapedData {
+                url: "https://example.com/b".to_string(),
+                title: Some("New B".to_string()),
+                ..Default::default()
+            },
+            ScrapedData {
+                url: "https://example.com/c".to_string(),
+                title: Some("C".to_string()),
+                ..Default::default()
             },
         ];
+        merge_into_file(&results, path_str, None).unwrap();
 
-        let result = format_text_custom_selectors(&selectors);
-        assert!(result.contains("'.item' (2 matches)"));
-        assert!(result.contains("1. Match 1"));
-        assert!(result.contains("2. Match 2"));
+        let merged: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let keys: Vec<&String> = merged.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            vec!["https://example.com/a", "https://example.com/b", "https://example.com/c"]
+        );
+        assert_eq!(merged["https://example.com/a"]["title"], "Old A");
+        assert_eq!(merged["https://example.com/b"]["title"], "New B");
+        assert_eq!(merged["https://example.com/c"]["title"], "C");
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn test_format_text_custom_selectors_truncated() {
-        let selectors = vec![
-            CustomSelectorResult {
-                selector: ".item".to_string(),
-                matches: vec![
-                    "Match 1".to_string(),
-                    "Match 2".to_string(),
-                    "Match 3".to_string(),
-                    "Match 4".to_string(),
-                ],
-            },
-        ];
+    fn test_merge_into_creates_file_and_rejects_arrays() {
+        let path = std::env::temp_dir().join("test_merge_into_creates_file.json");
+        let path_str = path.to_str().unwrap();
+        let _ = std::fs::remove_file(&path);
+        let results = vec![ScrapedData { url: "https://example.com/".to_string(), ..Default::default() }];
 
-        let result = format_text_custom_selectors(&selectors);
-        assert!(result.contains("... and 1 more"));
+        merge_into_file(&results, path_str, Some(&["url".to_string()])).unwrap();
+        let merged: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(merged, serde_json::json!({"https://example.com/": {"url": "https://example.com/"}}));
+
+        std::fs::write(&path, "[]").unwrap();
+        assert!(merge_into_file(&results, path_str, None).is_err());
+
+        std::fs::remove_file(&path).unwrap();
     }
 
-    // ========== Tables Extraction Tests ==========
+    // ========== Requirement Gate Tests ==========
 
-    #[test]
-    fn test_extract_tables_with_headers() {
-        let html = r#"
-            <html><body>
-                <table>
-                    <tr><th>Name</th><th>Age</th></tr>
-                    <tr><td>Alice</td><td>30</td></tr>
-                    <tr><td>Bob</td><td>25</td></tr>
-                </table>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let tables = extract_tables(&document);
+    #[tokio::test]
+    async fn test_require_title_fails_titleless_page() {
+        let base = spawn_mock_server(|req| match req.path.as_str() {
+            "/titled" => MockResponse::html("<html><head><title>Fine</title></head></html>"),
+            _ => MockResponse::html("<html><head></head><body><p>Redesigned</p></body></html>"),
+        })
+        .await;
+        let titled = format!("{}/titled", base);
+        let untitled = format!("{}/untitled", base);
 
-        assert_eq!(tables.len(), 1);
-        assert_eq!(tables[0].headers, vec!["Name", "Age"]);
-        assert_eq!(tables[0].rows.len(), 2);
-        assert_eq!(tables[0].rows[0], vec!["Alice", "30"]);
-        assert_eq!(tables[0].rows[1], vec!["Bob", "25"]);
+        let args = test_args(&["--require-title", &titled, &untitled]);
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert!(results[0].requirement_failures.is_empty());
+        assert_eq!(results[1].requirement_failures, vec!["missing or empty <title>"]);
+
+        let err = report_requirement_failures(&results).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 page(s) failed validation");
     }
 
     #[test]
-    fn test_extract_tables_without_headers() {
-        let html = r#"
-            <html><body>
-                <table>
-                    <tr><td>Data 1</td><td>Data 2</td></tr>
-                    <tr><td>Data 3</td><td>Data 4</td></tr>
-                </table>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let tables = extract_tables(&document);
+    fn test_require_selector() {
+        let document = Html::parse_document(r#"<div class="price">10</div>"#);
 
-        assert_eq!(tables.len(), 1);
-        assert_eq!(tables[0].headers.len(), 0);
-        assert_eq!(tables[0].rows.len(), 2);
-        assert_eq!(tables[0].rows[0], vec!["Data 1", "Data 2"]);
+        let args = test_args(&["--require-selector", ".price", "--require-selector", ".stock", "https://a.com"]);
+        let failures = check_requirements(&document, Some("Title"), &args).unwrap();
+        assert_eq!(failures, vec!["required selector '.stock' matched nothing"]);
+
+        let args = test_args(&["--require-selector", "[[bad", "https://a.com"]);
+        assert!(check_requirements(&document, Some("Title"), &args).is_err());
+
+        assert!(report_requirement_failures(&[ScrapedData::default()]).is_ok());
     }
 
-    #[test]
-    fn test_extract_tables_multiple() {
-        let html = r#"
-            <html><body>
-                <table>
-                    <tr><th>Column 1</th></tr>
-                    <tr><td>Value 1</td></tr>
-                </table>
-                <table>
-                    <tr><td>Table 2</td></tr>
-                </table>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let tables = extract_tables(&document);
+    // ========== Head-Only Tests ==========
 
-        assert_eq!(tables.len(), 2);
-        assert_eq!(tables[0].headers, vec!["Column 1"]);
-        assert_eq!(tables[1].rows.len(), 1);
+    #[tokio::test]
+    async fn test_head_only_reports_status_without_body() {
+        let base = spawn_mock_server(|req| {
+            assert_eq!(req.method, "HEAD");
+            match req.path.as_str() {
+                "/gone" => MockResponse::status(404),
+                // The mock server sends Content-Length for the (withheld) 45-byte body
+                _ => MockResponse::html("<html><head><title>Body</title></head></html>"),
+            }
+        })
+        .await;
+
+        let args = test_args(&["--head-only", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.status_code, 200);
+        assert_eq!(data.content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert_eq!(data.content_length, Some(45));
+        // Nothing was parsed
+        assert_eq!(data.title, None);
+        assert!(data.links.is_empty());
+
+        // Error statuses are reported rather than failing the page
+        let gone = format!("{}/gone", base);
+        let data = scrape_website(&gone, &args, None, None).await.unwrap();
+        assert_eq!(data.status_code, 404);
+    }
+
+    #[tokio::test]
+    async fn test_head_only_falls_back_to_get() {
+        let base = spawn_mock_server(|req| match req.method.as_str() {
+            "HEAD" => MockResponse::status(405),
+            _ => MockResponse::html("<html><head><title>Body</title></head></html>"),
+        })
+        .await;
+
+        let args = test_args(&["--head-only", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.status_code, 200);
+        assert_eq!(data.title, None);
+    }
+
+    // ========== Read Timeout Tests ==========
+
+    #[tokio::test]
+    async fn test_read_timeout_fires_on_slow_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Headers arrive at once, then the body dribbles out a byte every 200ms
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let body = "<html><head><title>Slow</title></head></html>";
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            let _ = socket.write_all(head.as_bytes()).await;
+            for byte in body.bytes() {
+                if socket.write_all(&[byte]).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+
+        let args = test_args(&["--read-timeout", "1", &base]);
+        let started = std::time::Instant::now();
+        let err = scrape_website(&base, &args, None, None).await.unwrap_err();
+
+        assert!(matches!(err.downcast_ref::<ScraperError>(), Some(ScraperError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_page_timeout_abandons_stalled_page() {
+        // Accepts the connection but never answers, well within the 30s request timeout
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+
+        let args = test_args(&["--page-timeout", "1", &base]);
+        let started = std::time::Instant::now();
+        let err = scrape_website(&base, &args, None, None).await.unwrap_err();
+
+        assert!(matches!(err.downcast_ref::<ScraperError>(), Some(ScraperError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    // ========== Background Image Tests ==========
+
+    #[test]
+    fn test_extract_background_images() {
+        let html = r#"<html><head><style>
+            .hero { background: #000 url("/hero.png") no-repeat; }
+            .icon { background-image: url(data:image/png;base64,AAAA); }
+        </style></head><body>
+            <div style="background-image:url(/bg.jpg)"></div>
+            <div style="color: red; background-image: url('https://cdn.example.com/x.webp')"></div>
+            <div style="background-image:url(/bg.jpg)"></div>
+        </body></html>"#;
+        let base = Url::parse("https://example.com/page").unwrap();
+        let images = extract_background_images(&Html::parse_document(html), &base);
+
+        let srcs: Vec<&str> = images.iter().map(|i| i.src.as_str()).collect();
+        assert_eq!(
+            srcs,
+            vec![
+                "https://example.com/bg.jpg",
+                "https://cdn.example.com/x.webp",
+                "https://example.com/hero.png"
+            ]
+        );
+        assert!(images.iter().all(|i| i.background));
     }
 
     #[test]
-    fn test_extract_tables_none() {
-        let html = r#"<html><body><p>No tables here</p></body></html>"#;
-        let document = Html::parse_document(html);
-        let tables = extract_tables(&document);
+    fn test_background_flag_serialization() {
+        let regular = Image { alt: "a".to_string(), src: "/a.png".to_string(), background: false };
+        let background = Image { alt: String::new(), src: "/b.png".to_string(), background: true };
 
-        assert_eq!(tables.len(), 0);
+        assert!(!serde_json::to_string(&regular).unwrap().contains("background"));
+        assert!(serde_json::to_string(&background).unwrap().contains("\"background\":true"));
     }
 
+    // ========== Retry-After Pacing Tests ==========
+
     #[test]
-    fn test_extract_tables_empty() {
-        let html = r#"<html><body><table></table></body></html>"#;
-        let document = Html::parse_document(html);
-        let tables = extract_tables(&document);
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 2 "), Some(Duration::from_secs(2)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
 
-        assert_eq!(tables.len(), 0);
+    #[test]
+    fn test_retry_after_pause_is_capped() {
+        let args = test_args(&["https://a.com"]);
+        let page = |secs| -> Result<ScrapedData> {
+            Ok(ScrapedData {
+                retry_after: Some(Duration::from_secs(secs)),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(next_request_pause(&args, false, &page(2)), Duration::from_secs(2));
+        assert_eq!(next_request_pause(&args, true, &page(86400)), MAX_RETRY_AFTER);
     }
 
-    // ========== Code Blocks Extraction Tests ==========
+    #[tokio::test]
+    async fn test_retry_after_on_success_spaces_next_request() {
+        let fetched_at = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = fetched_at.clone();
+        let base = spawn_mock_server(move |req: &MockRequest| {
+            log.lock().unwrap().push(std::time::Instant::now());
+            let response = MockResponse::html("<html><body>ok</body></html>");
+            if req.path == "/first" {
+                response.with_header("Retry-After", "2")
+            } else {
+                response
+            }
+        })
+        .await;
+        let first = format!("{}/first", base);
+        let second = format!("{}/second", base);
 
-    #[test]
-    fn test_extract_code_blocks_pre_code() {
-        let html = r#"
-            <html><body>
-                <pre><code>function hello() {
-    console.log("Hello");
-}</code></pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+        let results = scrape_multiple(&test_args(&[&first, &second])).await.unwrap();
 
-        assert_eq!(code_blocks.len(), 1);
-        assert!(code_blocks[0].content.contains("function hello()"));
-        assert_eq!(code_blocks[0].language, None);
+        assert_eq!(results.len(), 2);
+        let fetched_at = fetched_at.lock().unwrap();
+        assert!(fetched_at[1] - fetched_at[0] >= Duration::from_secs(2));
     }
 
+    // ========== Error Delay Tests ==========
+
     #[test]
-    fn test_extract_code_blocks_with_language() {
-        let html = r#"
-            <html><body>
-                <pre><code class="language-rust">fn main() {
-    println!("Hello");
-}</code></pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_request_pause_adds_error_delay_only_after_failures() {
+        let args = Args::parse_from(["scraper", "--delay", "500", "--error-delay", "2000", "https://a.com"]);
 
-        assert_eq!(code_blocks.len(), 1);
-        assert!(code_blocks[0].content.contains("fn main()"));
-        assert_eq!(code_blocks[0].language, Some("rust".to_string()));
+        assert_eq!(request_pause(&args, false, false), Duration::from_millis(500));
+        assert_eq!(request_pause(&args, false, true), Duration::from_millis(2500));
+        // With --rps the limiter spaces requests, so only the error backoff remains
+        assert_eq!(request_pause(&args, true, false), Duration::ZERO);
+        assert_eq!(request_pause(&args, true, true), Duration::from_millis(2000));
+
+        let args = test_args(&["https://a.com"]);
+        assert_eq!(request_pause(&args, false, true), Duration::ZERO);
     }
 
+    // ========== Omit Empty Tests ==========
+
     #[test]
-    fn test_extract_code_blocks_lang_prefix() {
-        let html = r#"
-            <html><body>
-                <pre><code class="lang-python">def hello():
-    print("Hello")</code></pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_omit_empty_drops_empty_and_null_fields() {
+        let results = vec![ScrapedData {
+            url: "https://example.com".to_string(),
+            status_code: 200,
+            headings: vec!["Hello".to_string()],
+            metadata: Some(Metadata {
+                description: Some("Desc".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
 
-        assert_eq!(code_blocks.len(), 1);
-        assert!(code_blocks[0].content.contains("def hello()"));
-        assert_eq!(code_blocks[0].language, Some("python".to_string()));
+        let args = test_args(&["--omit-empty", "https://example.com"]);
+        let json = format_results(&results, &args, Palette::new("never", false)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let record = value[0].as_object().unwrap();
+
+        assert!(!record.contains_key("links"));
+        assert!(!record.contains_key("title"));
+        assert!(!record.contains_key("paragraphs"));
+        assert_eq!(record["headings"], serde_json::json!(["Hello"]));
+        assert_eq!(record["metadata"], serde_json::json!({"description": "Desc"}));
+
+        // Default output keeps the uniform shape
+        let args = test_args(&["https://example.com"]);
+        let json = format_results(&results, &args, Palette::new("never", false)).unwrap();
+        assert!(json.contains("\"links\": []"));
+        assert!(json.contains("\"title\": null"));
     }
 
-    #[test]
-    fn test_extract_code_blocks_pre_only() {
-        let html = r#"
-            <html><body>
-                <pre>Plain preformatted text</pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    // ========== Proxy Map Tests ==========
 
-        assert_eq!(code_blocks.len(), 1);
-        assert_eq!(code_blocks[0].content, "Plain preformatted text");
-        assert_eq!(code_blocks[0].language, None);
+    #[test]
+    fn test_parse_proxy_map() {
+        let map = parse_proxy_map("example.com=http://p1:8080, .foo.de=socks5://p2:1080").unwrap();
+        assert_eq!(
+            map,
+            DomainMap(vec![
+                ("example.com".to_string(), "http://p1:8080".to_string()),
+                ("foo.de".to_string(), "socks5://p2:1080".to_string())
+            ])
+        );
+        assert!(parse_proxy_map("example.com").is_err());
+        assert!(parse_proxy_map("example.com=not a url").is_err());
     }
 
     #[test]
-    fn test_extract_code_blocks_inline_code() {
-        let html = r#"
-            <html><body>
-                <p>Use the <code>print()</code> function</p>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_select_proxy_by_domain() {
+        let args = test_args(&[
+            "--proxy",
+            "http://default:3128",
+            "--proxy-map",
+            "example.com=http://p1:8080,foo.de=socks5://p2:1080",
+            "https://example.com",
+        ]);
+
+        let proxy = |url: &str| select_proxy(&args, url);
+        assert_eq!(proxy("https://example.com/page"), Some("http://p1:8080"));
+        assert_eq!(proxy("https://shop.foo.de/"), Some("socks5://p2:1080"));
+        assert_eq!(proxy("https://notexample.com/"), Some("http://default:3128"));
+        assert_eq!(proxy("https://other.org/"), Some("http://default:3128"));
+    }
 
-        assert_eq!(code_blocks.len(), 1);
-        assert_eq!(code_blocks[0].content, "print()");
-        assert_eq!(code_blocks[0].language, None);
+    #[test]
+    fn test_domain_map_prefers_most_specific_domain() {
+        let args = test_args(&[
+            "--ua-map",
+            "example.com=Chrome,docs.example.com=Googlebot",
+            "--proxy-map",
+            "example.com=http://p1:8080,shop.example.com=http://p2:8080",
+            "https://example.com",
+        ]);
+
+        assert_eq!(select_user_agent(&args, "https://docs.example.com/a"), "Googlebot");
+        assert_eq!(select_user_agent(&args, "https://api.docs.example.com/"), "Googlebot");
+        assert_eq!(select_user_agent(&args, "https://www.example.com/"), "Chrome");
+        assert_eq!(select_proxy(&args, "https://shop.example.com/"), Some("http://p2:8080"));
+        assert_eq!(select_proxy(&args, "https://example.com/"), Some("http://p1:8080"));
     }
 
     #[test]
-    fn test_extract_code_blocks_multiple() {
-        let html = r#"
-            <html><body>
-                <pre><code>code block 1</code></pre>
-                <pre><code>code block 2</code></pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_invalid_domain_map_rejected_at_parse_time() {
+        let parsed = |flag: &str, map: &str| Args::try_parse_from(["scraper", flag, map, "https://a.com"]);
+        assert!(parsed("--ua-map", "example.com").is_err());
+        assert!(parsed("--proxy-map", "example.com=not a url").is_err());
+        assert!(parsed("--ua-map", "example.com=Agent").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_map_routes_mapped_domain_through_proxy() {
+        // The mock plays the proxy: it sees absolute-form request targets
+        let proxy = spawn_mock_server(|req| {
+            MockResponse::html(&format!("<html><head><title>via proxy {}</title></head></html>", req.path))
+        })
+        .await;
 
-        assert_eq!(code_blocks.len(), 2);
-        assert_eq!(code_blocks[0].content, "code block 1");
-        assert_eq!(code_blocks[1].content, "code block 2");
+        let args = test_args(&["--proxy-map", &format!("mapped.test={}", proxy), "http://mapped.test/"]);
+        let data = scrape_website("http://mapped.test/page", &args, None, None).await.unwrap();
+
+        assert_eq!(data.title.as_deref(), Some("via proxy http://mapped.test/page"));
     }
 
+    // ========== User Agent Map Tests ==========
+
     #[test]
-    fn test_extract_code_blocks_none() {
-        let html = r#"<html><body><p>No code blocks here</p></body></html>"#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_select_user_agent_by_domain() {
+        let args = test_args(&[
+            "--user-agent",
+            "GlobalBot/1.0",
+            "--ua-map",
+            "docs.example.com=Googlebot/2.1, example.com=Chrome",
+            "https://example.com",
+        ]);
+
+        assert_eq!(select_user_agent(&args, "https://docs.example.com/a"), "Googlebot/2.1");
+        assert_eq!(select_user_agent(&args, "https://www.example.com/"), "Chrome");
+        assert_eq!(select_user_agent(&args, "https://other.org/"), "GlobalBot/1.0");
+        assert!(parse_ua_map("example.com").is_err());
+        assert!(parse_ua_map("=Agent").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ua_map_sends_mapped_user_agent() {
+        let base = spawn_mock_server(|req| {
+            let agent = req.headers.get("user-agent").cloned().unwrap_or_default();
+            MockResponse::html(&format!("<html><head><title>{}</title></head></html>", agent))
+        })
+        .await;
 
-        assert_eq!(code_blocks.len(), 0);
+        let mapped = test_args(&["--ua-map", "127.0.0.1=MappedBot/1.0", &base]);
+        let data = scrape_website(&base, &mapped, None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some("MappedBot/1.0"));
+
+        let unmapped = test_args(&["--ua-map", "example.com=MappedBot/1.0", &base]);
+        let data = scrape_website(&base, &unmapped, None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some(DEFAULT_USER_AGENT));
     }
 
+    // ========== Replay Tests ==========
+
     #[test]
-    fn test_extract_code_blocks_filters_empty() {
-        let html = r#"
-            <html><body>
-                <pre><code>Valid code</code></pre>
-                <pre><code>   </code></pre>
-                <pre><code></code></pre>
-            </body></html>
-        "#;
-        let document = Html::parse_document(html);
-        let code_blocks = extract_code_blocks(&document);
+    fn test_url_from_dump_filename() {
+        assert_eq!(url_from_dump_filename("example.com.html"), "https://example.com/");
+        assert_eq!(url_from_dump_filename("example.com_a_b.html"), "https://example.com/a/b");
+        assert_eq!(
+            url_from_dump_filename(&dom_dump_filename("https://example.com/a/b")),
+            "https://example.com/a/b"
+        );
+    }
 
-        assert_eq!(code_blocks.len(), 1);
-        assert_eq!(code_blocks[0].content, "Valid code");
+    #[test]
+    fn test_replay_archive_re_extracts_saved_pages() {
+        let dir = std::env::temp_dir().join("test_replay_archive_re_extracts_saved_pages");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(
+            dir.join("127.0.0.1_8080_first.html"),
+            r#"<html><head><title>First</title></head><body><p class="price">10</p><a href="/next">n</a></body></html>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("example.com_docs.html"),
+            r#"<html><head><title>Second</title></head><body><p class="price">20</p></body></html>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(REPLAY_MANIFEST),
+            r#"{"filename": "127.0.0.1_8080_first.html", "url": "http://127.0.0.1:8080/first"}"#,
+        )
+        .unwrap();
+
+        // New selectors apply to the archived pages without any network access
+        let dir_str = dir.to_str().unwrap();
+        let args = test_args(&["--replay", dir_str, "-s", ".price"]);
+        let results = replay_archive(dir_str, &args).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "http://127.0.0.1:8080/first");
+        assert_eq!(results[0].title.as_deref(), Some("First"));
+        assert_eq!(results[0].links[0].url, "http://127.0.0.1:8080/next");
+        assert_eq!(results[0].custom_selectors[0].matches, vec!["10"]);
+        assert_eq!(results[1].url, "https://example.com/docs");
+        assert_eq!(results[1].custom_selectors[0].matches, vec!["20"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    // ========== JSON Format Tests ==========
+    #[tokio::test]
+    async fn test_dump_dom_then_replay_round_trip() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html("<html><head><title>Archived</title></head><body><h1>Hi</h1></body></html>")
+        })
+        .await;
+        let dir = std::env::temp_dir().join("test_dump_dom_then_replay_round_trip");
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+
+        let page = format!("{}/page", base);
+        let args = test_args(&["--dump-dom", dir_str, &page]);
+        scrape_website(&page, &args, None, None).await.unwrap();
+
+        let args = test_args(&["--replay", dir_str]);
+        let results = replay_archive(dir_str, &args).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, page);
+        assert_eq!(results[0].headings, vec!["Hi"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // ========== Per-Page Manifest Tests ==========
 
     #[test]
-    fn test_format_json_single_result() {
-        let data = vec![ScrapedData {
-            url: "https://example.com".to_string(),
-            status_code: 200,
-            title: Some("Test".to_string()),
-            headings: vec!["H1".to_string()],
-            paragraphs: vec!["Para".to_string()],
-            links: vec![],
-            images: vec![],
-            tables: vec![],
-            code_blocks: vec![],
-            metadata: None,
-            custom_selectors: vec![],
-            depth: None,
-        }];
+    fn test_output_per_page_writes_manifest() {
+        let prefix = std::env::temp_dir().join("test_output_per_page_manifest");
+        let prefix = prefix.to_str().unwrap();
+        let results = vec![
+            ScrapedData {
+                url: "https://example.com/a".to_string(),
+                status_code: 200,
+                title: Some("Page A".to_string()),
+                ..Default::default()
+            },
+            ScrapedData {
+                url: "https://example.com/b".to_string(),
+                status_code: 200,
+                ..Default::default()
+            },
+        ];
 
-        let result = format_json(&data).unwrap();
-        assert!(result.contains("https://example.com"));
-        assert!(result.contains("Test"));
-        assert!(result.contains("H1"));
+        let args = test_args(&["--output-per-page", "-o", prefix, "https://example.com"]);
+        output_results(&results, &args).unwrap();
+
+        let manifest_path = format!("{}_manifest.json", prefix);
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(
+            manifest,
+            serde_json::json!([
+                {"filename": format!("{}_001.json", prefix), "url": "https://example.com/a",
+                 "status_code": 200, "title": "Page A"},
+                {"filename": format!("{}_002.json", prefix), "url": "https://example.com/b",
+                 "status_code": 200, "title": null},
+            ])
+        );
+
+        for file in [manifest_path, format!("{}_001.json", prefix), format!("{}_002.json", prefix)] {
+            assert!(std::path::Path::new(&file).exists());
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    // ========== Root Selector Tests ==========
+
+    #[tokio::test]
+    async fn test_root_selector_excludes_outside_content() {
+        let base = spawn_mock_server(|_| {
+            MockResponse::html(
+                r#"<html><head><title>Article</title></head><body>
+                    <nav><h2>Menu</h2><a href="/home">Home</a></nav>
+                    <main class="content">
+                        <h1>Story</h1><p>Body text</p><a href="/more">More</a><img src="/photo.jpg" alt="Photo">
+                    </main>
+                    <footer><p>Copyright</p></footer>
+                </body></html>"#,
+            )
+        })
+        .await;
+
+        let args = test_args(&["--root-selector", "main.content", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+
+        assert_eq!(data.title.as_deref(), Some("Article"));
+        assert_eq!(data.headings, vec!["Story"]);
+        assert_eq!(data.paragraphs, vec!["Body text"]);
+        let links: Vec<&str> = data.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(links, vec![format!("{}/more", base)]);
+        assert_eq!(data.images.len(), 1);
+
+        // A root that matches nothing falls back to the whole page
+        let args = test_args(&["--root-selector", "article", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.headings, vec!["Story", "Menu"]);
     }
 
+    // ========== Sitemap Format Tests ==========
+
     #[test]
-    fn test_format_json_multiple_results() {
-        let data = vec![
+    fn test_format_sitemap() {
+        let results = vec![
             ScrapedData {
-                url: "https://example.com/1".to_string(),
+                url: "https://example.com/".to_string(),
                 status_code: 200,
-                title: Some("Page 1".to_string()),
-                headings: vec![],
-                paragraphs: vec![],
-                links: vec![],
-                images: vec![],
-                tables: vec![],
-                code_blocks: vec![],
-                metadata: None,
-                custom_selectors: vec![],
-                depth: None,
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+                ..Default::default()
             },
             ScrapedData {
-                url: "https://example.com/2".to_string(),
+                url: "https://example.com/search?q=a&page=2".to_string(),
                 status_code: 200,
-                title: Some("Page 2".to_string()),
-                headings: vec![],
-                paragraphs: vec![],
-                links: vec![],
-                images: vec![],
-                tables: vec![],
-                code_blocks: vec![],
-                metadata: None,
-                custom_selectors: vec![],
-                depth: None,
+                ..Default::default()
+            },
+            ScrapedData {
+                url: "https://example.com/missing".to_string(),
+                status_code: 404,
+                error: Some("HTTP 404".to_string()),
+                ..Default::default()
             },
         ];
 
-        let result = format_json(&data).unwrap();
-        assert!(result.contains("Page 1"));
-        assert!(result.contains("Page 2"));
+        let sitemap = format_sitemap(&results);
+        assert!(sitemap.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset "));
+        assert!(sitemap.trim_end().ends_with("</urlset>"));
+        assert_eq!(sitemap.matches("<url>").count(), 2);
+        assert!(sitemap.contains("<loc>https://example.com/</loc>\n    <lastmod>2015-10-21</lastmod>"));
+        assert!(sitemap.contains("<loc>https://example.com/search?q=a&amp;page=2</loc>"));
+        assert!(!sitemap.contains("missing"));
     }
 
-    // ========== CSV Format Tests ==========
+    // ========== Mermaid Format Tests ==========
 
     #[test]
-    fn test_format_csv_headers() {
-        let data = vec![ScrapedData {
-            url: "https://example.com".to_string(),
-            status_code: 200,
-            title: Some("Test".to_string()),
-            headings: vec![],
-            paragraphs: vec![],
-            links: vec![],
-            images: vec![],
-            tables: vec![],
-            code_blocks: vec![],
-            metadata: None,
-            custom_selectors: vec![],
-            depth: None,
-        }];
+    fn test_format_ndjson_one_compact_object_per_line() {
+        let results = vec![
+            crawled_page("https://a.com/", "Home\nPage", 0, &["https://a.com/b"]),
+            crawled_page("https://a.com/b", "B", 1, &[]),
+        ];
+        let args = test_args(&["--format", "ndjson", "--fields", "url,title", "https://a.com"]);
 
-        let result = format_csv(&data).unwrap();
-        let lines: Vec<&str> = result.lines().collect();
+        let output = format_results(&results, &args, Palette::new("never", false)).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
 
-        assert_eq!(lines[0], "url,status_code,title,headings_count,paragraphs_count,links_count,images_count,tables_count,code_blocks_count,depth");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], r#"{"url":"https://a.com/","title":"Home\nPage"}"#);
+        assert_eq!(lines[1], r#"{"url":"https://a.com/b","title":"B"}"#);
     }
 
-    #[test]
-    fn test_format_csv_data_row() {
-        let data = vec![ScrapedData {
-            url: "https://example.com".to_string(),
-            status_code: 200,
-            title: Some("Test".to_string()),
-            headings: vec!["H1".to_string()],
-            paragraphs: vec!["P1".to_string(), "P2".to_string()],
-            links: vec![],
-            images: vec![],
-            tables: vec![],
-            code_blocks: vec![],
-            metadata: None,
-            custom_selectors: vec![],
-            depth: Some(1),
-        }];
+    #[tokio::test]
+    async fn test_ndjson_crawl_streams_pages_as_scraped() {
+        let base = spawn_mock_server(|req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(r#"<title>Home</title><a href="/a">a</a><a href="/b">b</a>"#),
+            path => MockResponse::html(&format!("<title>{}</title>", path)),
+        })
+        .await;
+        let path = std::env::temp_dir().join("test_ndjson_crawl_streams_pages_as_scraped.ndjson");
+        let path_str = path.to_str().unwrap();
+        let args = test_args(&[&base, "--crawl", "--format", "ndjson", "-o", path_str, "--tag", "job=1"]);
+        assert!(streams_ndjson(&args));
+
+        // Each page reaches the callback before the crawl moves on
+        let mut seen = Vec::new();
+        let results = crawl_website_with(&args, &mut |data| {
+            seen.push(data.url.clone());
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(seen, results.iter().map(|d| d.url.clone()).collect::<Vec<_>>());
 
-        let result = format_csv(&data).unwrap();
-        let lines: Vec<&str> = result.lines().collect();
+        let user_data = parse_user_data(&args.tags, None).unwrap();
+        stream_crawl_ndjson(&args, user_data.as_ref()).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let pages: Vec<serde_json::Value> =
+            written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let _ = std::fs::remove_file(&path);
 
-        assert_eq!(lines[1], "https://example.com,200,Test,1,2,0,0,0,0,1");
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0]["title"], "Home");
+        assert!(pages.iter().all(|page| page["user_data"]["job"] == "1"));
     }
 
-    // ========== Error Handling Tests ==========
+    #[test]
+    fn test_format_link_lines_carry_source_url() {
+        let results = vec![
+            crawled_page("https://a.com/", "Home", 0, &["https://a.com/b", "https://z.com/"]),
+            crawled_page("https://a.com/b", "B", 1, &["https://a.com/"]),
+        ];
+
+        let output = format_link_lines(&results).unwrap();
+        let lines: Vec<serde_json::Value> =
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["source_url"], "https://a.com/");
+        assert_eq!(lines[1]["source_url"], "https://a.com/");
+        assert_eq!(lines[1]["url"], "https://z.com/");
+        assert_eq!(lines[2]["source_url"], "https://a.com/b");
+        assert_eq!(lines[2]["depth"], 1);
+        assert!(lines.iter().all(|line| line.as_object().unwrap().len() == 4));
+    }
 
     #[test]
-    fn test_classify_http_status_success() {
-        let result = classify_http_status(200, "https://example.com");
-        assert!(result.is_ok());
+    fn test_format_urls_sorted_and_deduped() {
+        let results = vec![
+            crawled_page("https://a.com/b", "B", 1, &["https://a.com/", "https://z.com/"]),
+            crawled_page("https://a.com/", "Home", 0, &["https://a.com/b"]),
+            crawled_page("https://a.com/b", "B again", 1, &[]),
+        ];
+
+        assert_eq!(format_urls(&results, false), "https://a.com/\nhttps://a.com/b\n");
+        assert_eq!(
+            format_urls(&results, true),
+            "https://a.com/\nhttps://a.com/b\nhttps://z.com/\n"
+        );
     }
 
     #[test]
-    fn test_classify_http_status_404() {
-        let result = classify_http_status(404, "https://example.com/missing");
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Not Found"));
-        assert!(err.to_string().contains("/missing"));
+    fn test_format_mermaid_nodes_and_edges() {
+        let results = vec![
+            crawled_page("https://example.com/", "Home", 0, &["https://example.com/about", "https://other.com/"]),
+            crawled_page("https://example.com/about", "About \"us\"", 1, &["https://example.com/"]),
+        ];
+
+        let mermaid = format_mermaid(&results);
+        assert!(mermaid.starts_with("graph TD\n"));
+        assert!(mermaid.contains("    n0[\"Home\"]\n"));
+        assert!(mermaid.contains("    n1[\"About #quot;us#quot;\"]\n"));
+        assert!(mermaid.contains("    n0 --> n1\n"));
+        assert!(mermaid.contains("    n1 --> n0\n"));
+        // Links to pages outside the crawl are not drawn
+        assert_eq!(mermaid.matches("-->").count(), 2);
     }
 
     #[test]
-    fn test_classify_http_status_403() {
-        let result = classify_http_status(403, "https://example.com");
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Forbidden"));
-        assert!(err.to_string().contains("bot protection"));
+    fn test_http_date_to_w3c() {
+        assert_eq!(http_date_to_w3c("Wed, 21 Oct 2015 07:28:00 GMT").as_deref(), Some("2015-10-21"));
+        assert_eq!(http_date_to_w3c("garbage"), None);
     }
 
+    // ========== Audit Tests ==========
+
     #[test]
-    fn test_classify_http_status_429() {
-        let result = classify_http_status(429, "https://example.com");
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Rate limited"));
-        assert!(err.to_string().contains("Too many requests"));
+    fn test_audit_page_missing_description_and_alt() {
+        let html = r#"<html><head><title>Shop</title></head><body>
+            <img src="/a.jpg" alt="A"><img src="/b.jpg"><img src="/c.jpg" alt="">
+        </body></html>"#;
+        let url = Url::parse("https://example.com/").unwrap();
+        let warnings = audit_page(&Html::parse_document(html), Some("Shop"), &url);
+
+        assert_eq!(warnings, vec!["Missing meta description", "1 image(s) without alt text"]);
     }
 
     #[test]
-    fn test_classify_http_status_500() {
-        let result = classify_http_status(500, "https://example.com");
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Internal Server Error"));
+    fn test_audit_page_canonical_and_refresh() {
+        let html = r#"<html><head>
+            <meta name="description" content="Fine">
+            <meta http-equiv="refresh" content="0; url=/new">
+            <link rel="canonical" href="https://mirror.example.org/page">
+            <link rel="canonical" href="/page">
+        </head></html>"#;
+        let url = Url::parse("https://example.com/page").unwrap();
+        let warnings = audit_page(&Html::parse_document(html), None, &url);
+
+        assert_eq!(
+            warnings,
+            vec![
+                "Missing <title>",
+                "Meta refresh redirect: 0; url=/new",
+                "Multiple canonical tags (2)",
+                "Canonical points off-domain: https://mirror.example.org/page",
+            ]
+        );
     }
 
     #[test]
-    fn test_classify_http_status_503() {
-        let result = classify_http_status(503, "https://example.com");
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Service Unavailable"));
+    fn test_audit_across_pages_flags_duplicate_titles() {
+        let page = |url: &str, title: &str| ScrapedData {
+            url: url.to_string(),
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let mut results = vec![
+            page("https://example.com/a", "Products"),
+            page("https://example.com/b", "About"),
+            page("https://example.com/c", "  products "),
+        ];
+        audit_across_pages(&mut results);
+
+        assert_eq!(results[0].audit, vec!["Duplicate title shared with: https://example.com/c"]);
+        assert!(results[1].audit.is_empty());
+        assert_eq!(results[2].audit, vec!["Duplicate title shared with: https://example.com/a"]);
     }
 
+    // ========== Duplicate Title Report Tests ==========
+
     #[test]
-    fn test_detect_anti_bot_cloudflare() {
-        let html = r#"<html><body><div class="cf-browser-verification">Checking your browser</div></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("Cloudflare"));
+    fn test_duplicate_title_report_lists_shared_title_cluster() {
+        let page = |url: &str, title: &str| ScrapedData {
+            url: url.to_string(),
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let results = vec![
+            page("https://example.com/a", "Widgets | Shop"),
+            page("https://example.com/b", "About us"),
+            page("https://example.com/c", "  widgets |   shop "),
+        ];
+
+        let report = duplicate_title_report(&results);
+        assert!(report.starts_with("Duplicate titles (1 cluster(s)):"));
+        assert!(report.contains("\"Widgets | Shop\" (2 pages)"));
+        assert!(report.contains("  - https://example.com/a\n  - https://example.com/c\n"));
+        assert!(!report.contains("https://example.com/b"));
     }
 
     #[test]
-    fn test_detect_anti_bot_cloudflare_challenge() {
-        let html = r#"<html><body><div>Just a moment...</div><script>challenge-platform</script></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("JavaScript challenge"));
+    fn test_duplicate_title_report_without_duplicates() {
+        let results = vec![ScrapedData {
+            url: "https://example.com/".to_string(),
+            title: Some("Home".to_string()),
+            ..Default::default()
+        }];
+        assert_eq!(duplicate_title_report(&results), "No duplicate titles found\n");
     }
 
+    // ========== Upgrade Insecure Tests ==========
+
     #[test]
-    fn test_detect_anti_bot_recaptcha() {
-        let html = r#"<html><body><div class="g-recaptcha"></div></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("reCAPTCHA"));
+    fn test_upgrade_insecure_rewrites_known_https_host() {
+        let https_hosts: HashSet<String> = ["example.com".to_string()].into();
+        assert_eq!(
+            upgrade_insecure("http://example.com/page?x=1", &https_hosts),
+            Some("https://example.com/page?x=1".to_string())
+        );
+        assert_eq!(
+            upgrade_insecure("http://Example.com:80/", &https_hosts),
+            Some("https://example.com/".to_string())
+        );
+        assert_eq!(upgrade_insecure("http://other.com/", &https_hosts), None);
+        assert_eq!(upgrade_insecure("https://example.com/", &https_hosts), None);
     }
 
     #[test]
-    fn test_detect_anti_bot_hcaptcha() {
-        let html = r#"<html><body><div class="h-captcha"></div></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("hCaptcha"));
+    fn test_upgrade_crawl_candidate_only_with_flag() {
+        let https_hosts: HashSet<String> = ["example.com".to_string()].into();
+        let mut upgrades = HashMap::new();
+
+        let args = test_args(&["https://example.com"]);
+        let candidate = upgrade_crawl_candidate(
+            "http://example.com/a".to_string(),
+            &args,
+            &https_hosts,
+            &mut upgrades,
+        );
+        assert_eq!(candidate, "http://example.com/a");
+        assert!(upgrades.is_empty());
+
+        let args = test_args(&["https://example.com", "--crawl", "--upgrade-insecure"]);
+        let candidate = upgrade_crawl_candidate(
+            "http://example.com/a".to_string(),
+            &args,
+            &https_hosts,
+            &mut upgrades,
+        );
+        assert_eq!(candidate, "https://example.com/a");
+        assert_eq!(upgrades["https://example.com/a"], "http://example.com/a");
     }
 
+    // ========== Collapse Canonical Tests ==========
+
     #[test]
-    fn test_detect_anti_bot_perimeterx() {
-        let html = r#"<html><body><div class="px-captcha"></div></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("PerimeterX"));
+    fn test_collapse_by_canonical_merges_shared_canonical() {
+        let page = |url: &str, canonical: Option<&str>| ScrapedData {
+            url: url.to_string(),
+            status_code: 200,
+            canonical: canonical.map(dedup_key),
+            ..Default::default()
+        };
+        let results = vec![
+            page("https://example.com/shoes?ref=nav", Some("https://example.com/shoes")),
+            page("https://example.com/about", None),
+            page("https://example.com/shoes", Some("https://example.com/shoes")),
+        ];
+
+        let collapsed = collapse_by_canonical(results);
+        let urls: Vec<&str> = collapsed.iter().map(|d| d.url.as_str()).collect();
+
+        assert_eq!(urls, vec!["https://example.com/shoes", "https://example.com/about"]);
+        assert_eq!(
+            collapsed[0].source_urls,
+            vec!["https://example.com/shoes?ref=nav", "https://example.com/shoes"]
+        );
+        assert!(collapsed[1].source_urls.is_empty());
     }
 
-    #[test]
-    fn test_detect_anti_bot_datadome() {
-        let html = r#"<html><body><script src="datadome.js"></script></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("DataDome"));
+    #[tokio::test]
+    async fn test_max_per_depth_caps_pages_fetched_per_level() {
+        let base = spawn_mock_server(|req: &MockRequest| {
+            let body = match req.path.as_str() {
+                "/" => (1..=6).map(|i| format!(r#"<a href="/p{}">p{}</a>"#, i, i)).collect(),
+                _ => r#"<a href="/deeper">deeper</a>"#.to_string(),
+            };
+            MockResponse::html(&body)
+        })
+        .await;
+        let args = test_args(&[&base, "--crawl", "--max-pages", "50", "--max-per-depth", "2"]);
+
+        let results = crawl_website(&args).await.unwrap();
+        let count_at = |depth| results.iter().filter(|d| d.depth == Some(depth)).count();
+
+        assert_eq!(count_at(0), 1);
+        assert_eq!(count_at(1), 2);
+        // Links from the capped level are still followed
+        assert_eq!(count_at(2), 1);
     }
 
-    #[test]
-    fn test_detect_anti_bot_title_access_denied() {
-        let html = r#"<html><body>Content</body></html>"#;
-        let result = detect_anti_bot_features(html, Some("Access Denied - Forbidden"));
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("Access restriction detected"));
+    #[tokio::test]
+    async fn test_multi_seed_crawls_every_seed_under_one_budget() {
+        let outside = spawn_mock_server(|_| MockResponse::html("<p>outside</p>")).await;
+        let link_to_outside = format!(r#"<a href="/a1">a1</a><a href="{}/x">x</a>"#, outside);
+        let seed_a = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(&link_to_outside),
+            _ => MockResponse::html("<p>a</p>"),
+        })
+        .await;
+        let seed_b = spawn_mock_server(|req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(r#"<a href="/b1">b1</a><a href="/b2">b2</a>"#),
+            _ => MockResponse::html("<p>b</p>"),
+        })
+        .await;
+
+        let args = test_args(&[&seed_a, &seed_b, "--crawl", "--multi-seed", "--max-pages", "10"]);
+        let urls: HashSet<String> = crawl_website(&args).await.unwrap().into_iter().map(|d| d.url).collect();
+        let expected: HashSet<String> = [
+            seed_a.clone(),
+            format!("{}/a1", seed_a),
+            seed_b.clone(),
+            format!("{}/b1", seed_b),
+            format!("{}/b2", seed_b),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(urls, expected);
+
+        // Both seeds draw from the same page budget
+        let args = test_args(&[&seed_a, &seed_b, "--crawl", "--multi-seed", "--max-pages", "3"]);
+        let results = crawl_website(&args).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|d| d.url.starts_with(&seed_b)));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_by_declared_canonical_stores_one_page() {
+        let base = spawn_mock_server(|req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(r#"<a href="/x?utm=a">a</a><a href="/x?utm=b">b</a>"#),
+            _ => MockResponse::html(&format!(
+                r#"<html><head><link rel="canonical" href="/x"><title>{}</title></head></html>"#,
+                req.path
+            )),
+        })
+        .await;
+
+        let args = test_args(&[&base, "--crawl", "--dedup-by-declared-canonical"]);
+        let results = crawl_website(&args).await.unwrap();
+        let urls: Vec<&str> = results.iter().map(|d| d.url.as_str()).collect();
+        assert_eq!(urls, vec![base.clone(), format!("{}/x?utm=a", base)]);
+
+        let tracking = [format!("{}/x?utm=a", base), format!("{}/x?utm=b", base), format!("{}/x", base)];
+        let mut argv: Vec<&str> = tracking.iter().map(String::as_str).collect();
+        argv.push("--dedup-by-declared-canonical");
+        let results = scrape_multiple(&test_args(&argv)).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, tracking[0]);
     }
 
-    #[test]
-    fn test_detect_anti_bot_title_blocked() {
-        let html = r#"<html><body>Content</body></html>"#;
-        let result = detect_anti_bot_features(html, Some("You have been blocked"));
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("Access restriction"));
+    #[tokio::test]
+    async fn test_crawl_max_depth_zero_fetches_only_start_url() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let links: String = (1..=20).map(|i| format!(r#"<a href="/p{}">p{}</a>"#, i, i)).collect();
+            MockResponse::html(&format!(r#"<link rel="next" href="/page/2">{}"#, links))
+        })
+        .await;
+        let args = test_args(&[&base, "--crawl", "--max-depth", "0", "--follow-pagination"]);
+
+        let results = crawl_website(&args).await.unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].depth, Some(0));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
     }
 
-    #[test]
-    fn test_detect_anti_bot_none() {
-        let html = r#"<html><body><p>Normal content here</p></body></html>"#;
-        let result = detect_anti_bot_features(html, Some("Normal Page"));
-        assert!(result.is_none());
+    #[tokio::test]
+    async fn test_max_total_bytes_stops_crawl_when_budget_used() {
+        // A chain of pages, each body padded to exactly 100 bytes
+        let base = spawn_mock_server(|req: &MockRequest| {
+            let next: u32 = req.path.trim_start_matches("/p").parse().unwrap_or(0) + 1;
+            MockResponse::html(&format!("{:<100}", format!(r#"<a href="/p{}">next</a>"#, next)))
+        })
+        .await;
+        let args = test_args(&[&base, "--crawl", "--max-depth", "10", "--max-total-bytes", "150"]);
+
+        let results = crawl_website(&args).await.unwrap();
+
+        assert!(results.iter().all(|d| d.body_bytes == 100));
+        assert_eq!(results.len(), 2);
     }
 
     #[test]
-    fn test_detect_anti_bot_cloudflare_ray_id() {
-        let html = r#"<html><body><div>Cloudflare Ray ID: abc123</div></body></html>"#;
-        let result = detect_anti_bot_features(html, None);
-        assert!(result.is_some());
-        assert!(result.unwrap().contains("Cloudflare error page"));
-    }
+    fn test_visit_key_trailing_slash() {
+        assert_eq!(visit_key("https://a.com/about/", true), "https://a.com/about");
+        assert_eq!(visit_key("https://a.com/about/?x=1", true), "https://a.com/about?x=1");
+        assert_eq!(visit_key("https://a.com/", true), "https://a.com/");
+        assert_eq!(visit_key("https://a.com/about/", false), "https://a.com/about/");
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_equal_visits_once() {
+        let base = spawn_mock_server(|req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(r#"<a href="/about">About</a><a href="/about/">About us</a>"#),
+            _ => MockResponse::html("<p>about</p>"),
+        })
+        .await;
 
-    // ========== URL File Reading Tests ==========
+        let args = test_args(&[&base, "--crawl"]);
+        assert_eq!(crawl_website(&args).await.unwrap().len(), 3);
+
+        let args = test_args(&[&base, "--crawl", "--treat-trailing-slash-equal"]);
+        let results = crawl_website(&args).await.unwrap();
+        let urls: Vec<&str> = results.iter().map(|d| d.url.as_str()).collect();
+        assert_eq!(urls, vec![base.clone(), format!("{}/about", base)]);
+    }
 
     #[test]
-    fn test_read_urls_from_file_valid() {
+    fn test_parse_sitemap_locs_pages_and_index() {
+        let base = Url::parse("https://example.com/sitemap.xml").unwrap();
+        let xml = r#"<?xml version="1.0"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://example.com/a</loc><lastmod>2024-01-01</lastmod></url>
+                <url><loc> /b?x=1&amp;y=2 </loc></url>
+            </urlset>"#;
+        let (pages, sitemaps) = parse_sitemap_locs(xml, &base);
+        assert_eq!(pages, vec!["https://example.com/a", "https://example.com/b?x=1&y=2"]);
+        assert!(sitemaps.is_empty());
+
+        let index = r#"<sitemapindex><sitemap><loc>https://example.com/posts.xml</loc></sitemap></sitemapindex>"#;
+        let (pages, sitemaps) = parse_sitemap_locs(index, &base);
+        assert!(pages.is_empty());
+        assert_eq!(sitemaps, vec!["https://example.com/posts.xml"]);
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_only_ignores_in_page_links() {
+        let base = spawn_mock_server(|req: &MockRequest| match req.path.as_str() {
+            "/sitemap.xml" => MockResponse::html(
+                "<urlset><url><loc>/a</loc></url><url><loc>/b</loc></url></urlset>",
+            ),
+            _ => MockResponse::html(r#"<a href="/c">c</a><a href="/">home</a>"#),
+        })
+        .await;
+        let args = test_args(&[&base, "--crawl", "--sitemap-only"]);
+
+        let results = crawl_website(&args).await.unwrap();
+        let urls: Vec<&str> = results.iter().map(|d| d.url.as_str()).collect();
+
+        assert_eq!(urls, vec![format!("{}/a", base), format!("{}/b", base)]);
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_only_reads_gzip_sitemap() {
         use std::io::Write;
 
-        // Create a temporary file with valid URLs
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_valid.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "https://example.com").unwrap();
-        writeln!(file, "https://google.com").unwrap();
-        writeln!(file, "https://rust-lang.org").unwrap();
-        drop(file);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(b"<urlset><url><loc>/a</loc></url><url><loc>/b</loc></url></urlset>")
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+        let base = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/sitemap.xml.gz" => MockResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "application/gzip".to_string())],
+                body: compressed.clone(),
+            },
+            _ => MockResponse::html("<p>page</p>"),
+        })
+        .await;
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_ok());
-        let urls = result.unwrap();
-        assert_eq!(urls.len(), 3);
-        assert_eq!(urls[0], "https://example.com");
-        assert_eq!(urls[1], "https://google.com");
-        assert_eq!(urls[2], "https://rust-lang.org");
+        let args = test_args(&[&format!("{}/sitemap.xml.gz", base), "--crawl", "--sitemap-only"]);
+        let results = crawl_website(&args).await.unwrap();
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+        let urls: Vec<&str> = results.iter().map(|d| d.url.as_str()).collect();
+        assert_eq!(urls, vec![format!("{}/a", base), format!("{}/b", base)]);
     }
 
     #[test]
-    fn test_read_urls_from_file_with_comments_and_empty_lines() {
-        use std::io::Write;
+    fn test_decode_sitemap_body_passes_plain_xml_through() {
+        let url = Url::parse("https://example.com/sitemap.xml.gz").unwrap();
+        assert_eq!(decode_sitemap_body(b"<urlset/>", &url).unwrap(), "<urlset/>");
+        assert!(decode_sitemap_body(&[0x1f, 0x8b, 0x00], &url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_fetches_are_paced_and_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let index_hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = index_hits.clone();
+        let base = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/sitemap.xml" => match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::status(503),
+                _ => MockResponse::html("<sitemapindex><sitemap><loc>/posts.xml</loc></sitemap></sitemapindex>"),
+            },
+            "/posts.xml" => MockResponse::html("<urlset><url><loc>/a</loc></url></urlset>"),
+            _ => MockResponse::html("<p>page</p>"),
+        })
+        .await;
+
+        let args = test_args(&[
+            &base,
+            "--crawl",
+            "--sitemap-only",
+            "--retries",
+            "1",
+            "--retry-backoff",
+            "1",
+            "--min-host-interval",
+            "150",
+        ]);
+        let started = std::time::Instant::now();
+        let results = crawl_website(&args).await.unwrap();
 
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_comments.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "# This is a comment").unwrap();
-        writeln!(file, "https://example.com").unwrap();
-        writeln!(file, "").unwrap();
-        writeln!(file, "  ").unwrap();
-        writeln!(file, "# Another comment").unwrap();
-        writeln!(file, "https://google.com").unwrap();
-        writeln!(file, "").unwrap();
-        drop(file);
+        assert_eq!(results.len(), 1);
+        assert_eq!(index_hits.load(Ordering::SeqCst), 2);
+        // Index, its retry, the nested sitemap and the page all pass the host gate
+        assert!(started.elapsed() >= Duration::from_millis(450), "{:?}", started.elapsed());
+    }
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_ok());
-        let urls = result.unwrap();
-        assert_eq!(urls.len(), 2);
-        assert_eq!(urls[0], "https://example.com");
-        assert_eq!(urls[1], "https://google.com");
+    #[tokio::test]
+    async fn test_collapse_canonical_end_to_end() {
+        let base = spawn_mock_server(|req: &MockRequest| {
+            let body = match req.path.as_str() {
+                "/" => r#"<a href="/print">print</a><a href="/article">article</a>"#,
+                _ => r#"<html><head><link rel="canonical" href="/article"></head><body>x</body></html>"#,
+            };
+            MockResponse::html(body)
+        })
+        .await;
+        let args = test_args(&[&base, "--crawl", "--collapse-canonical"]);
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+        let results = collapse_by_canonical(crawl_website(&args).await.unwrap());
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].url, format!("{}/article", base));
+        assert_eq!(
+            results[1].source_urls,
+            vec![format!("{}/print", base), format!("{}/article", base)]
+        );
     }
 
-    #[test]
-    fn test_read_urls_from_file_mixed_valid_invalid() {
-        use std::io::Write;
+    // ========== Prefer AMP Tests ==========
 
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_mixed.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "https://example.com").unwrap();
-        writeln!(file, "not-a-valid-url").unwrap();
-        writeln!(file, "https://google.com").unwrap();
-        writeln!(file, "also invalid").unwrap();
-        writeln!(file, "https://rust-lang.org").unwrap();
-        drop(file);
+    #[tokio::test]
+    async fn test_prefer_amp_extracts_from_amp_version() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_ok());
-        let urls = result.unwrap();
-        // Should only get valid URLs, invalid ones are logged as warnings
-        assert_eq!(urls.len(), 3);
-        assert_eq!(urls[0], "https://example.com");
-        assert_eq!(urls[1], "https://google.com");
-        assert_eq!(urls[2], "https://rust-lang.org");
+        let amp_hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = amp_hits.clone();
+        let base = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/article/amp" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                MockResponse::html("<html><head><title>Story (AMP)</title></head><body><p>Lean</p></body></html>")
+            }
+            _ => MockResponse::html(
+                r#"<html><head><title>Story</title><link rel="amphtml" href="/article/amp"></head>
+                <body><p>Heavy</p></body></html>"#,
+            ),
+        })
+        .await;
+        let url = format!("{}/article", base);
+
+        let data = scrape_website(&url, &test_args(&[&url]), None, None).await.unwrap();
+        assert_eq!(data.title.as_deref(), Some("Story"));
+        assert_eq!(data.amp_url, None);
+        assert_eq!(amp_hits.load(Ordering::SeqCst), 0);
+
+        let data = scrape_website(&url, &test_args(&["--prefer-amp", &url]), None, None).await.unwrap();
+        assert_eq!(amp_hits.load(Ordering::SeqCst), 1);
+        assert_eq!(data.url, url);
+        assert_eq!(data.amp_url, Some(format!("{}/article/amp", base)));
+        assert_eq!(data.title.as_deref(), Some("Story (AMP)"));
+        assert_eq!(data.paragraphs, vec!["Lean"]);
+    }
+
+    #[tokio::test]
+    async fn test_prefer_amp_fetch_is_paced_and_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let amp_hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = amp_hits.clone();
+        let base = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/article/amp" => match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::status(503),
+                _ => MockResponse::html("<html><head><title>Story (AMP)</title></head></html>"),
+            },
+            _ => MockResponse::html(r#"<html><head><link rel="amphtml" href="/article/amp"></head></html>"#),
+        })
+        .await;
+        let url = format!("{}/article", base);
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+        let args = test_args(&[
+            "--prefer-amp",
+            "--retries",
+            "1",
+            "--retry-backoff",
+            "1",
+            "--min-host-interval",
+            "150",
+            &url,
+        ]);
+        let started = std::time::Instant::now();
+        let results = scrape_multiple(&args).await.unwrap();
+
+        assert_eq!(results[0].title.as_deref(), Some("Story (AMP)"));
+        assert_eq!(amp_hits.load(Ordering::SeqCst), 2);
+        // Page, AMP fetch and AMP retry each wait their turn at the host gate
+        assert!(started.elapsed() >= Duration::from_millis(300), "{:?}", started.elapsed());
     }
 
-    #[test]
-    fn test_read_urls_from_file_not_found() {
-        let result = read_urls_from_file("/nonexistent/path/to/urls.txt");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Failed to open URL file"));
+    // ========== Capture IP Tests ==========
+
+    #[tokio::test]
+    async fn test_capture_ip_records_peer_address() {
+        let base = spawn_mock_server(|_| MockResponse::html("<html><body>ok</body></html>")).await;
+
+        let data = scrape_website(&base, &test_args(&[&base]), None, None).await.unwrap();
+        assert_eq!(data.resolved_ip, None);
+
+        let data = scrape_website(&base, &test_args(&["--capture-ip", &base]), None, None)
+            .await
+            .unwrap();
+        assert_eq!(data.resolved_ip.as_deref(), Some("127.0.0.1"));
     }
 
-    #[test]
-    fn test_read_urls_from_file_no_valid_urls() {
-        use std::io::Write;
+    #[tokio::test]
+    async fn test_capture_size_records_body_length() {
+        // Multi-byte characters: the size is in bytes, not characters
+        let body = "<html><body><p>héllo wörld</p></body></html>";
+        let base = spawn_mock_server(move |_| MockResponse::html(body)).await;
 
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_empty.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "# Only comments").unwrap();
-        writeln!(file, "").unwrap();
-        writeln!(file, "# More comments").unwrap();
-        drop(file);
+        let data = scrape_website(&base, &test_args(&[&base]), None, None).await.unwrap();
+        assert_eq!(data.content_bytes, None);
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("No valid URLs found"));
+        let args = test_args(&["--capture-size", &base]);
+        let data = scrape_website(&base, &args, None, None).await.unwrap();
+        assert_eq!(data.content_bytes, Some(body.len()));
+        assert!(format_csv(&[data], None).unwrap().lines().nth(1).unwrap().ends_with(&format!(",{}", body.len())));
+    }
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+    // ========== Named Selector Tests ==========
+
+    #[test]
+    fn test_named_selectors_keyed_by_name() {
+        let html = r#"<html><body>
+            <h2 class="name">Lamp</h2><span class="price">$20</span>
+            <h2 class="name">Desk</h2><span class="price">$90</span>
+        </body></html>"#;
+        let document = Html::parse_document(html);
+        let specs = vec!["price=.price".to_string(), "title = h2.name".to_string()];
+
+        let named = process_named_selectors(&document, &specs, &test_args(&["https://a.com"])).unwrap();
+        assert_eq!(
+            serde_json::to_value(&named).unwrap(),
+            serde_json::json!({"price": ["$20", "$90"], "title": ["Lamp", "Desk"]})
+        );
+
+        let counted =
+            process_named_selectors(&document, &specs, &test_args(&["--count-only", "https://a.com"]))
+                .unwrap();
+        assert_eq!(counted["price"], 2);
     }
 
     #[test]
-    fn test_read_urls_from_file_only_invalid_urls() {
-        use std::io::Write;
+    fn test_parse_named_selectors_rejects_bad_specs() {
+        assert!(parse_named_selectors(&["price".to_string()]).is_err());
+        assert!(parse_named_selectors(&["=.price".to_string()]).is_err());
+        assert!(parse_named_selectors(&["a=.x".to_string(), "a=.y".to_string()]).is_err());
+        assert!(parse_named_selectors(&["a=[[".to_string()]).is_err());
+    }
+
+    // ========== Host Scope Tests ==========
+
+    #[tokio::test]
+    async fn test_crawl_ip_literal_start_scopes_to_host_and_port() {
+        let other = spawn_mock_server(|_| MockResponse::html("<p>other port</p>")).await;
+        let other_link = format!(r#"<a href="{}/elsewhere">elsewhere</a>"#, other);
+        let base = spawn_mock_server(move |req: &MockRequest| match req.path.as_str() {
+            "/" => MockResponse::html(&format!(r#"<a href="/inside">inside</a>{}"#, other_link)),
+            _ => MockResponse::html("<p>inside</p>"),
+        })
+        .await;
+        assert!(base.starts_with("http://127.0.0.1:"));
 
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_invalid.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "not-a-url").unwrap();
-        writeln!(file, "also-not-a-url").unwrap();
-        writeln!(file, "still-not-valid").unwrap();
-        drop(file);
+        let results = crawl_website(&test_args(&[&base, "--crawl"])).await.unwrap();
+        let urls: Vec<&str> = results.iter().map(|d| d.url.as_str()).collect();
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("No valid URLs found"));
+        assert_eq!(urls, vec![base.clone(), format!("{}/inside", base)]);
+    }
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+    // ========== Selector Diff Tests ==========
+
+    fn page_with_selectors(url: &str, values: &[(&str, serde_json::Value)]) -> ScrapedData {
+        let mut data = crawled_page(url, "Page", 0, &[]);
+        data.named_selectors = values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        data
     }
 
     #[test]
-    fn test_read_urls_from_file_whitespace_handling() {
-        use std::io::Write;
+    fn test_diff_selector_snapshot_detects_changed_value() {
+        let url = "https://shop.example/item";
+        let mut snapshot = SelectorSnapshot::from([(
+            url.to_string(),
+            BTreeMap::from([
+                ("price".to_string(), serde_json::json!(["$10"])),
+                ("stock".to_string(), serde_json::json!(["In stock"])),
+            ]),
+        )]);
+        let results = vec![page_with_selectors(
+            url,
+            &[("price", serde_json::json!(["$12"])), ("stock", serde_json::json!(["In stock"]))],
+        )];
+
+        let changes = diff_selector_snapshot(&mut snapshot, &results);
 
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join("test_urls_whitespace.txt");
-        let mut file = std::fs::File::create(&file_path).unwrap();
-        writeln!(file, "  https://example.com  ").unwrap();
-        writeln!(file, "\thttps://google.com\t").unwrap();
-        writeln!(file, " \t https://rust-lang.org \t ").unwrap();
-        drop(file);
+        assert_eq!(
+            changes,
+            vec![SelectorChange {
+                url: url.to_string(),
+                name: "price".to_string(),
+                old: Some(serde_json::json!(["$10"])),
+                new: Some(serde_json::json!(["$12"])),
+            }]
+        );
+        assert_eq!(snapshot[url]["price"], serde_json::json!(["$12"]));
+        assert!(selector_change_report(&changes).contains(r#"["$10"] -> ["$12"]"#));
+    }
 
-        let result = read_urls_from_file(file_path.to_str().unwrap());
-        assert!(result.is_ok());
-        let urls = result.unwrap();
-        assert_eq!(urls.len(), 3);
-        // URLs should be trimmed
-        assert_eq!(urls[0], "https://example.com");
-        assert_eq!(urls[1], "https://google.com");
-        assert_eq!(urls[2], "https://rust-lang.org");
+    #[test]
+    fn test_diff_selector_snapshot_first_run_is_baseline() {
+        let mut snapshot = SelectorSnapshot::new();
+        let results = vec![page_with_selectors("https://a.com", &[("price", serde_json::json!(["$1"]))])];
 
-        // Cleanup
-        std::fs::remove_file(&file_path).ok();
+        assert!(diff_selector_snapshot(&mut snapshot, &results).is_empty());
+        assert!(snapshot.contains_key("https://a.com"));
+        assert_eq!(selector_change_report(&[]), "No selector values changed\n");
     }
 }